@@ -0,0 +1,182 @@
+use crate::number::Number;
+
+/// A Binary Indexed Tree supporting point updates and prefix sums in O(log n).
+///
+/// `tree` is 1-indexed: `tree[k]` holds the sum of the `lowbit(k)` elements ending at
+/// position `k`, where `lowbit(k) = k & k.wrapping_neg()` is the lowest set bit of `k`. A
+/// prefix is split into O(log n) of these blocks by repeatedly clearing the lowest bit of
+/// its length, and an update reaches every block containing its position by repeatedly
+/// adding it.
+pub struct FenwickTree<T: Number> {
+    tree: Vec<T>,
+}
+
+fn lowbit(k: usize) -> usize {
+    k & k.wrapping_neg()
+}
+
+impl<T: Number> FenwickTree<T> {
+    pub fn build_empty(num_elems: usize) -> Self {
+        Self {
+            tree: vec![T::zero(); num_elems + 1],
+        }
+    }
+
+    /// Builds the tree over `a` in O(n), pushing every block into the next one containing it.
+    pub fn build(a: &[T]) -> Self {
+        let mut tree = vec![T::zero(); a.len() + 1];
+        tree[1..].copy_from_slice(a);
+        for k in 1..tree.len() {
+            let parent = k + lowbit(k);
+            if parent < tree.len() {
+                tree[parent] = tree[parent] + tree[k];
+            }
+        }
+        Self { tree }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds `delta` to the element at position `i`.
+    pub fn add(&mut self, i: usize, delta: T) {
+        assert!(i < self.len(), "Index {i} out of bounds");
+        let mut k = i + 1;
+        while k < self.tree.len() {
+            self.tree[k] = self.tree[k] + delta;
+            k += lowbit(k);
+        }
+    }
+
+    // sum of the first `count` elements
+    pub(crate) fn sum_first(&self, count: usize) -> T {
+        let mut sum = T::zero();
+        let mut k = count;
+        while k > 0 {
+            sum = sum + self.tree[k];
+            k -= lowbit(k);
+        }
+        sum
+    }
+
+    /// Returns the sum of the elements in `[0, i]`.
+    pub fn prefix_sum(&self, i: usize) -> T {
+        assert!(i < self.len(), "Index {i} out of bounds");
+        self.sum_first(i + 1)
+    }
+
+    /// Returns the sum of the elements in `[i, j]`.
+    pub fn sum(&self, i: usize, j: usize) -> T {
+        if i > j {
+            return T::zero();
+        }
+        assert!(j < self.len(), "Index {j} out of bounds");
+        self.sum_first(j + 1) - self.sum_first(i)
+    }
+
+    pub fn get(&self, i: usize) -> T {
+        self.sum(i, i)
+    }
+
+    /// Returns the smallest `i` such that `prefix_sum(i) >= prefix`, or `None` if even the
+    /// sum of all the elements is smaller.
+    ///
+    /// Prefix sums must be non-decreasing, i.e. no element is negative. The tree is
+    /// descended from the largest power of two, so the search costs O(log n) instead of the
+    /// O(log^2 n) of a binary search over `prefix_sum`.
+    pub fn lower_bound(&self, prefix: T) -> Option<usize> {
+        // pos is the longest prefix found so far whose sum is still smaller than `prefix`
+        let mut pos = 0;
+        let mut remaining = prefix;
+        let mut step = self.len().checked_next_power_of_two()?;
+        while step > 0 {
+            if pos + step <= self.len() && self.tree[pos + step] < remaining {
+                pos += step;
+                remaining = remaining - self.tree[pos];
+            }
+            step /= 2;
+        }
+        // the element right after that prefix is the first one reaching `prefix`
+        (pos < self.len()).then_some(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fenwick_tree::FenwickTree;
+
+    #[test]
+    fn test_build_and_sum() {
+        let tree = FenwickTree::build(&[3, 4, 5, 3, 8, 12, -4]);
+        assert_eq!(31, tree.prefix_sum(6));
+        assert_eq!(3, tree.prefix_sum(0));
+        assert_eq!(9, tree.sum(1, 2));
+        assert_eq!(16, tree.sum(4, 6));
+        assert_eq!(0, tree.sum(4, 3));
+        assert_eq!(12, tree.get(5));
+    }
+
+    #[test]
+    fn test_add() {
+        let mut tree = FenwickTree::build_empty(10);
+        tree.add(3, 5i64);
+        tree.add(9, 2);
+        tree.add(0, -1);
+        tree.add(3, 1);
+        assert_eq!(7, tree.sum(0, 9));
+        assert_eq!(5, tree.prefix_sum(8));
+        assert_eq!(6, tree.get(3));
+        assert_eq!(8, tree.sum(1, 9));
+    }
+
+    #[test]
+    fn test_build_matches_adds() {
+        let a: Vec<i32> = (0..37).map(|x| (x * 7919) % 23 - 11).collect();
+        let built = FenwickTree::build(&a);
+        let mut added = FenwickTree::build_empty(a.len());
+        for (i, &v) in a.iter().enumerate() {
+            added.add(i, v);
+        }
+        for i in 0..a.len() {
+            let expected: i32 = a[..=i].iter().sum();
+            assert_eq!(expected, built.prefix_sum(i));
+            assert_eq!(expected, added.prefix_sum(i));
+        }
+    }
+
+    #[test]
+    fn test_floats() {
+        let mut tree = FenwickTree::build(&[0.5, 1.25, 2.0]);
+        tree.add(1, 0.25);
+        assert_eq!(4.0, tree.sum(0, 2));
+        assert_eq!(1.5, tree.get(1));
+    }
+
+    #[test]
+    fn test_lower_bound() {
+        let tree = FenwickTree::build(&[1, 0, 2, 0, 0, 3, 1]);
+        assert_eq!(Some(0), tree.lower_bound(0));
+        assert_eq!(Some(0), tree.lower_bound(1));
+        assert_eq!(Some(2), tree.lower_bound(2));
+        assert_eq!(Some(2), tree.lower_bound(3));
+        assert_eq!(Some(5), tree.lower_bound(4));
+        assert_eq!(Some(6), tree.lower_bound(7));
+        assert_eq!(None, tree.lower_bound(8));
+        assert_eq!(None, FenwickTree::<i32>::build_empty(0).lower_bound(1));
+    }
+
+    #[test]
+    fn test_lower_bound_against_naive() {
+        let a: Vec<i64> = (0..50).map(|x| (x * 31) % 7 % 3).collect();
+        let tree = FenwickTree::build(&a);
+        for prefix in 0..=a.iter().sum::<i64>() + 1 {
+            let expected = (0..a.len()).find(|&i| a[..=i].iter().sum::<i64>() >= prefix);
+            assert_eq!(expected, tree.lower_bound(prefix));
+        }
+    }
+}
@@ -0,0 +1,9 @@
+mod fenwick_tree;
+mod number;
+mod range_fenwick_tree;
+mod range_update_fenwick_tree;
+
+pub use fenwick_tree::FenwickTree;
+pub use number::Number;
+pub use range_fenwick_tree::RangeFenwickTree;
+pub use range_update_fenwick_tree::RangeUpdateFenwickTree;
@@ -0,0 +1,37 @@
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// The values a Fenwick tree can hold: a group under addition, with multiplication by an
+/// index needed by the range-update / range-sum variant.
+///
+/// Unsigned integers are left out on purpose, since undoing an update or a range update
+/// on them needs negative values.
+pub trait Number:
+    Copy
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Neg<Output = Self>
+{
+    fn zero() -> Self;
+
+    fn from_usize(n: usize) -> Self;
+}
+
+macro_rules! impl_number {
+    ($($t:ty),*) => {
+        $(
+            impl Number for $t {
+                fn zero() -> Self {
+                    0 as $t
+                }
+
+                fn from_usize(n: usize) -> Self {
+                    n as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_number!(i8, i16, i32, i64, i128, isize, f32, f64);
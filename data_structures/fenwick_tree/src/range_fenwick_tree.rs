@@ -0,0 +1,108 @@
+use crate::fenwick_tree::FenwickTree;
+use crate::number::Number;
+
+/// A pair of Fenwick trees supporting range updates and range sums in O(log n).
+///
+/// Adding `v` to `[l, r]` changes the sum of the first `p` elements by `v * (p - l)` for
+/// `l < p <= r + 1` and by `v * (r + 1 - l)` after that. Both are written as
+/// `linear(p) * p - constant(p)` where `linear` and `constant` are prefix sums of two
+/// difference arrays, each kept in its own tree:
+/// - `linear` gets `+v` at `l` and `-v` at `r + 1`;
+/// - `constant` gets `+v * l` at `l` and `-v * (r + 1)` at `r + 1`.
+pub struct RangeFenwickTree<T: Number> {
+    linear: FenwickTree<T>,
+    constant: FenwickTree<T>,
+}
+
+impl<T: Number> RangeFenwickTree<T> {
+    pub fn build_empty(num_elems: usize) -> Self {
+        Self {
+            linear: FenwickTree::build_empty(num_elems),
+            constant: FenwickTree::build_empty(num_elems),
+        }
+    }
+
+    pub fn build(a: &[T]) -> Self {
+        let mut inst = Self::build_empty(a.len());
+        for (i, &v) in a.iter().enumerate() {
+            inst.range_add(i, i, v);
+        }
+        inst
+    }
+
+    pub fn len(&self) -> usize {
+        self.linear.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.linear.is_empty()
+    }
+
+    /// Adds `v` to every element in `[i, j]`.
+    pub fn range_add(&mut self, i: usize, j: usize, v: T) {
+        if i > j {
+            return;
+        }
+        assert!(j < self.len(), "Index {j} out of bounds");
+        self.linear.add(i, v);
+        self.constant.add(i, v * T::from_usize(i));
+        if j + 1 < self.len() {
+            self.linear.add(j + 1, -v);
+            self.constant.add(j + 1, -(v * T::from_usize(j + 1)));
+        }
+    }
+
+    // sum of the first `count` elements
+    fn sum_first(&self, count: usize) -> T {
+        self.linear.sum_first(count) * T::from_usize(count) - self.constant.sum_first(count)
+    }
+
+    /// Returns the sum of the elements in `[i, j]`.
+    pub fn sum(&self, i: usize, j: usize) -> T {
+        if i > j {
+            return T::zero();
+        }
+        assert!(j < self.len(), "Index {j} out of bounds");
+        self.sum_first(j + 1) - self.sum_first(i)
+    }
+
+    pub fn get(&self, i: usize) -> T {
+        self.sum(i, i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::range_fenwick_tree::RangeFenwickTree;
+
+    #[test]
+    fn test_range_add_and_sum() {
+        let mut tree = RangeFenwickTree::build(&[1, 2, 3, 4, 5]);
+        tree.range_add(1, 3, 10);
+        tree.range_add(0, 4, -1);
+        // [0, 11, 12, 13, 4]
+        assert_eq!(40, tree.sum(0, 4));
+        assert_eq!(36, tree.sum(1, 3));
+        assert_eq!(12, tree.get(2));
+        assert_eq!(17, tree.sum(3, 4));
+    }
+
+    #[test]
+    fn test_against_naive() {
+        let n = 23;
+        let mut a = vec![0i64; n];
+        let mut tree = RangeFenwickTree::build_empty(n);
+        for step in 0..40 {
+            let (i, j) = ((step * 7) % n, (step * 13) % n);
+            let (i, j) = (i.min(j), i.max(j));
+            let v = (step as i64 * 5) % 11 - 5;
+            tree.range_add(i, j, v);
+            a[i..=j].iter_mut().for_each(|x| *x += v);
+            for l in 0..n {
+                for r in l..n {
+                    assert_eq!(a[l..=r].iter().sum::<i64>(), tree.sum(l, r));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::fenwick_tree::FenwickTree;
+use crate::number::Number;
+
+/// A Fenwick tree supporting range updates and point queries in O(log n).
+///
+/// It stores the difference array `d[i] = a[i] - a[i - 1]`: adding `v` to `[i, j]` only
+/// changes `d[i]` and `d[j + 1]`, and `a[i]` is the prefix sum of `d` up to `i`.
+pub struct RangeUpdateFenwickTree<T: Number> {
+    diff: FenwickTree<T>,
+}
+
+impl<T: Number> RangeUpdateFenwickTree<T> {
+    pub fn build_empty(num_elems: usize) -> Self {
+        Self {
+            diff: FenwickTree::build_empty(num_elems),
+        }
+    }
+
+    pub fn build(a: &[T]) -> Self {
+        let diff: Vec<T> = (0..a.len())
+            .map(|i| if i == 0 { a[0] } else { a[i] - a[i - 1] })
+            .collect();
+        Self {
+            diff: FenwickTree::build(&diff),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.diff.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diff.is_empty()
+    }
+
+    /// Adds `v` to every element in `[i, j]`.
+    pub fn range_add(&mut self, i: usize, j: usize, v: T) {
+        if i > j {
+            return;
+        }
+        assert!(j < self.len(), "Index {j} out of bounds");
+        self.diff.add(i, v);
+        if j + 1 < self.len() {
+            self.diff.add(j + 1, -v);
+        }
+    }
+
+    pub fn get(&self, i: usize) -> T {
+        self.diff.prefix_sum(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::range_update_fenwick_tree::RangeUpdateFenwickTree;
+
+    #[test]
+    fn test_range_add() {
+        let mut tree = RangeUpdateFenwickTree::build(&[1, 2, 3, 4, 5]);
+        tree.range_add(1, 3, 10);
+        tree.range_add(3, 4, -1);
+        let values: Vec<i32> = (0..5).map(|i| tree.get(i)).collect();
+        assert_eq!(vec![1, 12, 13, 13, 4], values);
+    }
+
+    #[test]
+    fn test_update_the_array_sample() {
+        // SPOJ UPDATEIT: n = 5, updates (0, 1, 7), (2, 4, 6), (1, 3, 2)
+        let mut tree = RangeUpdateFenwickTree::build_empty(5);
+        tree.range_add(0, 1, 7i64);
+        tree.range_add(2, 4, 6);
+        tree.range_add(1, 3, 2);
+        assert_eq!(7, tree.get(0));
+        assert_eq!(8, tree.get(3));
+        assert_eq!(6, tree.get(4));
+    }
+}
@@ -0,0 +1,378 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// A cycle with a negative total cost is reachable from the source, so some nodes have no
+/// shortest path: `cycle` lists its nodes in order, each with an edge to the next one and
+/// the last with an edge to the first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegativeCycleError {
+    pub cycle: Vec<usize>,
+}
+
+impl Display for NegativeCycleError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // the field is public, so the cycle may have been built empty
+        let Some(first) = self.cycle.first() else {
+            return write!(f, "The graph has a negative cycle");
+        };
+        write!(f, "The graph has a negative cycle:")?;
+        for node in &self.cycle {
+            write!(f, " {node} ->")?;
+        }
+        write!(f, " {first}")
+    }
+}
+
+impl Error for NegativeCycleError {}
+
+/// A weighted graph, possibly with cycles, either directed or undirected.
+///
+/// The shortest path functions return, for each node, `Some((parent, cost))` with the
+/// previous node on a shortest path from the source and the cost of that path, or `None`
+/// if the node is not reachable. The source is its own parent, as in `Dag`'s functions, so
+/// `extract_path` works on their output.
+pub struct Graph {
+    adj_lists: Vec<Vec<(usize, i64)>>,
+    directed: bool,
+}
+
+impl Graph {
+    pub fn new(num_nodes: usize, directed: bool) -> Self {
+        if num_nodes == 0 {
+            panic!("Num nodes should be > 0");
+        }
+        Self {
+            adj_lists: vec![Vec::new(); num_nodes],
+            directed,
+        }
+    }
+
+    pub fn from_edges_weighted(
+        num_nodes: usize,
+        directed: bool,
+        edges: &[(usize, usize, i64)],
+    ) -> Self {
+        let mut inst = Self::new(num_nodes, directed);
+        for &(s, d, w) in edges {
+            inst.add_edge(s, d, w);
+        }
+        inst
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.adj_lists.len()
+    }
+
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    /// Adds the edge `s -> e` with cost `w`, and `e -> s` too if the graph is undirected.
+    pub fn add_edge(&mut self, s: usize, e: usize, w: i64) {
+        self.adj_lists[s].push((e, w));
+        if !self.directed && s != e {
+            self.adj_lists[e].push((s, w));
+        }
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (usize, usize, i64)> + '_ {
+        self.adj_lists
+            .iter()
+            .enumerate()
+            .flat_map(|(s, adj_list)| adj_list.iter().map(move |&(e, w)| (s, e, w)))
+    }
+
+    /// Shortest paths from `source` in O((n + m) log n) with Dijkstra's algorithm: nodes are
+    /// settled by increasing distance, taking them from a binary heap.
+    ///
+    /// # Panics
+    /// if some edge has a negative cost.
+    pub fn dijkstra(&self, source: usize) -> Vec<Option<(usize, i64)>> {
+        assert!(
+            self.edges().all(|(_, _, w)| w >= 0),
+            "Dijkstra needs non-negative costs"
+        );
+        let mut path = vec![None; self.num_nodes()];
+        path[source] = Some((source, 0));
+        let mut heap = BinaryHeap::new();
+        heap.push(Reverse((0, source)));
+        while let Some(Reverse((cost, node))) = heap.pop() {
+            // the node has already been settled with a smaller cost
+            if path[node].is_some_and(|(_, best)| best < cost) {
+                continue;
+            }
+            for &(neighbor, weight) in &self.adj_lists[node] {
+                let new_cost = cost + weight;
+                if path[neighbor].is_none_or(|(_, old_cost)| new_cost < old_cost) {
+                    path[neighbor] = Some((node, new_cost));
+                    heap.push(Reverse((new_cost, neighbor)));
+                }
+            }
+        }
+        path
+    }
+
+    /// Shortest paths from `source` in O(n + m) when every edge costs 0 or 1: a deque plays
+    /// the role of Dijkstra's heap, with nodes reached by a 0 edge pushed in front.
+    ///
+    /// # Panics
+    /// if some edge costs neither 0 nor 1.
+    pub fn zero_one_bfs(&self, source: usize) -> Vec<Option<(usize, i64)>> {
+        assert!(
+            self.edges().all(|(_, _, w)| w == 0 || w == 1),
+            "0-1 BFS needs costs equal to 0 or 1"
+        );
+        let mut path = vec![None; self.num_nodes()];
+        path[source] = Some((source, 0));
+        let mut deque = VecDeque::new();
+        deque.push_back((0, source));
+        while let Some((cost, node)) = deque.pop_front() {
+            if path[node].is_some_and(|(_, best)| best < cost) {
+                continue;
+            }
+            for &(neighbor, weight) in &self.adj_lists[node] {
+                let new_cost = cost + weight;
+                if path[neighbor].is_none_or(|(_, old_cost)| new_cost < old_cost) {
+                    path[neighbor] = Some((node, new_cost));
+                    if weight == 0 {
+                        deque.push_front((new_cost, neighbor));
+                    } else {
+                        deque.push_back((new_cost, neighbor));
+                    }
+                }
+            }
+        }
+        path
+    }
+
+    /// Shortest paths from `source` in O(n m) with the Bellman–Ford algorithm, which allows
+    /// negative costs: the `k`-th round of relaxations finds every shortest path with `k`
+    /// edges, and it stops as soon as a round changes nothing.
+    ///
+    /// A relaxation in round `n` means that some path keeps improving past `n - 1` edges,
+    /// which only happens if a negative cycle is reachable from `source`; that cycle is
+    /// returned as the error. In an undirected graph any negative edge is such a cycle.
+    pub fn bellman_ford(
+        &self,
+        source: usize,
+    ) -> Result<Vec<Option<(usize, i64)>>, NegativeCycleError> {
+        let n = self.num_nodes();
+        let mut cost: Vec<Option<i64>> = vec![None; n];
+        // the source has no parent here, so that walking back never loops on it
+        let mut parent: Vec<Option<usize>> = vec![None; n];
+        cost[source] = Some(0);
+        for round in 1..=n {
+            let mut last_relaxed = None;
+            for (s, e, w) in self.edges() {
+                let Some(cost_s) = cost[s] else {
+                    continue;
+                };
+                if cost[e].is_none_or(|old_cost| cost_s + w < old_cost) {
+                    cost[e] = Some(cost_s + w);
+                    parent[e] = Some(s);
+                    last_relaxed = Some(e);
+                }
+            }
+            let Some(relaxed) = last_relaxed else {
+                break;
+            };
+            if round == n {
+                return Err(NegativeCycleError {
+                    cycle: Self::cycle_through_parents(&parent, relaxed),
+                });
+            }
+        }
+        Ok((0..n)
+            .map(|node| {
+                let node_parent = if node == source {
+                    Some(source)
+                } else {
+                    parent[node]
+                };
+                node_parent.zip(cost[node])
+            })
+            .collect())
+    }
+
+    // Walking back n times from a node relaxed in the last round lands on the negative cycle,
+    // since its chain of parents is at least n edges long.
+    fn cycle_through_parents(parent: &[Option<usize>], relaxed: usize) -> Vec<usize> {
+        let mut on_cycle = relaxed;
+        for _ in 0..parent.len() {
+            on_cycle = parent[on_cycle].unwrap();
+        }
+        let mut cycle = vec![on_cycle];
+        let mut node = parent[on_cycle].unwrap();
+        while node != on_cycle {
+            cycle.push(node);
+            node = parent[node].unwrap();
+        }
+        cycle.reverse();
+        cycle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extract_path;
+    use crate::graph::{Graph, NegativeCycleError};
+
+    // checks that the cycle exists in the graph and costs less than zero
+    fn assert_negative_cycle(graph: &Graph, cycle: &[usize]) {
+        let mut total = 0;
+        for (i, &node) in cycle.iter().enumerate() {
+            let next = cycle[(i + 1) % cycle.len()];
+            total += graph.adj_lists[node]
+                .iter()
+                .filter(|&&(neighbor, _)| neighbor == next)
+                .map(|&(_, w)| w)
+                .min()
+                .unwrap();
+        }
+        assert!(total < 0);
+    }
+
+    #[test]
+    fn test_dijkstra() {
+        let graph = Graph::from_edges_weighted(
+            5,
+            true,
+            &[
+                (0, 1, 4),
+                (0, 2, 1),
+                (2, 1, 2),
+                (1, 3, 1),
+                (2, 3, 5),
+                (3, 0, 1),
+            ],
+        );
+        let path = graph.dijkstra(0);
+        assert_eq!(Some((0, 0)), path[0]);
+        assert_eq!(Some((2, 3)), path[1]);
+        assert_eq!(Some((1, 4)), path[3]);
+        assert_eq!(None, path[4]);
+        assert_eq!(Some((vec![0, 2, 1, 3], 4)), extract_path(&path, 0, 3));
+        assert_eq!(Ok(path), graph.bellman_ford(0));
+    }
+
+    #[test]
+    fn test_undirected() {
+        let graph =
+            Graph::from_edges_weighted(4, false, &[(0, 1, 1), (1, 2, 0), (2, 3, 1), (3, 0, 1)]);
+        let path = graph.zero_one_bfs(2);
+        assert_eq!(Some((vec![2, 1, 0], 1)), extract_path(&path, 2, 0));
+        assert_eq!(Some((2, 1)), path[3]);
+        assert_eq!(path, graph.dijkstra(2));
+    }
+
+    #[test]
+    fn test_zero_one_bfs() {
+        // moving right is free, moving back costs 1
+        let edges: Vec<(usize, usize, i64)> = (0..6)
+            .flat_map(|i| [(i, i + 1, 0), (i + 1, i, 1)])
+            .chain([(6, 2, 0)])
+            .collect();
+        let graph = Graph::from_edges_weighted(7, true, &edges);
+        assert_eq!(Some((5, 0)), graph.zero_one_bfs(0)[6]);
+        // going back from 3 to 2 is free through 6
+        let path = graph.zero_one_bfs(3);
+        assert_eq!(Some((vec![3, 4, 5, 6, 2], 0)), extract_path(&path, 3, 2));
+        assert_eq!(Some((2, 1)), path[1]);
+        assert_eq!(path, graph.dijkstra(3));
+    }
+
+    #[test]
+    fn test_bellman_ford_negative_costs() {
+        let graph = Graph::from_edges_weighted(
+            5,
+            true,
+            &[
+                (0, 1, 5),
+                (0, 2, 2),
+                (1, 3, -4),
+                (2, 3, 3),
+                (3, 4, 1),
+                (4, 2, 0),
+            ],
+        );
+        let path = graph.bellman_ford(0).unwrap();
+        assert_eq!(Some((vec![0, 1, 3], 1)), extract_path(&path, 0, 3));
+        assert_eq!(Some((vec![0, 1, 3, 4], 2)), extract_path(&path, 0, 4));
+        assert_eq!(Some((0, 2)), path[2]);
+    }
+
+    #[test]
+    fn test_negative_cycle() {
+        let graph = Graph::from_edges_weighted(
+            6,
+            true,
+            &[
+                (0, 1, 1),
+                (1, 2, 1),
+                (2, 3, -2),
+                (3, 1, 0),
+                (3, 4, 1),
+                (5, 0, -100),
+            ],
+        );
+        let error = graph.bellman_ford(0).unwrap_err();
+        assert_eq!(3, error.cycle.len());
+        assert_negative_cycle(&graph, &error.cycle);
+        // the cycle is not reachable from 4
+        assert_eq!(Some((4, 0)), graph.bellman_ford(4).unwrap()[4]);
+
+        let undirected = Graph::from_edges_weighted(3, false, &[(0, 1, 2), (1, 2, -1)]);
+        let error = undirected.bellman_ford(0).unwrap_err();
+        assert_eq!(2, error.cycle.len());
+        assert_negative_cycle(&undirected, &error.cycle);
+        assert!(
+            error
+                .to_string()
+                .starts_with("The graph has a negative cycle:")
+        );
+        let empty = NegativeCycleError { cycle: vec![] };
+        assert_eq!("The graph has a negative cycle", empty.to_string());
+    }
+
+    #[test]
+    fn test_dijkstra_improves_reached_nodes() {
+        // 1 and 3 are first reached by expensive edges, and found again later through cheaper
+        // paths: the stale entries left in the heap must be skipped
+        let graph = Graph::from_edges_weighted(
+            5,
+            true,
+            &[
+                (0, 1, 10),
+                (0, 2, 1),
+                (2, 1, 1),
+                (2, 1, 7),
+                (1, 3, 1),
+                (2, 3, 5),
+                (3, 4, 0),
+                (4, 2, 0),
+            ],
+        );
+        let path = graph.dijkstra(0);
+        assert_eq!(
+            vec![
+                Some((0, 0)),
+                Some((2, 2)),
+                Some((0, 1)),
+                Some((1, 3)),
+                Some((3, 3))
+            ],
+            path
+        );
+        assert_eq!(Some((vec![0, 2, 1, 3, 4], 3)), extract_path(&path, 0, 4));
+        assert_eq!(Ok(path), graph.bellman_ford(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "non-negative")]
+    fn test_dijkstra_negative_cost() {
+        Graph::from_edges_weighted(2, true, &[(0, 1, -1)]).dijkstra(0);
+    }
+}
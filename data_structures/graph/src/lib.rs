@@ -1,3 +1,34 @@
+mod graph;
+
+pub use graph::{Graph, NegativeCycleError};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// The graph is not acyclic: `cycle` lists the nodes of one of its cycles in order, each
+/// with an edge to the next one and the last with an edge to the first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    pub cycle: Vec<usize>,
+}
+
+impl Display for CycleError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // the field is public, so the cycle may have been built empty
+        let Some(first) = self.cycle.first() else {
+            return write!(f, "The graph has a cycle");
+        };
+        write!(f, "The graph has a cycle:")?;
+        for node in &self.cycle {
+            write!(f, " {node} ->")?;
+        }
+        write!(f, " {first}")
+    }
+}
+
+impl Error for CycleError {}
+
 pub struct Dag {
     adj_lists: Vec<Vec<(usize, u32)>>,
 }
@@ -12,7 +43,7 @@ impl Dag {
         }
     }
 
-    fn from_edges_weighted(num_nodes: usize, edges: &[(usize, usize, u32)]) -> Self {
+    pub fn from_edges_weighted(num_nodes: usize, edges: &[(usize, usize, u32)]) -> Self {
         let mut inst = Self::new(num_nodes);
         for &(s, d, w) in edges {
             inst.add_edge(s, d, w);
@@ -20,27 +51,113 @@ impl Dag {
         inst
     }
 
+    /// Adds the edge `s -> e` without checking that the graph stays acyclic: a cycle will
+    /// be reported by `topological_sort`.
     pub fn add_edge(&mut self, s: usize, e: usize, w: u32) {
         self.adj_lists[s].push((e, w));
     }
 
-    fn topological_sort(&self) -> Vec<usize> {
-        let mut queue = std::collections::VecDeque::new();
-        let mut order = Vec::new();
-        let mut visited = vec![false; self.adj_lists.len()];
-        queue.push_back(0usize);
+    /// Adds the edge `s -> e` only if it does not close a cycle, in O(n + m).
+    pub fn try_add_edge(&mut self, s: usize, e: usize, w: u32) -> Result<(), CycleError> {
+        if let Some(mut path) = self.find_path(e, s) {
+            // the new edge s -> e closes the path e -> ... -> s
+            path.rotate_right(1);
+            return Err(CycleError { cycle: path });
+        }
+        self.add_edge(s, e, w);
+        Ok(())
+    }
+
+    // returns the nodes of a path from `from` to `to`, both included, if any
+    fn find_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut parent = vec![None; self.adj_lists.len()];
+        parent[from] = Some(from);
+        let mut stack = vec![from];
+        while let Some(node_id) = stack.pop() {
+            if node_id == to {
+                let mut path = vec![to];
+                let mut current = to;
+                while current != from {
+                    current = parent[current]?;
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &(neighbor, _) in &self.adj_lists[node_id] {
+                if parent[neighbor].is_none() {
+                    parent[neighbor] = Some(node_id);
+                    stack.push(neighbor);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns all the nodes in an order where every edge goes from a node to a later one,
+    /// or a cycle that makes such an order impossible.
+    ///
+    /// Kahn's algorithm: a node is emitted once all its predecessors have been, i.e. when
+    /// its remaining in-degree drops to zero.
+    pub fn topological_sort(&self) -> Result<Vec<usize>, CycleError> {
+        let num_nodes = self.adj_lists.len();
+        let mut in_degree = vec![0usize; num_nodes];
+        for adj_list in &self.adj_lists {
+            for &(neighbor, _) in adj_list {
+                in_degree[neighbor] += 1;
+            }
+        }
+        let mut queue: VecDeque<usize> = (0..num_nodes)
+            .filter(|&node_id| in_degree[node_id] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(num_nodes);
         while let Some(node_id) = queue.pop_front() {
-            if visited[node_id] == false {
-                for &(neighbor, _) in &self.adj_lists[node_id] {
-                    if visited[node_id] == false {
-                        queue.push_back(neighbor);
-                    }
+            order.push(node_id);
+            for &(neighbor, _) in &self.adj_lists[node_id] {
+                in_degree[neighbor] -= 1;
+                if in_degree[neighbor] == 0 {
+                    queue.push_back(neighbor);
                 }
-                visited[node_id] = true;
-                order.push(node_id);
             }
         }
-        order
+        if order.len() == num_nodes {
+            Ok(order)
+        } else {
+            Err(self.find_cycle(&in_degree))
+        }
+    }
+
+    // Every node left with a positive in-degree by Kahn's algorithm has a predecessor that
+    // is left too, so walking back along those predecessors must eventually repeat a node.
+    fn find_cycle(&self, in_degree: &[usize]) -> CycleError {
+        let mut predecessor = vec![None; self.adj_lists.len()];
+        for (node_id, adj_list) in self.adj_lists.iter().enumerate() {
+            if in_degree[node_id] == 0 {
+                continue;
+            }
+            for &(neighbor, _) in adj_list {
+                if in_degree[neighbor] > 0 {
+                    predecessor[neighbor] = Some(node_id);
+                }
+            }
+        }
+        let mut seen = vec![false; self.adj_lists.len()];
+        let mut current = (0..in_degree.len())
+            .find(|&node_id| in_degree[node_id] > 0)
+            .unwrap();
+        while !seen[current] {
+            seen[current] = true;
+            current = predecessor[current].unwrap();
+        }
+        // current is on the cycle: walk it once more to collect it
+        let mut cycle = vec![current];
+        let mut node_id = predecessor[current].unwrap();
+        while node_id != current {
+            cycle.push(node_id);
+            node_id = predecessor[node_id].unwrap();
+        }
+        cycle.reverse();
+        CycleError { cycle }
     }
 }
 
@@ -48,13 +165,13 @@ pub fn best_paths_from_source<F>(
     dag: &Dag,
     source: usize,
     better: F,
-) -> Vec<Option<(usize, u32)>>
+) -> Result<Vec<Option<(usize, u32)>>, CycleError>
 where
     F: Fn(u32, u32) -> bool + Copy,
 {
     let mut path = vec![None; dag.adj_lists.len()];
     path[source] = Some((source, 0)); // parent=source per coerenza
-    let topological_sort = dag.topological_sort();
+    let topological_sort = dag.topological_sort()?;
 
     for node in topological_sort {
         for &(neighbor, weight) in &dag.adj_lists[node] {
@@ -72,26 +189,31 @@ where
             }
         }
     }
-    path
+    Ok(path)
 }
 
-pub fn minimum_paths_cost(dag: &Dag, source: usize)
-                          -> Vec<Option<(usize, u32)>>
-{
+pub fn minimum_paths_cost(
+    dag: &Dag,
+    source: usize,
+) -> Result<Vec<Option<(usize, u32)>>, CycleError> {
     best_paths_from_source(dag, source, |new, old| new < old)
 }
 
-pub fn maximum_paths_beneficial(dag: &Dag, source: usize)
-                                -> Vec<Option<(usize, u32)>>
-{
+pub fn maximum_paths_beneficial(
+    dag: &Dag,
+    source: usize,
+) -> Result<Vec<Option<(usize, u32)>>, CycleError> {
     best_paths_from_source(dag, source, |new, old| new > old)
 }
 
-pub fn extract_path(
-    path_info: &[Option<(usize, u32)>],
+/// Follows the parents stored in `path_info` back from `dest` to `source`, returning the
+/// path and its cost. Works on the output of any shortest path function of this crate,
+/// whatever the type of its costs.
+pub fn extract_path<C: Copy>(
+    path_info: &[Option<(usize, C)>],
     source: usize,
     dest: usize,
-) -> Option<(Vec<usize>, u32)> {
+) -> Option<(Vec<usize>, C)> {
     let (_, total_cost) = path_info[dest]?;
 
     let mut path = Vec::new();
@@ -112,36 +234,114 @@ pub fn extract_path(
 pub fn minimum_paths_cost_from_to(
     dag: &Dag,
     source: usize,
-    dest: usize
-) -> Option<(Vec<usize>, u32)> {
-    extract_path(&minimum_paths_cost(dag, source), source, dest)
+    dest: usize,
+) -> Result<Option<(Vec<usize>, u32)>, CycleError> {
+    Ok(extract_path(
+        &minimum_paths_cost(dag, source)?,
+        source,
+        dest,
+    ))
 }
 
 pub fn maximum_path_beneficial_from_to(
     dag: &Dag,
     source: usize,
-    dest: usize
-) -> Option<(Vec<usize>, u32)> {
-    extract_path(&maximum_paths_beneficial(dag, source), source, dest)
+    dest: usize,
+) -> Result<Option<(Vec<usize>, u32)>, CycleError> {
+    Ok(extract_path(
+        &maximum_paths_beneficial(dag, source)?,
+        source,
+        dest,
+    ))
 }
 
-
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_two_paths() {
-        let dag = Dag::from_edges_weighted(4, &[
-            (0, 1, 10),
-            (1, 3, 10),
-            (0, 2, 1),
-            (2, 3, 1),
-        ]);
-
-        let (path, cost) = minimum_paths_cost_from_to(&dag, 0, 3).unwrap();
+        let dag = Dag::from_edges_weighted(4, &[(0, 1, 10), (1, 3, 10), (0, 2, 1), (2, 3, 1)]);
+
+        let (path, cost) = minimum_paths_cost_from_to(&dag, 0, 3).unwrap().unwrap();
         assert_eq!(path, vec![0, 2, 3]);
         assert_eq!(cost, 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_relaxes_in_topological_order() {
+        // a BFS from 0 would visit 1 before 2 and miss the path through 2
+        let dag = Dag::from_edges_weighted(3, &[(0, 1, 10), (0, 2, 1), (2, 1, 1)]);
+        assert_eq!(Ok(vec![0, 2, 1]), dag.topological_sort());
+        let (path, cost) = minimum_paths_cost_from_to(&dag, 0, 1).unwrap().unwrap();
+        assert_eq!(path, vec![0, 2, 1]);
+        assert_eq!(cost, 2);
+        let (path, cost) = maximum_path_beneficial_from_to(&dag, 0, 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(path, vec![0, 1]);
+        assert_eq!(cost, 10);
+    }
+
+    #[test]
+    fn test_unreachable_nodes_are_sorted() {
+        let dag = Dag::from_edges_weighted(5, &[(3, 4, 1), (4, 0, 1), (1, 2, 1)]);
+        let order = dag.topological_sort().unwrap();
+        assert_eq!(5, order.len());
+        let position = |node: usize| order.iter().position(|&x| x == node).unwrap();
+        assert!(position(3) < position(4) && position(4) < position(0));
+        assert!(position(1) < position(2));
+        assert_eq!(None, minimum_paths_cost_from_to(&dag, 0, 4).unwrap());
+    }
+
+    #[test]
+    fn test_cycle_witness() {
+        let dag = Dag::from_edges_weighted(
+            6,
+            &[
+                (0, 1, 1),
+                (1, 2, 1),
+                (2, 3, 1),
+                (3, 1, 1),
+                (3, 4, 1),
+                (5, 0, 1),
+            ],
+        );
+        let error = dag.topological_sort().unwrap_err();
+        let cycle = &error.cycle;
+        assert_eq!(3, cycle.len());
+        for (i, &node) in cycle.iter().enumerate() {
+            let next = cycle[(i + 1) % cycle.len()];
+            assert!(
+                dag.adj_lists[node]
+                    .iter()
+                    .any(|&(neighbor, _)| neighbor == next)
+            );
+        }
+        assert!(minimum_paths_cost(&dag, 0).is_err());
+    }
+
+    #[test]
+    fn test_try_add_edge() {
+        let mut dag = Dag::new(4);
+        assert_eq!(Ok(()), dag.try_add_edge(0, 1, 1));
+        assert_eq!(Ok(()), dag.try_add_edge(1, 2, 1));
+        assert_eq!(Ok(()), dag.try_add_edge(0, 2, 1));
+        let error = dag.try_add_edge(2, 0, 1).unwrap_err();
+        // the new edge and the path 0 -> 2 already in the graph
+        assert_eq!(vec![2, 0], error.cycle);
+        assert_eq!("The graph has a cycle: 2 -> 0 -> 2", error.to_string());
+        assert_eq!(
+            Err(CycleError { cycle: vec![3] }),
+            dag.try_add_edge(3, 3, 1)
+        );
+        // rejected edges are not added
+        assert!(dag.topological_sort().is_ok());
+    }
+
+    #[test]
+    fn test_empty_cycle_display() {
+        let error = CycleError { cycle: vec![] };
+        assert_eq!("The graph has a cycle", error.to_string());
+    }
+}
@@ -0,0 +1,389 @@
+use segment_tree::{LazyAction, LazySegmentTree, Monoid, Update, Value};
+use std::mem;
+
+/// A rooted tree whose node values are kept in a [`LazySegmentTree`], so that the values on
+/// a path or in a subtree can be aggregated and updated in O(log² n).
+///
+/// Every node marks as heavy the child with the largest subtree, and the heavy edges split
+/// the tree into chains. Going up from any node crosses at most O(log n) light edges, since
+/// the subtree size at least doubles at each of them, so every path is the union of
+/// O(log n) pieces of chains. The nodes are numbered visiting the heavy child first: each
+/// chain gets contiguous positions, and so does each subtree.
+///
+/// Paths are aggregated chain by chain, in no particular order: the merge of the value
+/// monoid must be commutative (sum, max, min, ...).
+pub struct HeavyLightDecomposition<A: LazyAction> {
+    parent: Vec<usize>,
+    depth: Vec<usize>,
+    subtree_size: Vec<usize>,
+    // the topmost node of the chain of each node
+    head: Vec<usize>,
+    // the position of each node in the segment tree
+    pos: Vec<usize>,
+    st: LazySegmentTree<A>,
+}
+
+impl<A: LazyAction> HeavyLightDecomposition<A> {
+    /// Builds the decomposition of the tree described by the undirected adjacency lists
+    /// `adj`, rooted in `root`, where node `u` holds `values[u]`.
+    ///
+    /// # Panics
+    /// if some node is not reachable from the root.
+    pub fn from_adjacency(adj: &[Vec<usize>], root: usize, values: &[Value<A>]) -> Self {
+        let n = adj.len();
+        let mut children = vec![Vec::new(); n];
+        let mut visited = vec![false; n];
+        visited[root] = true;
+        let mut stack = vec![root];
+        while let Some(u) = stack.pop() {
+            for &v in &adj[u] {
+                if !visited[v] {
+                    visited[v] = true;
+                    children[u].push(v);
+                    stack.push(v);
+                }
+            }
+        }
+        Self::build(&children, root, values)
+    }
+
+    /// Builds the decomposition of a binary tree stored in an arena as `handson1::tree::Tree`
+    /// does: `children[u]` holds the ids of the left and the right child of node `u`, and
+    /// the root is node 0.
+    ///
+    /// # Panics
+    /// if some node is not reachable from the root, or is the child of more than one node.
+    pub fn from_children(children: &[(Option<usize>, Option<usize>)], values: &[Value<A>]) -> Self {
+        let children: Vec<Vec<usize>> = children
+            .iter()
+            .map(|&(id_left, id_right)| id_left.into_iter().chain(id_right).collect())
+            .collect();
+        Self::build(&children, 0, values)
+    }
+
+    fn build(children: &[Vec<usize>], root: usize, values: &[Value<A>]) -> Self {
+        let n = children.len();
+        assert_eq!(
+            n,
+            values.len(),
+            "Expected a value for each of the {n} nodes"
+        );
+        let mut parent = vec![root; n];
+        let mut depth = vec![0; n];
+        // a preorder visit, so that every child comes after its parent
+        let mut order = Vec::with_capacity(n);
+        let mut visited = vec![false; n];
+        visited[root] = true;
+        let mut stack = vec![root];
+        while let Some(u) = stack.pop() {
+            order.push(u);
+            for &v in &children[u] {
+                // this also stops a cycle from being visited forever
+                assert!(!visited[v], "Node {v} is reached twice from the root");
+                visited[v] = true;
+                parent[v] = u;
+                depth[v] = depth[u] + 1;
+                stack.push(v);
+            }
+        }
+        if let Some(u) = visited.iter().position(|&seen| !seen) {
+            panic!("Node {u} is not reachable from the root");
+        }
+        let mut subtree_size = vec![1; n];
+        for &u in order.iter().rev().filter(|&&u| u != root) {
+            subtree_size[parent[u]] += subtree_size[u];
+        }
+
+        let mut head = vec![root; n];
+        let mut pos = vec![0; n];
+        let mut stack = vec![root];
+        let mut next_pos = 0;
+        while let Some(u) = stack.pop() {
+            pos[u] = next_pos;
+            next_pos += 1;
+            let heavy = children[u].iter().copied().max_by_key(|&v| subtree_size[v]);
+            // the heavy child is pushed last so that it is numbered right after u
+            for &v in children[u].iter().filter(|&&v| Some(v) != heavy) {
+                head[v] = v;
+                stack.push(v);
+            }
+            if let Some(v) = heavy {
+                head[v] = head[u];
+                stack.push(v);
+            }
+        }
+
+        let mut positioned_values = vec![A::ValueMonoid::identity(); n];
+        for (u, value) in values.iter().enumerate() {
+            positioned_values[pos[u]] = value.clone();
+        }
+        Self {
+            parent,
+            depth,
+            subtree_size,
+            head,
+            pos,
+            st: LazySegmentTree::build(&positioned_values),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    /// Returns the position of node `u` in the underlying segment tree.
+    pub fn position(&self, u: usize) -> usize {
+        self.pos[u]
+    }
+
+    /// Returns the O(log n) ranges `[i, j]` of segment tree positions whose union is the
+    /// path between `u` and `v`, both included.
+    pub fn path_ranges(&self, mut u: usize, mut v: usize) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::new();
+        // climb from the node whose chain starts deeper, until both are on the same chain
+        while self.head[u] != self.head[v] {
+            if self.depth[self.head[u]] < self.depth[self.head[v]] {
+                mem::swap(&mut u, &mut v);
+            }
+            ranges.push((self.pos[self.head[u]], self.pos[u]));
+            u = self.parent[self.head[u]];
+        }
+        let (i, j) = (self.pos[u].min(self.pos[v]), self.pos[u].max(self.pos[v]));
+        ranges.push((i, j));
+        ranges
+    }
+
+    /// Returns the range `[i, j]` of segment tree positions of the subtree of `u`.
+    pub fn subtree_range(&self, u: usize) -> (usize, usize) {
+        (self.pos[u], self.pos[u] + self.subtree_size[u] - 1)
+    }
+
+    /// Returns the lowest common ancestor of `u` and `v` in O(log n).
+    pub fn lca(&self, mut u: usize, mut v: usize) -> usize {
+        while self.head[u] != self.head[v] {
+            if self.depth[self.head[u]] < self.depth[self.head[v]] {
+                mem::swap(&mut u, &mut v);
+            }
+            u = self.parent[self.head[u]];
+        }
+        if self.depth[u] < self.depth[v] { u } else { v }
+    }
+
+    /// Aggregates the values of the nodes on the path between `u` and `v`.
+    pub fn path_query(&mut self, u: usize, v: usize) -> Value<A> {
+        self.path_ranges(u, v)
+            .into_iter()
+            .fold(A::ValueMonoid::identity(), |acc, (i, j)| {
+                A::ValueMonoid::combine(&acc, &self.st.query(i, j))
+            })
+    }
+
+    /// Applies `update` to every node on the path between `u` and `v`.
+    pub fn path_update(&mut self, u: usize, v: usize, update: Update<A>) {
+        for (i, j) in self.path_ranges(u, v) {
+            self.st.range_update(i, j, update.clone());
+        }
+    }
+
+    /// Aggregates the values of the nodes in the subtree of `u`.
+    pub fn subtree_query(&mut self, u: usize) -> Value<A> {
+        let (i, j) = self.subtree_range(u);
+        self.st.query(i, j)
+    }
+
+    /// Applies `update` to every node in the subtree of `u`.
+    pub fn subtree_update(&mut self, u: usize, update: Update<A>) {
+        let (i, j) = self.subtree_range(u);
+        self.st.range_update(i, j, update);
+    }
+
+    pub fn get(&mut self, u: usize) -> Value<A> {
+        self.st.get(self.pos[u])
+    }
+
+    /// Replaces the value of node `u` with `value`.
+    pub fn update(&mut self, u: usize, value: Value<A>) {
+        self.st.update(self.pos[u], value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heavy_light_decomposition::HeavyLightDecomposition;
+    use segment_tree::{LazyAction, Monoid};
+
+    struct Sum;
+
+    impl Monoid for Sum {
+        type Value = i64;
+
+        fn identity() -> i64 {
+            0
+        }
+
+        fn combine(a: &i64, b: &i64) -> i64 {
+            a + b
+        }
+    }
+
+    struct Max;
+
+    impl Monoid for Max {
+        type Value = i64;
+
+        fn identity() -> i64 {
+            i64::MIN
+        }
+
+        fn combine(a: &i64, b: &i64) -> i64 {
+            *a.max(b)
+        }
+    }
+
+    struct Add;
+
+    impl Monoid for Add {
+        type Value = i64;
+
+        fn identity() -> i64 {
+            0
+        }
+
+        fn combine(older: &i64, newer: &i64) -> i64 {
+            older + newer
+        }
+    }
+
+    struct AddSum;
+
+    impl LazyAction for AddSum {
+        type ValueMonoid = Sum;
+        type UpdateMonoid = Add;
+
+        fn apply(update: &i64, value: &i64, segment_len: usize) -> i64 {
+            value + update * segment_len as i64
+        }
+    }
+
+    struct AddMax;
+
+    impl LazyAction for AddMax {
+        type ValueMonoid = Max;
+        type UpdateMonoid = Add;
+
+        fn apply(update: &i64, value: &i64, _segment_len: usize) -> i64 {
+            value + update
+        }
+    }
+
+    //        0
+    //      /   \
+    //     1     2
+    //    / \     \
+    //   3   4     5
+    //      / \
+    //     6   7
+    fn binary_tree() -> Vec<(Option<usize>, Option<usize>)> {
+        vec![
+            (Some(1), Some(2)),
+            (Some(3), Some(4)),
+            (None, Some(5)),
+            (None, None),
+            (Some(6), Some(7)),
+            (None, None),
+            (None, None),
+            (None, None),
+        ]
+    }
+
+    #[test]
+    fn test_path_and_subtree_sum() {
+        let values = [1, 2, 3, 4, 5, 6, 7, 8];
+        let mut hld = HeavyLightDecomposition::<AddSum>::from_children(&binary_tree(), &values);
+        // 6 - 4 - 1 - 0 - 2 - 5
+        assert_eq!(7 + 5 + 2 + 1 + 3 + 6, hld.path_query(6, 5));
+        assert_eq!(4 + 2 + 5 + 8, hld.path_query(3, 7));
+        assert_eq!(5, hld.path_query(4, 4));
+        assert_eq!(2 + 4 + 5 + 7 + 8, hld.subtree_query(1));
+        assert_eq!(1, hld.lca(3, 7));
+        assert_eq!(0, hld.lca(6, 5));
+
+        hld.path_update(3, 7, 10);
+        assert_eq!(19 + 40, hld.path_query(3, 7));
+        assert_eq!(26 + 40, hld.subtree_query(1));
+        assert_eq!(1 + 3 + 6, hld.path_query(0, 5));
+        hld.update(0, 100);
+        assert_eq!(100 + 12 + 15, hld.path_query(1, 4) + hld.path_query(0, 0));
+    }
+
+    #[test]
+    fn test_path_ranges_are_few_on_a_path_graph() {
+        let n = 1000;
+        let adj: Vec<Vec<usize>> = (0..n)
+            .map(|u: usize| {
+                [u.checked_sub(1), (u + 1 < n).then_some(u + 1)]
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            })
+            .collect();
+        let hld = HeavyLightDecomposition::<AddSum>::from_adjacency(&adj, 0, &vec![0; n]);
+        // a path is a single chain
+        assert_eq!(vec![(0, n - 1)], hld.path_ranges(n - 1, 0));
+    }
+
+    #[test]
+    fn test_path_max_with_another_root() {
+        //     3
+        //    / \
+        //   0   5
+        //  / \   \
+        // 1   2   4
+        //     |
+        //     6
+        let edges = [(3, 0), (3, 5), (0, 1), (0, 2), (2, 6), (5, 4)];
+        let mut adj = vec![Vec::new(); 7];
+        for (u, v) in edges {
+            adj[u].push(v);
+            adj[v].push(u);
+        }
+        let values = [5, -2, 7, 1, 0, 3, -6];
+        let mut hld = HeavyLightDecomposition::<AddMax>::from_adjacency(&adj, 3, &values);
+        assert_eq!(3, hld.lca(6, 4));
+        assert_eq!(0, hld.lca(1, 6));
+        // 1 - 0 - 2 - 6
+        assert_eq!(7, hld.path_query(1, 6));
+        // 4 - 5 - 3 - 0 - 1
+        assert_eq!(5, hld.path_query(4, 1));
+
+        hld.subtree_update(0, 10);
+        //[15, 8, 17, 1, 0, 3, 4]
+        assert_eq!(15, hld.path_query(4, 1));
+        assert_eq!(17, hld.subtree_query(3));
+        // 4 - 5 - 3 - 0 - 2 - 6
+        hld.path_update(4, 6, -20);
+        //[-5, 8, -3, -19, -20, -17, -16]
+        assert_eq!(8, hld.path_query(1, 4));
+        assert_eq!(-17, hld.subtree_query(5));
+        assert_eq!(-3, hld.get(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Node 3 is not reachable from the root")]
+    fn test_unreachable_arena_slot() {
+        // slot 3 is a leftover that no node points to
+        let children = [(Some(1), Some(2)), (None, None), (None, None), (None, None)];
+        HeavyLightDecomposition::<AddSum>::from_children(&children, &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Node 0 is reached twice from the root")]
+    fn test_cycle_in_children() {
+        let children = [(Some(1), None), (Some(2), None), (None, Some(0))];
+        HeavyLightDecomposition::<AddSum>::from_children(&children, &[1, 2, 3]);
+    }
+}
@@ -0,0 +1,3 @@
+mod heavy_light_decomposition;
+
+pub use heavy_light_decomposition::HeavyLightDecomposition;
@@ -0,0 +1,5 @@
+mod mo;
+mod mo_with_updates;
+
+pub use mo::{MoState, QueryOrder, answer_queries};
+pub use mo_with_updates::{MoUpdateState, answer_queries_with_updates};
@@ -0,0 +1,214 @@
+/// The state of the current window `[l, r]` of the array, kept by the user of Mo's algorithm.
+///
+/// The framework only moves the borders of the window one position at a time, so the
+/// state just needs to know how to take a single element in or out of it.
+pub trait MoState {
+    type Answer;
+
+    /// The element at position `idx` enters the window.
+    fn add(&mut self, idx: usize);
+
+    /// The element at position `idx` leaves the window.
+    fn remove(&mut self, idx: usize);
+
+    /// The answer for the current window.
+    fn answer(&self) -> Self::Answer;
+}
+
+/// The order in which the queries are answered: both keep the total movement of the
+/// window borders in O((n + q) * sqrt(n)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryOrder {
+    /// Queries are grouped in blocks of `sqrt(n)` by left border and sorted by right border
+    /// inside a block, alternating increasing and decreasing order between consecutive
+    /// blocks so that the right border does not jump back at every new block.
+    OddEvenBlocks,
+    /// Queries are sorted by the position of the point `(l, r)` along a Hilbert curve,
+    /// which is usually faster on many queries.
+    Hilbert,
+}
+
+// Position of (x, y) along the Hilbert curve filling the 2^pow x 2^pow square, computed by
+// descending into the quadrant holding the point: `rotate` tracks how the sub-curve of the
+// current square is rotated with respect to the whole one.
+fn hilbert_order(x: usize, y: usize, pow: u32, rotate: usize) -> u64 {
+    if pow == 0 {
+        return 0;
+    }
+    let half = 1 << (pow - 1);
+    let quadrant = match (x < half, y < half) {
+        (true, true) => 0,
+        (true, false) => 3,
+        (false, false) => 2,
+        (false, true) => 1,
+    };
+    let quadrant = (quadrant + rotate) & 3;
+    const ROTATE_DELTA: [usize; 4] = [3, 0, 0, 1];
+    let sub_square_size = 1u64 << (2 * pow - 2);
+    let inner = hilbert_order(
+        x & (half - 1),
+        y & (half - 1),
+        pow - 1,
+        (rotate + ROTATE_DELTA[quadrant]) & 3,
+    );
+    // the first and the last quadrant are walked backwards
+    let inner = if quadrant == 1 || quadrant == 2 {
+        inner
+    } else {
+        sub_square_size - inner - 1
+    };
+    quadrant as u64 * sub_square_size + inner
+}
+
+// Moves the window from [*cur_l, *cur_r) to [l, r]. The window is enlarged before being
+// shrunk, so it never holds a negative number of elements.
+pub(crate) fn move_window<S: MoState>(
+    state: &mut S,
+    cur_l: &mut usize,
+    cur_r: &mut usize,
+    l: usize,
+    r: usize,
+) {
+    while *cur_l > l {
+        *cur_l -= 1;
+        state.add(*cur_l);
+    }
+    while *cur_r <= r {
+        state.add(*cur_r);
+        *cur_r += 1;
+    }
+    while *cur_l < l {
+        state.remove(*cur_l);
+        *cur_l += 1;
+    }
+    while *cur_r > r + 1 {
+        *cur_r -= 1;
+        state.remove(*cur_r);
+    }
+}
+
+/// Answers offline the range queries `[l, r]` in `queries` and returns the answers in the
+/// same order as the queries.
+///
+/// `state` must start from the empty window; queries are reordered following `order` so
+/// that moving the window from one query to the next costs O(sqrt(n)) amortized calls to
+/// `add` and `remove`, where n is the length of the array.
+pub fn answer_queries<S: MoState>(
+    state: &mut S,
+    queries: &[(usize, usize)],
+    order: QueryOrder,
+) -> Vec<S::Answer> {
+    assert!(queries.iter().all(|&(l, r)| l <= r), "Invalid query range");
+    let n = queries.iter().map(|&(_, r)| r + 1).max().unwrap_or(0);
+    let mut sorted: Vec<usize> = (0..queries.len()).collect();
+    match order {
+        QueryOrder::OddEvenBlocks => {
+            let block_size = n.isqrt().max(1);
+            sorted.sort_by_key(|&q| {
+                let (l, r) = queries[q];
+                let block = l / block_size;
+                // r is reversed in odd blocks
+                (block, if block % 2 == 0 { r } else { n - r })
+            });
+        }
+        QueryOrder::Hilbert => {
+            let pow = n.next_power_of_two().trailing_zeros();
+            sorted.sort_by_cached_key(|&q| hilbert_order(queries[q].0, queries[q].1, pow, 0));
+        }
+    }
+    let mut answers: Vec<Option<S::Answer>> = (0..queries.len()).map(|_| None).collect();
+    // the window is the half-open [cur_l, cur_r), empty at the beginning
+    let (mut cur_l, mut cur_r) = (0, 0);
+    for q in sorted {
+        let (l, r) = queries[q];
+        move_window(state, &mut cur_l, &mut cur_r, l, r);
+        answers[q] = Some(state.answer());
+    }
+    answers.into_iter().map(Option::unwrap).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mo::{MoState, QueryOrder, answer_queries};
+
+    // number of distinct values in the window
+    struct DistinctValues<'a> {
+        a: &'a [usize],
+        count: Vec<usize>,
+        distinct: usize,
+    }
+
+    impl<'a> DistinctValues<'a> {
+        fn new(a: &'a [usize]) -> Self {
+            let max_value = a.iter().copied().max().unwrap_or(0);
+            Self {
+                a,
+                count: vec![0; max_value + 1],
+                distinct: 0,
+            }
+        }
+    }
+
+    impl MoState for DistinctValues<'_> {
+        type Answer = usize;
+
+        fn add(&mut self, idx: usize) {
+            self.count[self.a[idx]] += 1;
+            if self.count[self.a[idx]] == 1 {
+                self.distinct += 1;
+            }
+        }
+
+        fn remove(&mut self, idx: usize) {
+            self.count[self.a[idx]] -= 1;
+            if self.count[self.a[idx]] == 0 {
+                self.distinct -= 1;
+            }
+        }
+
+        fn answer(&self) -> usize {
+            self.distinct
+        }
+    }
+
+    fn naive_distinct(a: &[usize], l: usize, r: usize) -> usize {
+        let mut values = a[l..=r].to_vec();
+        values.sort_unstable();
+        values.dedup();
+        values.len()
+    }
+
+    #[test]
+    fn test_distinct_values() {
+        let a = [1, 1, 2, 1, 3];
+        let queries = [(0, 4), (1, 3), (2, 4), (0, 1), (3, 3)];
+        for order in [QueryOrder::OddEvenBlocks, QueryOrder::Hilbert] {
+            let answers = answer_queries(&mut DistinctValues::new(&a), &queries, order);
+            assert_eq!(vec![3, 2, 3, 1, 1], answers);
+        }
+    }
+
+    #[test]
+    fn test_against_naive() {
+        let a: Vec<usize> = (0..97).map(|x| (x * 7919) % 13).collect();
+        let queries: Vec<(usize, usize)> = (0..500)
+            .map(|q| {
+                let (x, y) = ((q * 31) % a.len(), (q * 57 + 11) % a.len());
+                (x.min(y), x.max(y))
+            })
+            .collect();
+        for order in [QueryOrder::OddEvenBlocks, QueryOrder::Hilbert] {
+            let answers = answer_queries(&mut DistinctValues::new(&a), &queries, order);
+            for (&(l, r), answer) in queries.iter().zip(answers) {
+                assert_eq!(naive_distinct(&a, l, r), answer);
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_queries() {
+        let a = [1, 2, 3];
+        let answers = answer_queries(&mut DistinctValues::new(&a), &[], QueryOrder::Hilbert);
+        assert!(answers.is_empty());
+    }
+}
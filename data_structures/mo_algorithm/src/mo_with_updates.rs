@@ -0,0 +1,140 @@
+use crate::mo::{MoState, move_window};
+
+/// A [`MoState`] over an array that also receives point updates.
+///
+/// Each update is stored by the user together with its position and the value it writes.
+/// Applying an update exchanges the value in the array with the stored one, so applying
+/// it a second time restores the previous value and the framework can move back in time
+/// with the same operation.
+pub trait MoUpdateState: MoState {
+    /// The position written by update `u`.
+    fn update_position(&self, u: usize) -> usize;
+
+    /// Exchanges the value at the position of update `u` with the value stored in it.
+    fn toggle_update(&mut self, u: usize);
+}
+
+/// Answers offline the range queries `(l, r, time)` in `queries`, where `time` is the
+/// number of updates applied before the query, and returns the answers in the same order
+/// as the queries.
+///
+/// `state` must start from the empty window with no update applied. Queries are sorted by
+/// blocks of n^(2/3) on both borders and then by time, so moving the window and the time
+/// costs O(n^(5/3)) calls overall when the number of queries and updates is O(n).
+pub fn answer_queries_with_updates<S: MoUpdateState>(
+    state: &mut S,
+    queries: &[(usize, usize, usize)],
+    num_updates: usize,
+) -> Vec<S::Answer> {
+    assert!(
+        queries
+            .iter()
+            .all(|&(l, r, time)| l <= r && time <= num_updates),
+        "Invalid query"
+    );
+    let n = queries.iter().map(|&(_, r, _)| r + 1).max().unwrap_or(0);
+    let block_size = ((n as f64).powf(2.0 / 3.0) as usize).max(1);
+    let mut sorted: Vec<usize> = (0..queries.len()).collect();
+    sorted.sort_by_key(|&q| {
+        let (l, r, time) = queries[q];
+        (l / block_size, r / block_size, time)
+    });
+    let mut answers: Vec<Option<S::Answer>> = (0..queries.len()).map(|_| None).collect();
+    let (mut cur_l, mut cur_r, mut cur_time) = (0, 0, 0);
+    for q in sorted {
+        let (l, r, time) = queries[q];
+        move_window(state, &mut cur_l, &mut cur_r, l, r);
+        while cur_time < time {
+            toggle_update(state, cur_time, cur_l, cur_r);
+            cur_time += 1;
+        }
+        while cur_time > time {
+            cur_time -= 1;
+            toggle_update(state, cur_time, cur_l, cur_r);
+        }
+        answers[q] = Some(state.answer());
+    }
+    answers.into_iter().map(Option::unwrap).collect()
+}
+
+// an update inside the window [cur_l, cur_r) changes an element the state is counting, so
+// the old value is taken out before the update and the new one is put in after it
+fn toggle_update<S: MoUpdateState>(state: &mut S, u: usize, cur_l: usize, cur_r: usize) {
+    let pos = state.update_position(u);
+    let in_window = cur_l <= pos && pos < cur_r;
+    if in_window {
+        state.remove(pos);
+    }
+    state.toggle_update(u);
+    if in_window {
+        state.add(pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mo::MoState;
+    use crate::mo_with_updates::{MoUpdateState, answer_queries_with_updates};
+
+    // sum of the window, over an array updated by point assignments
+    struct RangeSum {
+        a: Vec<i64>,
+        // (position, value to exchange with the array)
+        updates: Vec<(usize, i64)>,
+        sum: i64,
+    }
+
+    impl MoState for RangeSum {
+        type Answer = i64;
+
+        fn add(&mut self, idx: usize) {
+            self.sum += self.a[idx];
+        }
+
+        fn remove(&mut self, idx: usize) {
+            self.sum -= self.a[idx];
+        }
+
+        fn answer(&self) -> i64 {
+            self.sum
+        }
+    }
+
+    impl MoUpdateState for RangeSum {
+        fn update_position(&self, u: usize) -> usize {
+            self.updates[u].0
+        }
+
+        fn toggle_update(&mut self, u: usize) {
+            let (pos, value) = &mut self.updates[u];
+            std::mem::swap(&mut self.a[*pos], value);
+        }
+    }
+
+    #[test]
+    fn test_against_naive() {
+        let a: Vec<i64> = (0..40).map(|x| (x * 7919) % 17 - 8).collect();
+        let updates: Vec<(usize, i64)> = (0..30)
+            .map(|u| ((u * 13) % a.len(), u as i64 - 15))
+            .collect();
+        let queries: Vec<(usize, usize, usize)> = (0..200)
+            .map(|q| {
+                let (x, y) = ((q * 31) % a.len(), (q * 57 + 11) % a.len());
+                (x.min(y), x.max(y), (q * 7) % (updates.len() + 1))
+            })
+            .collect();
+        let mut state = RangeSum {
+            a: a.clone(),
+            updates: updates.clone(),
+            sum: 0,
+        };
+        let answers = answer_queries_with_updates(&mut state, &queries, updates.len());
+        for (&(l, r, time), answer) in queries.iter().zip(answers) {
+            let mut current = a.clone();
+            for &(pos, value) in &updates[..time] {
+                current[pos] = value;
+            }
+            assert_eq!(current[l..=r].iter().sum::<i64>(), answer);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::generic_segment_tree::Monoid;
+use crate::lazy_segment_tree::{LazyAction, LazySegmentTree};
+use crate::mod_int::ModInt;
+use crate::serialization::Encode;
+use std::io;
+
+struct ModSum<const P: u64>;
+
+impl<const P: u64> Monoid for ModSum<P> {
+    type Value = ModInt<P>;
+
+    fn identity() -> ModInt<P> {
+        ModInt::new(0)
+    }
+
+    fn combine(a: &ModInt<P>, b: &ModInt<P>) -> ModInt<P> {
+        *a + *b
+    }
+}
+
+// The lazy tag x -> b * x + c. Composing two of them gives a map of the same shape, so a
+// node never holds more than one tag.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AffineMap<const P: u64> {
+    b: ModInt<P>,
+    c: ModInt<P>,
+}
+
+impl<const P: u64> Encode for AffineMap<P> {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.b.encode(writer)?;
+        self.c.encode(writer)
+    }
+
+    fn decode<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(AffineMap {
+            b: ModInt::decode(reader)?,
+            c: ModInt::decode(reader)?,
+        })
+    }
+}
+
+struct Affine<const P: u64>;
+
+impl<const P: u64> Monoid for Affine<P> {
+    type Value = AffineMap<P>;
+
+    fn identity() -> AffineMap<P> {
+        AffineMap {
+            b: ModInt::new(1),
+            c: ModInt::new(0),
+        }
+    }
+
+    fn combine(older: &AffineMap<P>, newer: &AffineMap<P>) -> AffineMap<P> {
+        // newer(older(x)) = b2 * (b1 * x + c1) + c2
+        AffineMap {
+            b: newer.b * older.b,
+            c: newer.b * older.c + newer.c,
+        }
+    }
+}
+
+struct AffineModSum<const P: u64>;
+
+impl<const P: u64> LazyAction for AffineModSum<P> {
+    type ValueMonoid = ModSum<P>;
+    type UpdateMonoid = Affine<P>;
+
+    fn apply(update: &AffineMap<P>, value: &ModInt<P>, segment_len: usize) -> ModInt<P> {
+        // every element gets multiplied by b, while c is added once per element
+        update.b * *value + update.c * ModInt::new(segment_len as u64)
+    }
+}
+
+/// A segment tree over integers modulo the prime `P` supporting the range affine update
+/// `a[i] = b * a[i] + c` and range sums, both in O(log n).
+pub struct AffineSegmentTree<const P: u64> {
+    st: LazySegmentTree<AffineModSum<P>>,
+}
+
+impl<const P: u64> AffineSegmentTree<P> {
+    /// Builds a tree over `a`, reducing every value modulo `P`.
+    pub fn build(a: &[u64]) -> Self {
+        let leaves: Vec<ModInt<P>> = a.iter().map(|&v| ModInt::new(v)).collect();
+        Self {
+            st: LazySegmentTree::build(&leaves),
+        }
+    }
+
+    /// Builds a tree over `num_elems` zeros.
+    pub fn build_empty(num_elems: usize) -> Self {
+        Self {
+            st: LazySegmentTree::build_empty(num_elems),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.st.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.st.is_empty()
+    }
+
+    /// Replaces every element `x` in `[i, j]` with `b * x + c`.
+    pub fn affine(&mut self, i: usize, j: usize, b: ModInt<P>, c: ModInt<P>) {
+        self.st.range_update(i, j, AffineMap { b, c });
+    }
+
+    pub fn sum(&mut self, i: usize, j: usize) -> ModInt<P> {
+        self.st.query(i, j)
+    }
+
+    pub fn get(&mut self, i: usize) -> ModInt<P> {
+        self.st.get(i)
+    }
+
+    /// Returns the current values of all the elements in O(n).
+    pub fn to_vec(&mut self) -> Vec<ModInt<P>> {
+        self.st.to_vec()
+    }
+
+    /// Writes the tree, pending affine maps included, as `LazySegmentTree::save` does. The
+    /// modulus is not saved: loading checks only that every value is reduced modulo `P`.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.st.save(writer)
+    }
+
+    /// Reads a tree written by `save`.
+    pub fn load<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            st: LazySegmentTree::load(reader)?,
+        })
+    }
+
+    /// Describes the tree in a human-readable form, one node per line in implicit order.
+    pub fn dump_text(&self) -> String {
+        self.st.dump_text()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::affine_segment_tree::AffineSegmentTree;
+    use crate::mod_int::ModInt;
+
+    const P: u64 = 998_244_353;
+    type Mint = ModInt<P>;
+
+    #[test]
+    fn test_affine_sum() {
+        let mut tree = AffineSegmentTree::<P>::build(&[1, 2, 3, 4, 5]);
+        assert_eq!(15, tree.sum(0, 4).value());
+        tree.affine(2, 3, Mint::new(100), Mint::new(101));
+        //[1, 2, 401, 501, 5]
+        assert_eq!(403, tree.sum(1, 2).value());
+        tree.affine(1, 4, Mint::new(2), Mint::new(3));
+        //[1, 7, 805, 1005, 13]
+        assert_eq!(1831, tree.sum(0, 4).value());
+        assert_eq!(1005, tree.get(3).value());
+    }
+
+    #[test]
+    fn test_composition_order() {
+        // x -> 2x + 1 followed by x -> 3x + 4 is x -> 6x + 7, not x -> 6x + 5
+        let mut tree = AffineSegmentTree::<P>::build(&[10, 20]);
+        tree.affine(0, 1, Mint::new(2), Mint::new(1));
+        tree.affine(0, 1, Mint::new(3), Mint::new(4));
+        assert_eq!(vec![Mint::new(67), Mint::new(127)], tree.to_vec());
+    }
+
+    #[test]
+    fn test_values_wrap_around() {
+        let mut tree = AffineSegmentTree::<P>::build(&[P - 1, P + 2, 0]);
+        assert_eq!(1, tree.sum(0, 1).value());
+        tree.affine(0, 2, Mint::from_i64(-1), Mint::new(P - 1));
+        //[0, P - 3, P - 1]
+        assert_eq!(P - 4, tree.sum(0, 2).value());
+    }
+
+    #[test]
+    fn test_save_and_load_with_pending_updates() {
+        let mut original = AffineSegmentTree::<P>::build(&[1, 2, 3, 4, 5]);
+        original.affine(1, 3, Mint::new(2), Mint::new(1));
+        original.affine(2, 4, Mint::new(3), Mint::new(P - 1));
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+
+        let mut loaded = AffineSegmentTree::<P>::load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        assert_eq!(original.to_vec(), loaded.to_vec());
+        // a smaller modulus rejects the values that are not reduced for it
+        assert!(AffineSegmentTree::<7>::load(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_large_modulus() {
+        // with P = 2^61 - 1 the products do not fit in a u64, and 2^61 = 1 (mod P)
+        const BIG: u64 = (1 << 61) - 1;
+        let mut tree = AffineSegmentTree::<BIG>::build(&[BIG - 1, BIG - 2, 5]);
+        tree.affine(0, 1, ModInt::from_i64(-1), ModInt::new(3));
+        //[4, 5, 5]
+        assert_eq!(14, tree.sum(0, 2).value());
+        tree.affine(1, 2, ModInt::new(1 << 60), ModInt::new(0));
+        // 5 * 2^60 = 2 * 2^61 + 2^60 = 2^60 + 2
+        //[4, 2^60 + 2, 2^60 + 2]
+        assert_eq!((1 << 60) + 2, tree.get(1).value());
+        // 2^61 + 4 = 5
+        assert_eq!(5, tree.sum(1, 2).value());
+        assert_eq!(9, tree.sum(0, 2).value());
+        assert_eq!(4, tree.get(0).value());
+    }
+}
@@ -0,0 +1,300 @@
+use crate::generic_segment_tree::Monoid;
+use crate::lazy_segment_tree::{LazyAction, LazySegmentTree};
+use crate::serialization::Encode;
+use std::cmp::{max, min};
+use std::io;
+
+// sum, minimum and maximum of a segment, maintained together so that a single
+// tree can answer all three queries
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RangeStats {
+    sum: i64,
+    min: i64,
+    max: i64,
+}
+
+impl RangeStats {
+    fn single(value: i64) -> Self {
+        Self {
+            sum: value,
+            min: value,
+            max: value,
+        }
+    }
+}
+
+impl Encode for RangeStats {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.sum.encode(writer)?;
+        self.min.encode(writer)?;
+        self.max.encode(writer)
+    }
+
+    fn decode<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        // the fields of a struct expression are evaluated in the order they are written
+        Ok(RangeStats {
+            sum: i64::decode(reader)?,
+            min: i64::decode(reader)?,
+            max: i64::decode(reader)?,
+        })
+    }
+}
+
+struct Stats;
+
+impl Monoid for Stats {
+    type Value = RangeStats;
+
+    fn identity() -> RangeStats {
+        RangeStats {
+            sum: 0,
+            min: i64::MAX,
+            max: i64::MIN,
+        }
+    }
+
+    fn combine(a: &RangeStats, b: &RangeStats) -> RangeStats {
+        RangeStats {
+            sum: a.sum + b.sum,
+            min: min(a.min, b.min),
+            max: max(a.max, b.max),
+        }
+    }
+}
+
+// The lazy tag "first set every element to `assign` (if any), then add `add` to it".
+// Any sequence of assignments and additions collapses into a single tag of this shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AssignAdd {
+    assign: Option<i64>,
+    add: i64,
+}
+
+impl Monoid for AssignAdd {
+    type Value = AssignAdd;
+
+    fn identity() -> AssignAdd {
+        AssignAdd {
+            assign: None,
+            add: 0,
+        }
+    }
+
+    fn combine(older: &AssignAdd, newer: &AssignAdd) -> AssignAdd {
+        match newer.assign {
+            // a newer assignment overrides whatever happened before
+            Some(_) => *newer,
+            // a newer addition stacks on the older tag, keeping its assignment
+            None => AssignAdd {
+                assign: older.assign,
+                add: older.add + newer.add,
+            },
+        }
+    }
+}
+
+impl Encode for AssignAdd {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.assign.encode(writer)?;
+        self.add.encode(writer)
+    }
+
+    fn decode<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(AssignAdd {
+            assign: Option::decode(reader)?,
+            add: i64::decode(reader)?,
+        })
+    }
+}
+
+struct AssignAddStats;
+
+impl LazyAction for AssignAddStats {
+    type ValueMonoid = Stats;
+    type UpdateMonoid = AssignAdd;
+
+    fn apply(update: &AssignAdd, value: &RangeStats, segment_len: usize) -> RangeStats {
+        let len = segment_len as i64;
+        let base = match update.assign {
+            Some(x) => RangeStats {
+                sum: x * len,
+                min: x,
+                max: x,
+            },
+            None => *value,
+        };
+        RangeStats {
+            sum: base.sum + update.add * len,
+            min: base.min + update.add,
+            max: base.max + update.add,
+        }
+    }
+}
+
+/// A segment tree supporting both range assignment and range addition, answering
+/// sum, minimum and maximum over a range in O(log n).
+pub struct AssignAddSegmentTree {
+    st: LazySegmentTree<AssignAddStats>,
+}
+
+impl AssignAddSegmentTree {
+    pub fn build(a: &[i64]) -> Self {
+        let leaves: Vec<RangeStats> = a.iter().map(|&v| RangeStats::single(v)).collect();
+        Self {
+            st: LazySegmentTree::build(&leaves),
+        }
+    }
+
+    /// Builds a tree over `num_elems` zeros.
+    pub fn build_empty(num_elems: usize) -> Self {
+        Self::build(&vec![0; num_elems])
+    }
+
+    /// Writes the tree, pending assignments and additions included, as
+    /// `LazySegmentTree::save` does.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.st.save(writer)
+    }
+
+    /// Reads a tree written by `save`.
+    pub fn load<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            st: LazySegmentTree::load(reader)?,
+        })
+    }
+
+    /// Describes the tree in a human-readable form, one node per line in implicit order.
+    pub fn dump_text(&self) -> String {
+        self.st.dump_text()
+    }
+
+    pub fn len(&self) -> usize {
+        self.st.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.st.is_empty()
+    }
+
+    /// Sets every element in `[i, j]` to `x`.
+    pub fn assign(&mut self, i: usize, j: usize, x: i64) {
+        self.st.range_update(
+            i,
+            j,
+            AssignAdd {
+                assign: Some(x),
+                add: 0,
+            },
+        );
+    }
+
+    /// Adds `y` to every element in `[i, j]`.
+    pub fn add(&mut self, i: usize, j: usize, y: i64) {
+        self.st.range_update(
+            i,
+            j,
+            AssignAdd {
+                assign: None,
+                add: y,
+            },
+        );
+    }
+
+    pub fn sum(&mut self, i: usize, j: usize) -> i64 {
+        self.st.query(i, j).sum
+    }
+
+    pub fn min(&mut self, i: usize, j: usize) -> i64 {
+        self.st.query(i, j).min
+    }
+
+    pub fn max(&mut self, i: usize, j: usize) -> i64 {
+        self.st.query(i, j).max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assign_add_segment_tree::AssignAddSegmentTree;
+
+    #[test]
+    fn test_build() {
+        let mut tree = AssignAddSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4]);
+        assert_eq!(31, tree.sum(0, 6));
+        assert_eq!(-4, tree.min(0, 6));
+        assert_eq!(12, tree.max(0, 6));
+        assert_eq!(3, tree.min(0, 4));
+    }
+
+    #[test]
+    fn test_assign() {
+        let mut tree = AssignAddSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4]);
+        tree.assign(1, 4, 7);
+        //[3, 7, 7, 7, 7, 12, -4]
+        assert_eq!(39, tree.sum(0, 6));
+        assert_eq!(7, tree.min(1, 5));
+        assert_eq!(7, tree.max(0, 4));
+        assert_eq!(14, tree.sum(3, 4));
+    }
+
+    #[test]
+    fn test_add_after_assign() {
+        let mut tree = AssignAddSegmentTree::build_empty(8);
+        tree.assign(0, 7, 5);
+        tree.add(2, 5, -2);
+        tree.add(4, 7, 1);
+        //[5, 5, 3, 3, 4, 4, 6, 6]
+        assert_eq!(36, tree.sum(0, 7));
+        assert_eq!(3, tree.min(0, 7));
+        assert_eq!(6, tree.max(0, 7));
+        assert_eq!(4, tree.max(2, 5));
+    }
+
+    #[test]
+    fn test_assign_after_add() {
+        let mut tree = AssignAddSegmentTree::build(&[1, 2, 3, 4, 5, 6]);
+        tree.add(0, 5, 10);
+        tree.assign(2, 3, 0);
+        tree.add(3, 5, 1);
+        //[11, 12, 0, 1, 16, 17]
+        assert_eq!(57, tree.sum(0, 5));
+        assert_eq!(0, tree.min(0, 5));
+        assert_eq!(1, tree.min(3, 5));
+        assert_eq!(12, tree.max(0, 3));
+    }
+
+    #[test]
+    fn test_interleaved_partial_updates() {
+        let mut tree = AssignAddSegmentTree::build_empty(10);
+        tree.add(0, 9, 1);
+        // a node first receives an addition and then an assignment while its
+        // children still hold the addition as pending
+        tree.assign(0, 4, 3);
+        tree.add(3, 6, 2);
+        tree.assign(6, 9, -1);
+        //[3, 3, 3, 5, 5, 3, -1, -1, -1, -1]
+        assert_eq!(18, tree.sum(0, 9));
+        assert_eq!(5, tree.sum(4, 4));
+        assert_eq!(-1, tree.min(5, 9));
+        assert_eq!(5, tree.max(0, 9));
+    }
+
+    #[test]
+    fn test_save_and_load_with_pending_updates() {
+        let mut original = AssignAddSegmentTree::build(&[5, -2, 8, 1, 0, 4]);
+        original.assign(1, 4, 3);
+        original.add(0, 2, -1);
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+
+        let mut loaded = AssignAddSegmentTree::load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        for i in 0..6 {
+            for j in i..6 {
+                assert_eq!(original.sum(i, j), loaded.sum(i, j));
+                assert_eq!(original.min(i, j), loaded.min(i, j));
+                assert_eq!(original.max(i, j), loaded.max(i, j));
+            }
+        }
+    }
+}
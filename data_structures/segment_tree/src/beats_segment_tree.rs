@@ -0,0 +1,639 @@
+use crate::error::{SegmentTreeError, check_index, check_range};
+use crate::range::{Leaves, Range, left_right_child_index};
+use crate::serialization::{
+    FORMAT_VERSION, TreeKind, read_header, read_i64, write_header, write_i64,
+};
+use std::cmp::{max, min};
+use std::fmt::Write;
+use std::io;
+
+// Everything a node needs to apply a chmin/chmax without visiting its children: besides the
+// sum, the largest and the second largest value with the number of occurrences of the
+// largest one, and the same three fields on the min side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Node {
+    sum: i64,
+    max: i64,
+    second_max: i64,
+    count_max: i64,
+    min: i64,
+    second_min: i64,
+    count_min: i64,
+    // value to add to every element of the children, not pushed down yet
+    pending_add: i64,
+}
+
+impl Node {
+    fn leaf(value: i64) -> Self {
+        Node {
+            sum: value,
+            max: value,
+            second_max: i64::MIN,
+            count_max: 1,
+            min: value,
+            second_min: i64::MAX,
+            count_min: 1,
+            pending_add: 0,
+        }
+    }
+
+    fn merge(left: &Node, right: &Node) -> Self {
+        let (max, second_max, count_max) = if left.max == right.max {
+            (
+                left.max,
+                max(left.second_max, right.second_max),
+                left.count_max + right.count_max,
+            )
+        } else if left.max > right.max {
+            (left.max, max(left.second_max, right.max), left.count_max)
+        } else {
+            (right.max, max(left.max, right.second_max), right.count_max)
+        };
+        let (min, second_min, count_min) = if left.min == right.min {
+            (
+                left.min,
+                min(left.second_min, right.second_min),
+                left.count_min + right.count_min,
+            )
+        } else if left.min < right.min {
+            (left.min, min(left.second_min, right.min), left.count_min)
+        } else {
+            (right.min, min(left.min, right.second_min), right.count_min)
+        };
+        Node {
+            sum: left.sum.wrapping_add(right.sum),
+            max,
+            second_max,
+            count_max,
+            min,
+            second_min,
+            count_min,
+            pending_add: 0,
+        }
+    }
+
+    fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        for field in [
+            self.sum,
+            self.max,
+            self.second_max,
+            self.count_max,
+            self.min,
+            self.second_min,
+            self.count_min,
+            self.pending_add,
+        ] {
+            write_i64(writer, field)?;
+        }
+        Ok(())
+    }
+
+    fn load<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        // the fields of a struct expression are evaluated in the order they are written
+        Ok(Node {
+            sum: read_i64(reader)?,
+            max: read_i64(reader)?,
+            second_max: read_i64(reader)?,
+            count_max: read_i64(reader)?,
+            min: read_i64(reader)?,
+            second_min: read_i64(reader)?,
+            count_min: read_i64(reader)?,
+            pending_add: read_i64(reader)?,
+        })
+    }
+}
+
+/// A "Segment Tree Beats" supporting range chmin (`a[i] = min(a[i], t)`), range chmax
+/// (`a[i] = max(a[i], t)`) and range add updates together with range sum, max and min
+/// queries, in amortized O(log² n) per operation.
+///
+/// A chmin with `t` only needs to be applied to a node whose `second_max < t < max`: in that
+/// case just the `count_max` maximum values change and the sum is fixed without visiting the
+/// children. Otherwise the update is pushed further down, and the amortized analysis shows
+/// that this happens a bounded number of times. Chmax works symmetrically on the min side.
+///
+/// Sums are kept modulo 2^64, so a range sum is exact whenever it fits in an `i64`, even if
+/// the sums of larger ranges overflow.
+pub struct SegmentTreeBeats {
+    tree: Vec<Node>,
+    num_leaf: usize,
+}
+
+impl SegmentTreeBeats {
+    pub fn build(a: &[i64]) -> Self {
+        let mut implicit_tree = vec![Node::leaf(0); (2 * a.len()).saturating_sub(1)];
+        if !a.is_empty() {
+            Self::build_rec(a, &mut implicit_tree, Range::new(0, a.len() - 1), 0);
+        }
+        Self {
+            tree: implicit_tree,
+            num_leaf: a.len(),
+        }
+    }
+
+    fn build_rec(a: &[i64], tree: &mut [Node], node_segment: Range, index: usize) {
+        if node_segment.is_single_point() {
+            tree[index] = Node::leaf(a[node_segment.start]);
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        Self::build_rec(a, tree, node_segment.left_half(), left_child_index);
+        Self::build_rec(a, tree, node_segment.right_half(), right_child_index);
+        tree[index] = Node::merge(&tree[left_child_index], &tree[right_child_index]);
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_leaf
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_leaf == 0
+    }
+
+    /// Writes the tree, pending additions included, in the little-endian binary format
+    /// shared by the trees of this crate: a versioned header followed by the eight fields of
+    /// each node, in implicit order.
+    ///
+    /// Every number is written separately, so `writer` should be buffered.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, TreeKind::Beats, self.num_leaf)?;
+        for node in &self.tree {
+            node.save(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tree written by `save`.
+    pub fn load<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let num_leaf = read_header(reader, TreeKind::Beats, size_of::<Node>())?;
+        let tree = (0..(2 * num_leaf).saturating_sub(1))
+            .map(|_| Node::load(reader))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { tree, num_leaf })
+    }
+
+    /// Describes the tree in a human-readable form, one node per line in implicit order.
+    pub fn dump_text(&self) -> String {
+        let mut text = format!(
+            "SegmentTreeBeats (format version {FORMAT_VERSION})\nleaves: {}\n",
+            self.num_leaf
+        );
+        if self.num_leaf > 0 {
+            self.dump_text_rec(Range::new(0, self.num_leaf - 1), 0, &mut text);
+        }
+        text
+    }
+
+    // the implicit layout is a preorder visit, so the nodes are dumped by increasing index
+    fn dump_text_rec(&self, node_segment: Range, index: usize, text: &mut String) {
+        let node = &self.tree[index];
+        // writing to a String never fails
+        writeln!(
+            text,
+            "node {index} {node_segment}: sum {}, max {} (x{}, second {}), min {} (x{}, second {}), pending add {}",
+            node.sum,
+            node.max,
+            node.count_max,
+            node.second_max,
+            node.min,
+            node.count_min,
+            node.second_min,
+            node.pending_add
+        )
+        .unwrap();
+        if node_segment.is_single_point() {
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.dump_text_rec(node_segment.left_half(), left_child_index, text);
+        self.dump_text_rec(node_segment.right_half(), right_child_index, text);
+    }
+
+    // Lowers the maximum values of the node to t, knowing that second_max < t < max.
+    fn apply_chmin(&mut self, index: usize, t: i64) {
+        let node = &mut self.tree[index];
+        if t >= node.max {
+            return;
+        }
+        node.sum = node
+            .sum
+            .wrapping_sub(node.max.wrapping_sub(t).wrapping_mul(node.count_max));
+        // the maximum may also be the minimum (or the second minimum) of the node
+        if node.min == node.max {
+            node.min = t;
+        } else if node.second_min == node.max {
+            node.second_min = t;
+        }
+        node.max = t;
+    }
+
+    // Raises the minimum values of the node to t, knowing that min < t < second_min.
+    fn apply_chmax(&mut self, index: usize, t: i64) {
+        let node = &mut self.tree[index];
+        if t <= node.min {
+            return;
+        }
+        node.sum = node
+            .sum
+            .wrapping_add(t.wrapping_sub(node.min).wrapping_mul(node.count_min));
+        if node.max == node.min {
+            node.max = t;
+        } else if node.second_max == node.min {
+            node.second_max = t;
+        }
+        node.min = t;
+    }
+
+    fn apply_add(&mut self, node_segment: Range, index: usize, v: i64) {
+        let node = &mut self.tree[index];
+        node.sum = node
+            .sum
+            .wrapping_add(v.wrapping_mul(node_segment.size() as i64));
+        node.max += v;
+        node.min += v;
+        if node.second_max != i64::MIN {
+            node.second_max += v;
+        }
+        if node.second_min != i64::MAX {
+            node.second_min += v;
+        }
+        node.pending_add += v;
+    }
+
+    // Brings both children up to date with the updates applied to the node at `index`.
+    // Pending additions are pushed as they are, while chmin/chmax are not stored explicitly:
+    // a child whose max exceeds the max of its father has been capped by a chmin and the
+    // same holds for the min side.
+    fn push_down(&mut self, node_segment: Range, index: usize) {
+        if node_segment.is_single_point() {
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        let pending_add = self.tree[index].pending_add;
+        if pending_add != 0 {
+            self.apply_add(node_segment.left_half(), left_child_index, pending_add);
+            self.apply_add(node_segment.right_half(), right_child_index, pending_add);
+            self.tree[index].pending_add = 0;
+        }
+        let (node_max, node_min) = (self.tree[index].max, self.tree[index].min);
+        for child_index in [left_child_index, right_child_index] {
+            self.apply_chmin(child_index, node_max);
+            self.apply_chmax(child_index, node_min);
+        }
+    }
+
+    fn pull_up(&mut self, node_segment: Range, index: usize) {
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.tree[index] = Node::merge(&self.tree[left_child_index], &self.tree[right_child_index]);
+    }
+
+    /// Replaces every `a[k]` with `min(a[k], t)` for k in `[i, j]`.
+    pub fn chmin(&mut self, i: usize, j: usize, t: i64) {
+        self.try_chmin(i, j, t).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `chmin`, but fails without touching the tree if `[i, j]` ends past the last
+    /// element.
+    pub fn try_chmin(&mut self, i: usize, j: usize, t: i64) -> Result<(), SegmentTreeError> {
+        check_range(i, j, self.num_leaf)?;
+        if i <= j {
+            self.chmin_rec(Range::new(i, j), Range::new(0, self.num_leaf - 1), 0, t);
+        }
+        Ok(())
+    }
+
+    fn chmin_rec(&mut self, query_range: Range, node_segment: Range, index: usize, t: i64) {
+        // break condition: nothing in the node is above t
+        if query_range.no_overlap(node_segment) || self.tree[index].max <= t {
+            return;
+        }
+        // tag condition: only the maximum values change. A leaf always takes the tag, as
+        // its i64::MIN second maximum would fail the test for t = i64::MIN
+        if query_range.contains(node_segment)
+            && (node_segment.is_single_point() || self.tree[index].second_max < t)
+        {
+            self.apply_chmin(index, t);
+            return;
+        }
+        self.push_down(node_segment, index);
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.chmin_rec(query_range, node_segment.left_half(), left_child_index, t);
+        self.chmin_rec(query_range, node_segment.right_half(), right_child_index, t);
+        self.pull_up(node_segment, index);
+    }
+
+    /// Replaces every `a[k]` with `max(a[k], t)` for k in `[i, j]`.
+    pub fn chmax(&mut self, i: usize, j: usize, t: i64) {
+        self.try_chmax(i, j, t).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `chmax`, but fails without touching the tree if `[i, j]` ends past the last
+    /// element.
+    pub fn try_chmax(&mut self, i: usize, j: usize, t: i64) -> Result<(), SegmentTreeError> {
+        check_range(i, j, self.num_leaf)?;
+        if i <= j {
+            self.chmax_rec(Range::new(i, j), Range::new(0, self.num_leaf - 1), 0, t);
+        }
+        Ok(())
+    }
+
+    fn chmax_rec(&mut self, query_range: Range, node_segment: Range, index: usize, t: i64) {
+        if query_range.no_overlap(node_segment) || self.tree[index].min >= t {
+            return;
+        }
+        if query_range.contains(node_segment)
+            && (node_segment.is_single_point() || self.tree[index].second_min > t)
+        {
+            self.apply_chmax(index, t);
+            return;
+        }
+        self.push_down(node_segment, index);
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.chmax_rec(query_range, node_segment.left_half(), left_child_index, t);
+        self.chmax_rec(query_range, node_segment.right_half(), right_child_index, t);
+        self.pull_up(node_segment, index);
+    }
+
+    /// Adds `v` to every `a[k]` for k in `[i, j]`.
+    pub fn add(&mut self, i: usize, j: usize, v: i64) {
+        self.try_add(i, j, v).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `add`, but fails without touching the tree if `[i, j]` ends past the last
+    /// element.
+    pub fn try_add(&mut self, i: usize, j: usize, v: i64) -> Result<(), SegmentTreeError> {
+        check_range(i, j, self.num_leaf)?;
+        if i <= j {
+            self.add_rec(Range::new(i, j), Range::new(0, self.num_leaf - 1), 0, v);
+        }
+        Ok(())
+    }
+
+    fn add_rec(&mut self, query_range: Range, node_segment: Range, index: usize, v: i64) {
+        if query_range.no_overlap(node_segment) {
+            return;
+        }
+        if query_range.contains(node_segment) {
+            self.apply_add(node_segment, index, v);
+            return;
+        }
+        self.push_down(node_segment, index);
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.add_rec(query_range, node_segment.left_half(), left_child_index, v);
+        self.add_rec(query_range, node_segment.right_half(), right_child_index, v);
+        self.pull_up(node_segment, index);
+    }
+
+    fn push_down_all(&mut self, node_segment: Range, index: usize) {
+        if node_segment.is_single_point() {
+            return;
+        }
+        self.push_down(node_segment, index);
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.push_down_all(node_segment.left_half(), left_child_index);
+        self.push_down_all(node_segment.right_half(), right_child_index);
+    }
+
+    /// Returns the current value of the element at position `i`.
+    pub fn leaf(&mut self, i: usize) -> i64 {
+        check_index(i, self.num_leaf).unwrap_or_else(|e| panic!("{e}"));
+        self.sum(i, i)
+    }
+
+    /// Iterates the current values of the elements from left to right, after pushing every
+    /// pending update down to the leaves in O(n).
+    pub fn leaves(&mut self) -> impl Iterator<Item = i64> + '_ {
+        if !self.is_empty() {
+            self.push_down_all(Range::new(0, self.num_leaf - 1), 0);
+        }
+        Leaves::new(&self.tree, self.num_leaf).map(|node| node.sum)
+    }
+
+    /// Returns the current values of all the elements in O(n).
+    pub fn to_vec(&mut self) -> Vec<i64> {
+        self.leaves().collect()
+    }
+
+    pub fn sum(&mut self, i: usize, j: usize) -> i64 {
+        self.try_sum(i, j).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn max(&mut self, i: usize, j: usize) -> i64 {
+        self.try_max(i, j).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn min(&mut self, i: usize, j: usize) -> i64 {
+        self.try_min(i, j).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Returns the sum of `[i, j]`, 0 if the range is empty (`i > j`) or an error if it
+    /// ends past the last element.
+    pub fn try_sum(&mut self, i: usize, j: usize) -> Result<i64, SegmentTreeError> {
+        Ok(self.query(i, j)?.map_or(0, |node| node.sum))
+    }
+
+    /// Like `try_sum`, for the maximum of `[i, j]` (`i64::MIN` if the range is empty).
+    pub fn try_max(&mut self, i: usize, j: usize) -> Result<i64, SegmentTreeError> {
+        Ok(self.query(i, j)?.map_or(i64::MIN, |node| node.max))
+    }
+
+    /// Like `try_sum`, for the minimum of `[i, j]` (`i64::MAX` if the range is empty).
+    pub fn try_min(&mut self, i: usize, j: usize) -> Result<i64, SegmentTreeError> {
+        Ok(self.query(i, j)?.map_or(i64::MAX, |node| node.min))
+    }
+
+    fn query(&mut self, i: usize, j: usize) -> Result<Option<Node>, SegmentTreeError> {
+        check_range(i, j, self.num_leaf)?;
+        if i > j {
+            return Ok(None);
+        }
+        Ok(self.query_rec(Range::new(i, j), Range::new(0, self.num_leaf - 1), 0))
+    }
+
+    fn query_rec(&mut self, query_range: Range, node_segment: Range, index: usize) -> Option<Node> {
+        if query_range.no_overlap(node_segment) {
+            return None;
+        }
+        if query_range.contains(node_segment) {
+            return Some(self.tree[index]);
+        }
+        self.push_down(node_segment, index);
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        let left = self.query_rec(query_range, node_segment.left_half(), left_child_index);
+        let right = self.query_rec(query_range, node_segment.right_half(), right_child_index);
+        match (left, right) {
+            (Some(left), Some(right)) => Some(Node::merge(&left, &right)),
+            (left, right) => left.or(right),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beats_segment_tree::SegmentTreeBeats;
+    use crate::error::SegmentTreeError;
+
+    // checks every range query against the expected values of the elements
+    fn check_against_naive(tree: &mut SegmentTreeBeats, naive: &[i64]) {
+        for i in 0..naive.len() {
+            for j in i..naive.len() {
+                let slice = &naive[i..=j];
+                assert_eq!(slice.iter().sum::<i64>(), tree.sum(i, j));
+                assert_eq!(*slice.iter().max().unwrap(), tree.max(i, j));
+                assert_eq!(*slice.iter().min().unwrap(), tree.min(i, j));
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_vec() {
+        let mut tree = SegmentTreeBeats::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+        tree.chmin(0, 9, 16);
+        tree.add(2, 5, 3);
+        tree.chmax(4, 8, 10);
+        assert_eq!(vec![8, 2, 9, 19, 19, 18, 10, 16, 10, 16], tree.to_vec());
+        assert_eq!(19, tree.leaf(3));
+        assert_eq!(2, tree.leaves().filter(|&v| v == 10).count());
+        assert_eq!(127, tree.sum(0, 9));
+    }
+
+    #[test]
+    fn test_save_and_load_with_pending_updates() {
+        let mut original = SegmentTreeBeats::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+        original.chmin(0, 9, 16);
+        original.add(2, 5, 3);
+        original.chmax(4, 8, 10);
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+        // header and eight i64 for each of the 19 nodes
+        assert_eq!(15 + 19 * 64, bytes.len());
+
+        let mut loaded = SegmentTreeBeats::load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        check_against_naive(&mut loaded, &[8, 2, 9, 19, 19, 18, 10, 16, 10, 16]);
+        assert!(SegmentTreeBeats::load(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_dump_text() {
+        let tree = SegmentTreeBeats::build(&[5, 3]);
+        assert_eq!(
+            format!(
+                "SegmentTreeBeats (format version 1)\n\
+                leaves: 2\n\
+                node 0 [0..1]: sum 8, max 5 (x1, second 3), min 3 (x1, second 5), pending add 0\n\
+                node 1 [0..0]: sum 5, max 5 (x1, second {min}), min 5 (x1, second {max}), pending add 0\n\
+                node 2 [1..1]: sum 3, max 3 (x1, second {min}), min 3 (x1, second {max}), pending add 0\n",
+                min = i64::MIN,
+                max = i64::MAX
+            ),
+            tree.dump_text()
+        );
+    }
+
+    #[test]
+    fn test_build() {
+        let mut tree = SegmentTreeBeats::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+        assert_eq!(123, tree.sum(0, 9));
+        assert_eq!(31, tree.max(0, 9));
+        assert_eq!(2, tree.min(0, 9));
+        assert_eq!(54, tree.sum(3, 5));
+    }
+
+    #[test]
+    fn test_chmin() {
+        let mut tree = SegmentTreeBeats::build(&[5, 1, 4, 3, 2]);
+        tree.chmin(0, 1, 2);
+        //[2, 1, 4, 3, 2]
+        assert_eq!(4, tree.max(1, 3));
+        assert_eq!(2, tree.max(0, 1));
+        assert_eq!(12, tree.sum(0, 4));
+        tree.chmin(0, 4, 3);
+        //[2, 1, 3, 3, 2]
+        assert_eq!(11, tree.sum(0, 4));
+        assert_eq!(6, tree.sum(2, 3));
+    }
+
+    #[test]
+    fn test_chmax() {
+        let mut tree = SegmentTreeBeats::build(&[5, 1, 4, 3, 2]);
+        tree.chmax(1, 4, 3);
+        //[5, 3, 4, 3, 3]
+        assert_eq!(18, tree.sum(0, 4));
+        assert_eq!(3, tree.min(1, 4));
+        assert_eq!(5, tree.max(0, 4));
+    }
+
+    #[test]
+    fn test_add() {
+        let mut tree = SegmentTreeBeats::build(&[5, 1, 4, 3, 2]);
+        tree.add(1, 3, -2);
+        //[5, -1, 2, 1, 2]
+        assert_eq!(9, tree.sum(0, 4));
+        assert_eq!(-1, tree.min(0, 4));
+        assert_eq!(2, tree.max(1, 4));
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut tree = SegmentTreeBeats::build(&[8, 2, 6]);
+        assert_eq!(
+            Err(SegmentTreeError::RangeOutOfBounds {
+                start: 1,
+                end: 3,
+                len: 3
+            }),
+            tree.try_chmin(1, 3, 0)
+        );
+        assert!(tree.try_chmax(0, 3, 0).is_err());
+        assert!(tree.try_add(3, 3, 1).is_err());
+        assert!(tree.try_sum(0, 3).is_err());
+        assert!(tree.try_max(2, 3).is_err());
+        assert_eq!(Ok(2), tree.try_min(0, 2));
+        assert_eq!(vec![8, 2, 6], tree.to_vec());
+
+        let mut empty = SegmentTreeBeats::build(&[]);
+        assert_eq!(0, empty.sum(1, 0));
+        assert!(empty.try_sum(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_sentinel_bounds() {
+        let mut tree = SegmentTreeBeats::build(&[5, 3, 7, 1]);
+        tree.chmin(0, 3, i64::MIN);
+        assert_eq!(vec![i64::MIN; 4], tree.to_vec());
+        assert_eq!(i64::MIN, tree.max(0, 3));
+        assert_eq!(i64::MIN, tree.sum(2, 2));
+
+        let mut tree = SegmentTreeBeats::build(&[5, 3, 7, 1]);
+        tree.chmax(0, 3, i64::MAX);
+        assert_eq!(vec![i64::MAX; 4], tree.to_vec());
+        assert_eq!(i64::MAX, tree.min(0, 3));
+        assert_eq!(i64::MAX, tree.sum(1, 1));
+
+        // the sum of the first two elements wraps around, but the sum of the whole array fits
+        let mut tree = SegmentTreeBeats::build(&[i64::MAX, 1, -4]);
+        assert_eq!(i64::MAX - 3, tree.sum(0, 2));
+    }
+
+    #[test]
+    fn test_mixed_operations() {
+        let mut tree = SegmentTreeBeats::build(&[8, 2, 6, 21, 18]);
+        tree.chmin(1, 4, 10);
+        check_against_naive(&mut tree, &[8, 2, 6, 10, 10]);
+        tree.chmax(0, 2, 5);
+        check_against_naive(&mut tree, &[8, 5, 6, 10, 10]);
+        tree.add(2, 4, -7);
+        check_against_naive(&mut tree, &[8, 5, -1, 3, 3]);
+        // only 8 and 5 are above 4 ...
+        tree.chmin(0, 4, 4);
+        check_against_naive(&mut tree, &[4, 4, -1, 3, 3]);
+        // ... and only -1 below 0
+        tree.chmax(2, 3, 0);
+        check_against_naive(&mut tree, &[4, 4, 0, 3, 3]);
+        tree.add(0, 1, 2);
+        check_against_naive(&mut tree, &[6, 6, 0, 3, 3]);
+        tree.chmax(0, 4, 3);
+        check_against_naive(&mut tree, &[6, 6, 3, 3, 3]);
+    }
+}
@@ -0,0 +1,137 @@
+// Micro-benchmark comparing the recursive implicit layout of `IntegerSegmentTree` with the
+// iterative power-of-two layout of `BottomUpSegmentTree` on point updates and range max
+// queries, derived from the handson2 min_max test sets.
+//
+// Usage: cargo run --release -p segment_tree --bin layout_benchmark [iterations]
+//
+// This is a point-update benchmark, not the min_max workload: neither tree supports range
+// chmin updates, so only the first position of an update `0 l r T` is kept and the update
+// is replayed as the point update a[l] = min(a[l], T). Max queries are replayed as they are.
+use segment_tree::{BottomUpSegmentTree, IntegerSegmentTree};
+use std::cmp::max;
+use std::error::Error;
+use std::fs;
+use std::time::{Duration, Instant};
+
+enum Operation {
+    PointChmin { i: usize, t: i32 },
+    Max { l: usize, r: usize },
+}
+
+struct Workload {
+    array: Vec<i32>,
+    operations: Vec<Operation>,
+}
+
+fn parse_workload(input: &str) -> Result<Workload, Box<dyn Error>> {
+    let mut iter = input.split_whitespace();
+    let n: usize = iter.next().ok_or("missing n")?.parse()?;
+    let m: usize = iter.next().ok_or("missing m")?.parse()?;
+    let array = (0..n)
+        .map(|_| -> Result<i32, Box<dyn Error>> {
+            Ok(iter.next().ok_or("missing value")?.parse()?)
+        })
+        .collect::<Result<_, _>>()?;
+    let mut operations = Vec::with_capacity(m);
+    for _ in 0..m {
+        let query_type: u8 = iter.next().ok_or("missing query type")?.parse()?;
+        let l: usize = iter.next().ok_or("missing l")?.parse()?;
+        let r: usize = iter.next().ok_or("missing r")?.parse()?;
+        if query_type == 0 {
+            let t: i32 = iter.next().ok_or("missing val")?.parse()?;
+            // the end of the range is dropped, see the comment at the top of the file
+            operations.push(Operation::PointChmin { i: l - 1, t });
+        } else {
+            operations.push(Operation::Max { l: l - 1, r: r - 1 });
+        }
+    }
+    Ok(Workload { array, operations })
+}
+
+// Runs the workload `iterations` times, returning the elapsed time and a checksum of
+// all the answers, used to check that both layouts agree.
+fn run<T>(
+    workload: &Workload,
+    iterations: usize,
+    build: fn(&[i32]) -> T,
+    query: fn(&mut T, usize, usize) -> i32,
+    add: fn(&mut T, usize, i32),
+) -> (Duration, i64) {
+    let mut checksum = 0i64;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let mut array = workload.array.clone();
+        let mut tree = build(&array);
+        for operation in &workload.operations {
+            match *operation {
+                Operation::PointChmin { i, t } => {
+                    if t < array[i] {
+                        add(&mut tree, i, t - array[i]);
+                        array[i] = t;
+                    }
+                }
+                Operation::Max { l, r } => checksum += query(&mut tree, l, r) as i64,
+            }
+        }
+    }
+    (start.elapsed(), checksum)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let iterations: usize = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => 10_000,
+    };
+    let test_sets = format!(
+        "{}/../../hands-on/handson2/test_sets/min_max",
+        env!("CARGO_MANIFEST_DIR")
+    );
+
+    println!("point chmin updates and range max queries, {iterations} iterations");
+    println!(
+        "{:<10} {:>14} {:>14} {:>8}",
+        "test set", "recursive", "bottom-up", "speedup"
+    );
+    let mut total_recursive = Duration::ZERO;
+    let mut total_bottom_up = Duration::ZERO;
+    for i in 0..=10 {
+        let input = fs::read_to_string(format!("{test_sets}/input{i}.txt"))?;
+        let workload = parse_workload(&input)?;
+
+        let (recursive, recursive_checksum) = run(
+            &workload,
+            iterations,
+            |a| IntegerSegmentTree::build(a, i32::MIN, max),
+            |tree, l, r| tree.query(l, r),
+            |tree, i, val| tree.add(i, val),
+        );
+        let (bottom_up, bottom_up_checksum) = run(
+            &workload,
+            iterations,
+            |a| BottomUpSegmentTree::build(a, i32::MIN, max),
+            |tree, l, r| tree.query(l, r),
+            |tree, i, val| tree.add(i, val),
+        );
+        if recursive_checksum != bottom_up_checksum {
+            return Err(format!("the two layouts disagree on input{i}").into());
+        }
+
+        println!(
+            "{:<10} {:>14?} {:>14?} {:>7.2}x",
+            format!("input{i}"),
+            recursive,
+            bottom_up,
+            recursive.as_secs_f64() / bottom_up.as_secs_f64()
+        );
+        total_recursive += recursive;
+        total_bottom_up += bottom_up;
+    }
+    println!(
+        "{:<10} {:>14?} {:>14?} {:>7.2}x",
+        "total",
+        total_recursive,
+        total_bottom_up,
+        total_recursive.as_secs_f64() / total_bottom_up.as_secs_f64()
+    );
+    Ok(())
+}
@@ -0,0 +1,294 @@
+use crate::error::{SegmentTreeError, check_index, check_range};
+use crate::serialization::{
+    FORMAT_VERSION, TreeKind, read_header, read_i32, write_header, write_i32,
+};
+use std::fmt::Write;
+use std::io;
+
+/// A non-recursive segment tree with the same interface as `IntegerSegmentTree`.
+///
+/// The number of leaves is padded to the next power of two `size`, so the tree is a
+/// complete binary tree stored in breadth-first order: the root is at index 1, the
+/// children of node `k` are `2k` and `2k + 1` and leaf `i` is at index `size + i`.
+/// Every operation walks the tree bottom-up with a simple loop, and the nodes of a
+/// level are contiguous in memory.
+pub struct BottomUpSegmentTree {
+    tree: Vec<i32>,
+    size: usize,
+    num_leaf: usize,
+    neutral_value: i32,
+    merge_values: fn(i32, i32) -> i32,
+}
+
+impl BottomUpSegmentTree {
+    pub fn build_empty(
+        num_elems: usize,
+        neutral_value: i32,
+        merge_values: fn(i32, i32) -> i32,
+    ) -> Self {
+        Self::build(&vec![0; num_elems], neutral_value, merge_values)
+    }
+
+    pub fn build(a: &[i32], neutral_value: i32, merge_values: fn(i32, i32) -> i32) -> Self {
+        let size = a.len().next_power_of_two();
+        // padding leaves hold the neutral value so they never affect a query
+        let mut tree = vec![neutral_value; 2 * size];
+        tree[size..size + a.len()].copy_from_slice(a);
+        for index in (1..size).rev() {
+            tree[index] = merge_values(tree[2 * index], tree[2 * index + 1]);
+        }
+        Self {
+            tree,
+            size,
+            num_leaf: a.len(),
+            neutral_value,
+            merge_values,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_leaf
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_leaf == 0
+    }
+
+    pub fn query(&self, i: usize, j: usize) -> i32 {
+        self.try_query(i, j).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Returns the merge of the elements in `[i, j]`, the neutral value if the range is
+    /// empty (`i > j`) or an error if it ends past the last element.
+    pub fn try_query(&self, i: usize, j: usize) -> Result<i32, SegmentTreeError> {
+        check_range(i, j, self.num_leaf)?;
+        if i > j {
+            return Ok(self.neutral_value);
+        }
+        let merge = self.merge_values;
+        // [l, r) is the half-open range of nodes still to be merged at the current level:
+        // a left border that is a right child (odd) or a right border whose previous node
+        // is a left child cannot be covered by their parent, so they are merged right away
+        let mut l = i + self.size;
+        let mut r = j + self.size + 1;
+        // the two sides are kept separate so that a non-commutative merge is applied in order
+        let mut left_acc = self.neutral_value;
+        let mut right_acc = self.neutral_value;
+        while l < r {
+            if l & 1 == 1 {
+                left_acc = merge(left_acc, self.tree[l]);
+                l += 1;
+            }
+            if r & 1 == 1 {
+                r -= 1;
+                right_acc = merge(self.tree[r], right_acc);
+            }
+            l >>= 1;
+            r >>= 1;
+        }
+        Ok(merge(left_acc, right_acc))
+    }
+
+    pub fn add(&mut self, i: usize, val: i32) {
+        self.try_add(i, val).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Adds `val` to the element at position `i`, or fails if there is no such element.
+    pub fn try_add(&mut self, i: usize, val: i32) -> Result<(), SegmentTreeError> {
+        check_index(i, self.num_leaf)?;
+        let mut index = i + self.size;
+        self.tree[index] += val;
+        while index > 1 {
+            index >>= 1;
+            self.tree[index] = (self.merge_values)(self.tree[2 * index], self.tree[2 * index + 1]);
+        }
+        Ok(())
+    }
+
+    /// Writes the tree in the little-endian binary format shared by the trees of this
+    /// crate: a versioned header followed by the neutral value and the `2 * size` nodes in
+    /// breadth-first order. The merge function is not saved.
+    ///
+    /// Every number is written separately, so `writer` should be buffered.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, TreeKind::BottomUp, self.num_leaf)?;
+        write_i32(writer, self.neutral_value)?;
+        for &value in &self.tree {
+            write_i32(writer, value)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tree written by `save`, that will merge values with `merge_values`: it must
+    /// be the same merge function the tree has been saved with.
+    pub fn load<R: io::Read>(
+        reader: &mut R,
+        merge_values: fn(i32, i32) -> i32,
+    ) -> io::Result<Self> {
+        let num_leaf = read_header(reader, TreeKind::BottomUp, size_of::<i32>())?;
+        let neutral_value = read_i32(reader)?;
+        let size = num_leaf.next_power_of_two();
+        let tree = (0..2 * size)
+            .map(|_| read_i32(reader))
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            tree,
+            size,
+            num_leaf,
+            neutral_value,
+            merge_values,
+        })
+    }
+
+    /// Describes the tree in a human-readable form, one node per line in breadth-first
+    /// order, starting from the root.
+    pub fn dump_text(&self) -> String {
+        let mut text = format!(
+            "BottomUpSegmentTree (format version {FORMAT_VERSION})\nleaves: {}\nsize: {}\nneutral value: {}\n",
+            self.num_leaf, self.size, self.neutral_value
+        );
+        for (index, value) in self.tree.iter().enumerate().skip(1) {
+            // writing to a String never fails
+            if index >= self.size {
+                writeln!(
+                    text,
+                    "node {index} (leaf {}): value {value}",
+                    index - self.size
+                )
+                .unwrap();
+            } else {
+                writeln!(text, "node {index}: value {value}").unwrap();
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bottom_up_segment_tree::BottomUpSegmentTree;
+    use crate::error::SegmentTreeError;
+    use crate::integer_segment_tree::IntegerSegmentTree;
+    use std::cmp::{max, min};
+
+    #[test]
+    fn test_query_sum() {
+        let tree = BottomUpSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4], 0, |x, y| x + y);
+        assert_eq!(31, tree.query(0, 6));
+        assert_eq!(9, tree.query(1, 2));
+        assert_eq!(16, tree.query(4, 6));
+        assert_eq!(8, tree.query(4, 4));
+        assert_eq!(0, tree.query(4, 3));
+    }
+
+    #[test]
+    fn test_min_query() {
+        let tree = BottomUpSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4, 5], i32::MAX, min);
+        assert_eq!(3, tree.query(1, 4));
+        assert_eq!(-4, tree.query(0, 7));
+        assert_eq!(8, tree.query(4, 5));
+    }
+
+    #[test]
+    fn test_add() {
+        let mut tree = BottomUpSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4], 0, |x, y| x + y);
+        tree.add(4, -8);
+        assert_eq!(23, tree.query(0, 6));
+        assert_eq!(0, tree.query(4, 4));
+        assert_eq!(8, tree.query(4, 6));
+    }
+
+    #[test]
+    fn test_multiple_add() {
+        let mut tree = BottomUpSegmentTree::build_empty(10, 0, |x, y| x + y);
+        for i in 0..5 {
+            tree.add(i, 1);
+        }
+        assert_eq!(1, tree.query(4, 5));
+        assert_eq!(5, tree.query(0, 9));
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        // 7 leaves padded to 8: the padding leaf must not be reachable
+        let mut tree = BottomUpSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4], i32::MAX, min);
+        assert_eq!(
+            Err(SegmentTreeError::IndexOutOfBounds { index: 7, len: 7 }),
+            tree.try_add(7, -100)
+        );
+        assert_eq!(
+            Err(SegmentTreeError::RangeOutOfBounds {
+                start: 5,
+                end: 7,
+                len: 7
+            }),
+            tree.try_query(5, 7)
+        );
+        assert_eq!(Ok(-4), tree.try_query(5, 6));
+        assert_eq!(
+            i32::MAX,
+            BottomUpSegmentTree::build(&[], i32::MAX, min).query(1, 0)
+        );
+    }
+
+    #[test]
+    fn test_non_commutative_merge() {
+        // keeps the leftmost non-zero value
+        let first_non_zero = |x, y| if x != 0 { x } else { y };
+        let tree = BottomUpSegmentTree::build(&[0, 0, 7, 0, 3, 9, 0], 0, first_non_zero);
+        assert_eq!(7, tree.query(0, 6));
+        assert_eq!(3, tree.query(3, 6));
+        assert_eq!(9, tree.query(5, 6));
+        assert_eq!(0, tree.query(6, 6));
+    }
+
+    #[test]
+    fn test_same_answers_as_recursive_layout() {
+        let a = [8, 2, 6, 21, 18, 15, 2, 31, 4, 16, -3];
+        let bottom_up = BottomUpSegmentTree::build(&a, i32::MIN, max);
+        let mut recursive = IntegerSegmentTree::build(&a, i32::MIN, max);
+        for i in 0..a.len() {
+            for j in i..a.len() {
+                assert_eq!(recursive.query(i, j), bottom_up.query(i, j));
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut original = BottomUpSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4], i32::MAX, min);
+        original.add(2, -7);
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+        // header, neutral value and the 16 nodes of a tree padded to 8 leaves
+        assert_eq!(15 + 4 + 16 * 4, bytes.len());
+
+        let loaded = BottomUpSegmentTree::load(&mut bytes.as_slice(), min).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        for i in 0..7 {
+            for j in i..7 {
+                assert_eq!(original.query(i, j), loaded.query(i, j));
+            }
+        }
+        assert!(IntegerSegmentTree::load(&mut bytes.as_slice(), min).is_err());
+    }
+
+    #[test]
+    fn test_dump_text() {
+        let tree = BottomUpSegmentTree::build(&[1, 2, 3], 0, |x, y| x + y);
+        assert_eq!(
+            "BottomUpSegmentTree (format version 1)\n\
+            leaves: 3\n\
+            size: 4\n\
+            neutral value: 0\n\
+            node 1: value 6\n\
+            node 2: value 3\n\
+            node 3: value 3\n\
+            node 4 (leaf 0): value 1\n\
+            node 5 (leaf 1): value 2\n\
+            node 6 (leaf 2): value 3\n\
+            node 7 (leaf 3): value 0\n",
+            tree.dump_text()
+        );
+    }
+}
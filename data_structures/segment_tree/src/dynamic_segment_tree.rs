@@ -0,0 +1,575 @@
+use crate::error::SegmentTreeError;
+use crate::serialization::{
+    Encode, FORMAT_VERSION, TreeKind, invalid_data, read_header, read_i64, write_header, write_i64,
+};
+use std::cmp::{max, min};
+use std::fmt::Write;
+use std::io;
+
+// A closed interval of coordinates: the same role `Range` has for the dense trees, but over
+// i64 so that it can describe the whole coordinate universe. Sizes and midpoints are
+// computed in i128 since the size of i64::MIN..=i64::MAX does not fit in 64 bits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Span {
+    pub(crate) start: i64,
+    pub(crate) end: i64,
+}
+
+impl Span {
+    pub(crate) fn new(start: i64, end: i64) -> Self {
+        Span { start, end }
+    }
+
+    pub(crate) fn contains(&self, other: Span) -> bool {
+        self.start <= other.start && self.end >= other.end
+    }
+
+    pub(crate) fn no_overlap(&self, other: Span) -> bool {
+        self.end < other.start || other.end < self.start
+    }
+
+    pub(crate) fn intersect(&self, other: Span) -> Self {
+        Span::new(max(self.start, other.start), min(self.end, other.end))
+    }
+
+    pub(crate) fn size(&self) -> i128 {
+        self.end as i128 - self.start as i128 + 1
+    }
+
+    pub(crate) fn middle(&self) -> i64 {
+        (self.start as i128 + (self.end as i128 - self.start as i128) / 2) as i64
+    }
+
+    pub(crate) fn left_half(&self) -> Self {
+        Span::new(self.start, self.middle())
+    }
+
+    pub(crate) fn right_half(&self) -> Self {
+        Span::new(self.middle() + 1, self.end)
+    }
+}
+
+#[derive(Default)]
+struct Node {
+    // sum of the whole segment, including every addition ever made on it
+    sum: i128,
+    // value added to each element of the segment by range updates that covered it entirely:
+    // it is never pushed down, queries that only partially cover the segment account for it.
+    // It is an i128 like the sums, since many additions can stack on the same node
+    pending_add: i128,
+    id_left: Option<usize>,
+    id_right: Option<usize>,
+}
+
+/// A sum segment tree over a huge coordinate universe (by default the whole i64 range)
+/// that allocates its nodes only when an update reaches them.
+///
+/// Each operation creates at most O(log U) nodes, where U is the size of the universe, so
+/// coordinates do not need to be compressed in advance and updates can arrive online.
+/// Sums are returned as i128 since a range update may cover up to 2^64 positions, and an
+/// update that would overflow them is rejected (see `try_range_add`).
+pub struct DynamicSegmentTree {
+    nodes: Vec<Node>,
+    universe: Span,
+}
+
+impl Default for DynamicSegmentTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DynamicSegmentTree {
+    /// Creates an all-zero tree over `i64::MIN..=i64::MAX`.
+    pub fn new() -> Self {
+        Self::with_bounds(i64::MIN, i64::MAX)
+    }
+
+    /// Creates an all-zero tree over `min_coord..=max_coord`.
+    pub fn with_bounds(min_coord: i64, max_coord: i64) -> Self {
+        assert!(min_coord <= max_coord, "Empty coordinate universe");
+        Self {
+            // the root always exists
+            nodes: vec![Node::default()],
+            universe: Span::new(min_coord, max_coord),
+        }
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Adds `val` to the element at coordinate `i`.
+    pub fn add(&mut self, i: i64, val: i64) {
+        self.range_add(i, i, val);
+    }
+
+    /// Like `add`, but fails without touching the tree if some sum would overflow.
+    pub fn try_add(&mut self, i: i64, val: i64) -> Result<(), SegmentTreeError> {
+        self.try_range_add(i, i, val)
+    }
+
+    /// Adds `val` to every element with coordinate in `[i, j]`.
+    pub fn range_add(&mut self, i: i64, j: i64, val: i64) {
+        self.try_range_add(i, j, val)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `range_add`, but fails without touching the tree if the sum of some segment, or
+    /// the additions recorded on it, would not fit in an i128 anymore: a couple of
+    /// additions of values close to `i64::MAX` on the whole universe are enough.
+    ///
+    /// # Panics
+    /// if `[i, j]` is not empty and not inside the coordinate universe.
+    pub fn try_range_add(&mut self, i: i64, j: i64, val: i64) -> Result<(), SegmentTreeError> {
+        if i > j {
+            return Ok(());
+        }
+        let update_range = Span::new(i, j);
+        assert!(
+            self.universe.contains(update_range),
+            "Range [{i}, {j}] out of bounds"
+        );
+        if !self.add_fits_rec(update_range, self.universe, Some(0), val) {
+            return Err(SegmentTreeError::SumOverflow);
+        }
+        self.range_add_rec(update_range, self.universe, 0, val);
+        Ok(())
+    }
+
+    // Visits the nodes that range_add_rec would change, without creating the missing ones,
+    // and checks that none of them overflows. A product `val * overlap` always fits in an
+    // i128, as |val| <= 2^63 and overlap <= 2^64.
+    fn add_fits_rec(
+        &self,
+        update_range: Span,
+        node_segment: Span,
+        node_id: Option<usize>,
+        val: i64,
+    ) -> bool {
+        let node = node_id.map(|id| &self.nodes[id]);
+        let (sum, pending_add) = node.map_or((0, 0), |node| (node.sum, node.pending_add));
+        let overlap = update_range.intersect(node_segment).size();
+        if sum.checked_add(val as i128 * overlap).is_none() {
+            return false;
+        }
+        if update_range.contains(node_segment) {
+            return pending_add.checked_add(val as i128).is_some();
+        }
+        let (id_left, id_right) = node.map_or((None, None), |node| (node.id_left, node.id_right));
+        (update_range.no_overlap(node_segment.left_half())
+            || self.add_fits_rec(update_range, node_segment.left_half(), id_left, val))
+            && (update_range.no_overlap(node_segment.right_half())
+                || self.add_fits_rec(update_range, node_segment.right_half(), id_right, val))
+    }
+
+    fn range_add_rec(&mut self, update_range: Span, node_segment: Span, node_id: usize, val: i64) {
+        let overlap = update_range.intersect(node_segment).size();
+        self.nodes[node_id].sum += val as i128 * overlap;
+        if update_range.contains(node_segment) {
+            // total overlap: record the addition here instead of creating the subtree
+            self.nodes[node_id].pending_add += val as i128;
+            return;
+        }
+        // partial overlap: only the children that overlap the update are created
+        if !update_range.no_overlap(node_segment.left_half()) {
+            let id_left = self.left_child(node_id);
+            self.range_add_rec(update_range, node_segment.left_half(), id_left, val);
+        }
+        if !update_range.no_overlap(node_segment.right_half()) {
+            let id_right = self.right_child(node_id);
+            self.range_add_rec(update_range, node_segment.right_half(), id_right, val);
+        }
+    }
+
+    fn left_child(&mut self, node_id: usize) -> usize {
+        match self.nodes[node_id].id_left {
+            Some(id) => id,
+            None => {
+                let id = self.new_node();
+                self.nodes[node_id].id_left = Some(id);
+                id
+            }
+        }
+    }
+
+    fn right_child(&mut self, node_id: usize) -> usize {
+        match self.nodes[node_id].id_right {
+            Some(id) => id,
+            None => {
+                let id = self.new_node();
+                self.nodes[node_id].id_right = Some(id);
+                id
+            }
+        }
+    }
+
+    fn new_node(&mut self) -> usize {
+        self.nodes.push(Node::default());
+        self.nodes.len() - 1
+    }
+
+    /// Returns the sum of the elements with coordinate in `[i, j]`.
+    pub fn query(&self, i: i64, j: i64) -> i128 {
+        if i > j {
+            return 0;
+        }
+        self.query_rec(Span::new(i, j), self.universe, Some(0))
+    }
+
+    fn query_rec(&self, query_range: Span, node_segment: Span, node_id: Option<usize>) -> i128 {
+        // a node that has never been created holds only zeros
+        let Some(node_id) = node_id else {
+            return 0;
+        };
+        if query_range.no_overlap(node_segment) {
+            return 0;
+        }
+        let node = &self.nodes[node_id];
+        if query_range.contains(node_segment) {
+            return node.sum;
+        }
+        // partial overlap: the additions recorded on this node are not included in the
+        // children's sums, so they are counted here for the overlapping positions only
+        let overlap = query_range.intersect(node_segment).size();
+        node.pending_add * overlap
+            + self.query_rec(query_range, node_segment.left_half(), node.id_left)
+            + self.query_rec(query_range, node_segment.right_half(), node.id_right)
+    }
+
+    /// Returns the smallest coordinate `c` such that the sum of the elements in
+    /// `[min_coord, c]` is at least `target`, or `None` if the sum of all the elements is
+    /// smaller. No element can be negative, so that the prefix sums never decrease.
+    pub fn lower_bound(&self, target: i128) -> Option<i64> {
+        if target <= 0 {
+            return Some(self.universe.start);
+        }
+        if self.nodes[0].sum < target {
+            return None;
+        }
+        let mut target = target;
+        let mut node_segment = self.universe;
+        let mut node_id = Some(0);
+        // additions recorded on the ancestors, that apply to every position below them
+        let mut pending: i128 = 0;
+        // the sum of node_segment, pending included, is always at least target
+        while node_segment.start != node_segment.end {
+            let Some(id) = node_id else {
+                // a node that has never been created holds `pending` in each position
+                let offset = (target + pending - 1) / pending - 1;
+                return Some((node_segment.start as i128 + offset) as i64);
+            };
+            let node = &self.nodes[id];
+            pending += node.pending_add;
+            let left_half = node_segment.left_half();
+            let left_sum = node.id_left.map_or(0, |id_left| self.nodes[id_left].sum)
+                + pending * left_half.size();
+            if left_sum >= target {
+                node_segment = left_half;
+                node_id = node.id_left;
+            } else {
+                target -= left_sum;
+                node_segment = node_segment.right_half();
+                node_id = node.id_right;
+            }
+        }
+        Some(node_segment.start)
+    }
+
+    /// Writes the tree in the little-endian binary format shared by the trees of this
+    /// crate: a versioned header holding the number of nodes, the bounds of the universe
+    /// and, for each node by increasing id, its sum, its recorded additions and the ids of
+    /// its children, if any.
+    ///
+    /// Every number is written separately, so `writer` should be buffered.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, TreeKind::Dynamic, self.nodes.len())?;
+        write_i64(writer, self.universe.start)?;
+        write_i64(writer, self.universe.end)?;
+        for node in &self.nodes {
+            node.sum.encode(writer)?;
+            node.pending_add.encode(writer)?;
+            node.id_left.map(|id| id as u64).encode(writer)?;
+            node.id_right.map(|id| id as u64).encode(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tree written by `save`.
+    ///
+    /// The nodes are checked to form a tree rooted in node 0, shaped like the universe,
+    /// whose sums agree with the additions recorded on them and the sums of their
+    /// children: a corrupt file is rejected instead of making later operations fail.
+    pub fn load<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let num_nodes = read_header(reader, TreeKind::Dynamic, size_of::<Node>())?;
+        let (start, end) = (read_i64(reader)?, read_i64(reader)?);
+        if start > end || num_nodes == 0 {
+            return Err(invalid_data("Empty coordinate universe".to_string()));
+        }
+        // the nodes grow as they are read, so a corrupt count fails on the missing bytes
+        // instead of allocating
+        let mut nodes = Vec::new();
+        while nodes.len() < num_nodes {
+            let father = nodes.len();
+            let node = Node {
+                sum: i128::decode(reader)?,
+                pending_add: i128::decode(reader)?,
+                id_left: read_child(reader, father, num_nodes)?,
+                id_right: read_child(reader, father, num_nodes)?,
+            };
+            nodes.push(node);
+        }
+        let mut has_father = vec![false; num_nodes];
+        for node in &nodes {
+            for id in node.id_left.into_iter().chain(node.id_right) {
+                if has_father[id] {
+                    return Err(invalid_data(format!("Node {id} has two fathers")));
+                }
+                has_father[id] = true;
+            }
+        }
+        let tree = Self {
+            nodes,
+            universe: Span::new(start, end),
+        };
+        tree.check_sums()?;
+        Ok(tree)
+    }
+
+    // Visits the tree from the root, checking that no single position has children and that
+    // every sum is the one range_add_rec would have left.
+    fn check_sums(&self) -> io::Result<()> {
+        let mut stack = vec![(0, self.universe)];
+        while let Some((id, node_segment)) = stack.pop() {
+            let node = &self.nodes[id];
+            let mut expected = node.pending_add.checked_mul(node_segment.size());
+            if node.id_left.is_some() || node.id_right.is_some() {
+                if node_segment.start == node_segment.end {
+                    return Err(invalid_data(format!(
+                        "Node {id} of a single position has children"
+                    )));
+                }
+                for (child, half) in [
+                    (node.id_left, node_segment.left_half()),
+                    (node.id_right, node_segment.right_half()),
+                ] {
+                    if let Some(child) = child {
+                        expected = expected.and_then(|sum| sum.checked_add(self.nodes[child].sum));
+                        stack.push((child, half));
+                    }
+                }
+            }
+            if expected != Some(node.sum) {
+                return Err(invalid_data(format!("Inconsistent sum in node {id}")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Describes the tree in a human-readable form, one node per line in preorder.
+    pub fn dump_text(&self) -> String {
+        let mut text = format!(
+            "DynamicSegmentTree (format version {FORMAT_VERSION})\nuniverse: [{}..{}]\nnodes: {}\n",
+            self.universe.start,
+            self.universe.end,
+            self.nodes.len()
+        );
+        self.dump_text_rec(self.universe, 0, &mut text);
+        text
+    }
+
+    fn dump_text_rec(&self, node_segment: Span, node_id: usize, text: &mut String) {
+        let node = &self.nodes[node_id];
+        // writing to a String never fails
+        writeln!(
+            text,
+            "node {node_id} [{}..{}]: sum {}, pending add {}",
+            node_segment.start, node_segment.end, node.sum, node.pending_add
+        )
+        .unwrap();
+        if let Some(id_left) = node.id_left {
+            self.dump_text_rec(node_segment.left_half(), id_left, text);
+        }
+        if let Some(id_right) = node.id_right {
+            self.dump_text_rec(node_segment.right_half(), id_right, text);
+        }
+    }
+}
+
+// A node is always created after its father, so the id of a child must be larger than the
+// one of its father.
+fn read_child<R: io::Read>(
+    reader: &mut R,
+    father: usize,
+    num_nodes: usize,
+) -> io::Result<Option<usize>> {
+    let Some(id) = Option::<u64>::decode(reader)? else {
+        return Ok(None);
+    };
+    match usize::try_from(id) {
+        Ok(id) if father < id && id < num_nodes => Ok(Some(id)),
+        _ => Err(invalid_data(format!("Invalid child {id} of node {father}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dynamic_segment_tree::DynamicSegmentTree;
+    use crate::error::SegmentTreeError;
+
+    #[test]
+    fn test_point_add() {
+        let mut tree = DynamicSegmentTree::new();
+        tree.add(-1_000_000_000_000, 3);
+        tree.add(42, 5);
+        tree.add(i64::MAX, 7);
+        tree.add(i64::MIN, 11);
+        assert_eq!(26, tree.query(i64::MIN, i64::MAX));
+        assert_eq!(8, tree.query(-1_000_000_000_000, 42));
+        assert_eq!(5, tree.query(0, 100));
+        assert_eq!(7, tree.query(43, i64::MAX));
+        assert_eq!(11, tree.query(i64::MIN, i64::MIN));
+        assert_eq!(0, tree.query(43, 44));
+    }
+
+    #[test]
+    fn test_range_add() {
+        let mut tree = DynamicSegmentTree::new();
+        tree.range_add(-5, 5, 2);
+        tree.range_add(0, 9, -1);
+        // -5..=-1 -> 2, 0..=5 -> 1, 6..=9 -> -1
+        assert_eq!(2 * 5 + 6 - 4, tree.query(-100, 100));
+        assert_eq!(2, tree.query(-3, -3));
+        assert_eq!(3, tree.query(-1, 0));
+        assert_eq!(-2, tree.query(8, 1_000));
+    }
+
+    #[test]
+    fn test_range_add_whole_universe() {
+        let mut tree = DynamicSegmentTree::new();
+        tree.range_add(i64::MIN, i64::MAX, 1);
+        tree.add(0, 1);
+        assert_eq!(
+            1i128 << 64,
+            tree.query(i64::MIN, -1) + tree.query(0, i64::MAX) - 1
+        );
+        assert_eq!(2, tree.query(0, 0));
+        assert_eq!(1000, tree.query(1, 1000));
+        // the range update on the whole universe is recorded on the root only, while the
+        // point update creates a node on each of the 64 levels below it
+        assert_eq!(65, tree.num_nodes());
+    }
+
+    #[test]
+    fn test_pending_adds_past_i64() {
+        let mut tree = DynamicSegmentTree::with_bounds(0, 9);
+        tree.range_add(0, 9, i64::MAX);
+        tree.range_add(0, 9, i64::MAX);
+        tree.range_add(0, 9, 2);
+        assert_eq!(1i128 << 64, tree.query(3, 3));
+        assert_eq!(3i128 << 64, tree.query(2, 4));
+    }
+
+    #[test]
+    fn test_sum_overflow() {
+        let mut tree = DynamicSegmentTree::new();
+        // 2^64 * (2^63 - 1) is just below i128::MAX = 2^127 - 1
+        tree.range_add(i64::MIN, i64::MAX, i64::MAX);
+        assert_eq!(
+            Err(SegmentTreeError::SumOverflow),
+            tree.try_range_add(i64::MIN, i64::MAX, 1)
+        );
+        // the sum of the left half still fits, but not the one of the root
+        assert_eq!(
+            Err(SegmentTreeError::SumOverflow),
+            tree.try_range_add(i64::MIN, -1, i64::MAX)
+        );
+        assert_eq!(1, tree.num_nodes());
+        assert_eq!(Ok(()), tree.try_add(0, -1));
+        assert_eq!(i64::MAX as i128 - 1, tree.query(0, 0));
+        assert_eq!(i64::MAX as i128, tree.query(1, 1));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut original = DynamicSegmentTree::new();
+        original.range_add(-5, 5, 2);
+        original.add(i64::MAX, 7);
+        original.range_add(i64::MIN, i64::MAX, 1);
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+
+        let loaded = DynamicSegmentTree::load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        assert_eq!(original.num_nodes(), loaded.num_nodes());
+        assert_eq!(3 * 11 + 1, loaded.query(-5, 6));
+        assert_eq!(8, loaded.query(i64::MAX, i64::MAX));
+        assert_eq!(Some(-5), loaded.lower_bound((1i128 << 63) - 5 + 3));
+
+        let mut truncated = bytes.clone();
+        truncated.pop();
+        assert!(DynamicSegmentTree::load(&mut truncated.as_slice()).is_err());
+        // the low byte of the sum of the root, right after the header and the universe
+        let mut inconsistent = bytes.clone();
+        inconsistent[15 + 16] ^= 1;
+        assert!(DynamicSegmentTree::load(&mut inconsistent.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_dump_text() {
+        let mut tree = DynamicSegmentTree::with_bounds(0, 3);
+        tree.range_add(0, 1, 5);
+        tree.add(3, 1);
+        assert_eq!(
+            "DynamicSegmentTree (format version 1)\n\
+            universe: [0..3]\n\
+            nodes: 4\n\
+            node 0 [0..3]: sum 11, pending add 0\n\
+            node 1 [0..1]: sum 10, pending add 5\n\
+            node 2 [2..3]: sum 1, pending add 0\n\
+            node 3 [3..3]: sum 1, pending add 1\n",
+            tree.dump_text()
+        );
+    }
+
+    #[test]
+    fn test_with_bounds() {
+        let mut tree = DynamicSegmentTree::with_bounds(0, 9);
+        tree.range_add(0, 9, 1);
+        tree.range_add(3, 6, 2);
+        assert_eq!(18, tree.query(0, 9));
+        assert_eq!(7, tree.query(2, 4));
+    }
+
+    #[test]
+    fn test_lower_bound() {
+        let mut tree = DynamicSegmentTree::new();
+        assert_eq!(None, tree.lower_bound(1));
+        tree.add(-50, 2);
+        tree.add(7, 1);
+        tree.range_add(100, 199, 3);
+        assert_eq!(Some(i64::MIN), tree.lower_bound(0));
+        assert_eq!(Some(-50), tree.lower_bound(2));
+        assert_eq!(Some(7), tree.lower_bound(3));
+        assert_eq!(Some(100), tree.lower_bound(4));
+        assert_eq!(Some(101), tree.lower_bound(7));
+        assert_eq!(Some(199), tree.lower_bound(303));
+        assert_eq!(None, tree.lower_bound(304));
+
+        let mut whole = DynamicSegmentTree::new();
+        whole.range_add(i64::MIN, i64::MAX, 2);
+        assert_eq!(Some(i64::MIN + 4), whole.lower_bound(10));
+        assert_eq!(Some(-1), whole.lower_bound(1i128 << 64));
+    }
+
+    #[test]
+    fn test_nodes_are_created_lazily() {
+        let mut tree = DynamicSegmentTree::new();
+        tree.add(12345, 1);
+        // the root plus one node for each of the 64 levels below it
+        assert_eq!(65, tree.num_nodes());
+        tree.range_add(-7, 1_000_000, 1);
+        assert!(tree.num_nodes() <= 65 + 4 * 64);
+        assert_eq!(1_000_009, tree.query(i64::MIN, i64::MAX));
+    }
+}
@@ -0,0 +1,64 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// The reasons an operation on a segment tree can be rejected by its `try_` variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentTreeError {
+    /// `index` is not a position of a tree with `len` leaves.
+    IndexOutOfBounds { index: usize, len: usize },
+    /// The range `[start, end]` is not empty but ends past the last of the `len` leaves.
+    RangeOutOfBounds {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    /// The implicit tree for `len` leaves does not fit in memory.
+    TooManyElements { len: usize },
+    /// An update would take a sum kept by the tree past the range of its type.
+    SumOverflow,
+}
+
+impl Display for SegmentTreeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SegmentTreeError::IndexOutOfBounds { index, len } => {
+                write!(f, "Index {index} out of bounds for {len} elements")
+            }
+            SegmentTreeError::RangeOutOfBounds { start, end, len } => {
+                write!(f, "Range [{start}, {end}] out of bounds for {len} elements")
+            }
+            SegmentTreeError::TooManyElements { len } => {
+                write!(f, "Too many elements for a segment tree: {len}")
+            }
+            SegmentTreeError::SumOverflow => write!(f, "The update overflows the sums"),
+        }
+    }
+}
+
+impl Error for SegmentTreeError {}
+
+pub(crate) fn check_index(index: usize, len: usize) -> Result<(), SegmentTreeError> {
+    if index >= len {
+        return Err(SegmentTreeError::IndexOutOfBounds { index, len });
+    }
+    Ok(())
+}
+
+// an empty range (start > end) is always valid and selects no element
+pub(crate) fn check_range(start: usize, end: usize, len: usize) -> Result<(), SegmentTreeError> {
+    if start <= end && end >= len {
+        return Err(SegmentTreeError::RangeOutOfBounds { start, end, len });
+    }
+    Ok(())
+}
+
+// an implicit tree over `len` leaves has 2 * len - 1 nodes of `node_size` bytes, and no
+// allocation can be larger than isize::MAX bytes
+pub(crate) fn check_num_nodes(len: usize, node_size: usize) -> Result<(), SegmentTreeError> {
+    let fits = (2 * len as u128) * (node_size as u128) <= isize::MAX as u128;
+    if !fits {
+        return Err(SegmentTreeError::TooManyElements { len });
+    }
+    Ok(())
+}
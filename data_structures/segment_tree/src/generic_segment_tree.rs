@@ -0,0 +1,540 @@
+use crate::error::{SegmentTreeError, check_index, check_range};
+use crate::range::{Range, left_right_child_index};
+use crate::serialization::{Encode, FORMAT_VERSION, TreeKind, read_header, write_header};
+use std::fmt::{Debug, Write};
+use std::io;
+
+/// The algebra aggregated by a [`SegmentTree`]: an associative `combine` over `Value`
+/// together with its `identity` element.
+///
+/// `combine` does not need to be commutative: the tree always merges the left segment
+/// with the right one, so ordered aggregates (e.g. matrix products) are supported.
+pub trait Monoid {
+    type Value: Clone;
+
+    fn identity() -> Self::Value;
+
+    fn combine(a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+/// A segment tree over any [`Monoid`], stored with the same implicit layout used by
+/// `IntegerSegmentTree`: the left child of a node is the next cell and the right child
+/// comes right after the whole left subtree.
+pub struct SegmentTree<M: Monoid> {
+    tree: Vec<M::Value>,
+    num_leaf: usize,
+}
+
+impl<M: Monoid> SegmentTree<M> {
+    pub fn build_empty(num_elems: usize) -> Self {
+        Self {
+            tree: vec![M::identity(); Self::num_nodes(num_elems)],
+            num_leaf: num_elems,
+        }
+    }
+
+    pub fn build(a: &[M::Value]) -> Self {
+        let mut implicit_tree = vec![M::identity(); Self::num_nodes(a.len())];
+        if !a.is_empty() {
+            Self::build_rec(a, &mut implicit_tree, Range::new(0, a.len() - 1), 0);
+        }
+        Self {
+            tree: implicit_tree,
+            num_leaf: a.len(),
+        }
+    }
+
+    // a tree with n leaves has n - 1 internal nodes; the empty tree has no node at all
+    fn num_nodes(num_leaf: usize) -> usize {
+        (2 * num_leaf).saturating_sub(1)
+    }
+
+    fn build_rec(a: &[M::Value], tree: &mut [M::Value], node_segment: Range, index: usize) {
+        if node_segment.is_single_point() {
+            tree[index] = a[node_segment.start].clone();
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        Self::build_rec(a, tree, node_segment.left_half(), left_child_index);
+        Self::build_rec(a, tree, node_segment.right_half(), right_child_index);
+        tree[index] = M::combine(&tree[left_child_index], &tree[right_child_index]);
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_leaf
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_leaf == 0
+    }
+
+    /// Returns the value currently stored at position `i`.
+    pub fn get(&self, i: usize) -> M::Value {
+        check_index(i, self.num_leaf).unwrap_or_else(|e| panic!("{e}"));
+        let mut node_segment = Range::new(0, self.num_leaf - 1);
+        let mut index = 0;
+        while !node_segment.is_single_point() {
+            let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+            if i <= node_segment.middle() {
+                node_segment = node_segment.left_half();
+                index = left_child_index;
+            } else {
+                node_segment = node_segment.right_half();
+                index = right_child_index;
+            }
+        }
+        self.tree[index].clone()
+    }
+
+    /// Replaces the value at position `i` with `value`, recomputing every ancestor.
+    pub fn update(&mut self, i: usize, value: M::Value) {
+        self.try_update(i, value).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `update`, but fails if there is no element at position `i`.
+    pub fn try_update(&mut self, i: usize, value: M::Value) -> Result<(), SegmentTreeError> {
+        check_index(i, self.num_leaf)?;
+        self.update_rec(i, value, Range::new(0, self.num_leaf - 1), 0);
+        Ok(())
+    }
+
+    fn update_rec(&mut self, i: usize, value: M::Value, node_segment: Range, index: usize) {
+        if node_segment.is_single_point() {
+            self.tree[index] = value;
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        if i <= node_segment.middle() {
+            self.update_rec(i, value, node_segment.left_half(), left_child_index);
+        } else {
+            self.update_rec(i, value, node_segment.right_half(), right_child_index);
+        }
+        self.tree[index] = M::combine(&self.tree[left_child_index], &self.tree[right_child_index]);
+    }
+
+    /// Combines, from left to right, the values in positions `[i, j]`.
+    pub fn query(&self, i: usize, j: usize) -> M::Value {
+        self.try_query(i, j).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `query`, returning the identity if the range is empty (`i > j`) or an error if
+    /// it ends past the last element.
+    pub fn try_query(&self, i: usize, j: usize) -> Result<M::Value, SegmentTreeError> {
+        check_range(i, j, self.num_leaf)?;
+        if i > j {
+            return Ok(M::identity());
+        }
+        Ok(self.query_rec(Range::new(i, j), Range::new(0, self.num_leaf - 1), 0))
+    }
+
+    fn query_rec(&self, query_range: Range, node_segment: Range, index: usize) -> M::Value {
+        if query_range.no_overlap(node_segment) {
+            return M::identity();
+        }
+        if query_range.contains(node_segment) {
+            return self.tree[index].clone();
+        }
+        // partial overlap: the left part of the answer must be combined before the right one
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        let left_value = self.query_rec(query_range, node_segment.left_half(), left_child_index);
+        let right_value = self.query_rec(query_range, node_segment.right_half(), right_child_index);
+        M::combine(&left_value, &right_value)
+    }
+
+    /// Returns the largest `r` such that `pred` holds on the combination of `a[l..r]`.
+    ///
+    /// `pred` must hold on the identity and be monotone: once it fails on `a[l..r]` it fails
+    /// on every longer range. The boundary is found with a single descent in O(log n), e.g.
+    /// with a sum tree `max_right(l, |&s| s < k)` is the first `j >= l` with `sum(l..=j) >= k`
+    /// (or `len()` if there is none).
+    pub fn max_right<P: Fn(&M::Value) -> bool>(&self, l: usize, pred: P) -> usize {
+        assert!(
+            pred(&M::identity()),
+            "The predicate must hold on the identity"
+        );
+        if l >= self.num_leaf {
+            return self.num_leaf;
+        }
+        let mut acc = M::identity();
+        self.max_right_rec(l, &pred, Range::new(0, self.num_leaf - 1), 0, &mut acc)
+            .unwrap_or(self.num_leaf)
+    }
+
+    // Extends `acc` with the positions of the node that are >= l, from left to right, and
+    // returns the first position on which the predicate fails (if any).
+    fn max_right_rec<P: Fn(&M::Value) -> bool>(
+        &self,
+        l: usize,
+        pred: &P,
+        node_segment: Range,
+        index: usize,
+        acc: &mut M::Value,
+    ) -> Option<usize> {
+        if node_segment.end < l {
+            return None;
+        }
+        if node_segment.start >= l {
+            let extended = M::combine(acc, &self.tree[index]);
+            if pred(&extended) {
+                // the whole node can be taken: no need to visit its children
+                *acc = extended;
+                return None;
+            }
+            if node_segment.is_single_point() {
+                return Some(node_segment.start);
+            }
+        }
+        // the boundary is inside this node: look for it in the left child first
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.max_right_rec(l, pred, node_segment.left_half(), left_child_index, acc)
+            .or_else(|| {
+                self.max_right_rec(l, pred, node_segment.right_half(), right_child_index, acc)
+            })
+    }
+
+    /// Returns the smallest `l` such that `pred` holds on the combination of `a[l..r]`.
+    ///
+    /// It is the mirror of [`SegmentTree::max_right`]: `pred` must hold on the identity and
+    /// once it fails on `a[l..r]` it must fail on every range `a[l'..r]` with `l' < l`.
+    pub fn min_left<P: Fn(&M::Value) -> bool>(&self, r: usize, pred: P) -> usize {
+        assert!(
+            pred(&M::identity()),
+            "The predicate must hold on the identity"
+        );
+        assert!(r <= self.num_leaf, "Index {r} out of bounds");
+        if r == 0 {
+            return 0;
+        }
+        let mut acc = M::identity();
+        self.min_left_rec(r - 1, &pred, Range::new(0, self.num_leaf - 1), 0, &mut acc)
+            .map_or(0, |failing| failing + 1)
+    }
+
+    // Extends `acc` with the positions of the node that are <= last, from right to left, and
+    // returns the first position on which the predicate fails (if any).
+    fn min_left_rec<P: Fn(&M::Value) -> bool>(
+        &self,
+        last: usize,
+        pred: &P,
+        node_segment: Range,
+        index: usize,
+        acc: &mut M::Value,
+    ) -> Option<usize> {
+        if node_segment.start > last {
+            return None;
+        }
+        if node_segment.end <= last {
+            let extended = M::combine(&self.tree[index], acc);
+            if pred(&extended) {
+                *acc = extended;
+                return None;
+            }
+            if node_segment.is_single_point() {
+                return Some(node_segment.start);
+            }
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.min_left_rec(
+            last,
+            pred,
+            node_segment.right_half(),
+            right_child_index,
+            acc,
+        )
+        .or_else(|| self.min_left_rec(last, pred, node_segment.left_half(), left_child_index, acc))
+    }
+}
+
+impl<M: Monoid> SegmentTree<M>
+where
+    M::Value: Encode,
+{
+    /// Writes the tree in the little-endian binary format shared by the trees of this
+    /// crate: a versioned header followed by the value of each node in implicit order,
+    /// written by its `Encode` impl.
+    ///
+    /// The monoid is not saved, so the tree must be loaded with the same `M`.
+    /// Every number is written separately, so `writer` should be buffered.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, TreeKind::Generic, self.num_leaf)?;
+        for value in &self.tree {
+            value.encode(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tree written by `save`.
+    pub fn load<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let num_leaf = read_header(reader, TreeKind::Generic, size_of::<M::Value>())?;
+        // the tree grows as the nodes are read, so a corrupt number of leaves fails on the
+        // missing bytes instead of allocating
+        let mut tree = Vec::new();
+        for _ in 0..Self::num_nodes(num_leaf) {
+            tree.push(M::Value::decode(reader)?);
+        }
+        Ok(Self { tree, num_leaf })
+    }
+}
+
+impl<M: Monoid> SegmentTree<M>
+where
+    M::Value: Debug,
+{
+    /// Describes the tree in a human-readable form, one node per line in implicit order.
+    pub fn dump_text(&self) -> String {
+        let mut text = format!(
+            "SegmentTree (format version {FORMAT_VERSION})\nleaves: {}\n",
+            self.num_leaf
+        );
+        if self.num_leaf > 0 {
+            self.dump_text_rec(Range::new(0, self.num_leaf - 1), 0, &mut text);
+        }
+        text
+    }
+
+    // the implicit layout is a preorder visit, so the nodes are dumped by increasing index
+    fn dump_text_rec(&self, node_segment: Range, index: usize, text: &mut String) {
+        // writing to a String never fails
+        writeln!(text, "node {index} {node_segment}: {:?}", self.tree[index]).unwrap();
+        if node_segment.is_single_point() {
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.dump_text_rec(node_segment.left_half(), left_child_index, text);
+        self.dump_text_rec(node_segment.right_half(), right_child_index, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::SegmentTreeError;
+    use crate::generic_segment_tree::{Monoid, SegmentTree};
+    use std::cmp::Ordering;
+
+    struct Sum;
+
+    impl Monoid for Sum {
+        type Value = u64;
+
+        fn identity() -> u64 {
+            0
+        }
+
+        fn combine(a: &u64, b: &u64) -> u64 {
+            a + b
+        }
+    }
+
+    // minimum together with the number of its occurrences
+    struct MinCount;
+
+    impl Monoid for MinCount {
+        type Value = (i32, usize);
+
+        fn identity() -> (i32, usize) {
+            (i32::MAX, 0)
+        }
+
+        fn combine(a: &(i32, usize), b: &(i32, usize)) -> (i32, usize) {
+            match a.0.cmp(&b.0) {
+                Ordering::Less => *a,
+                Ordering::Greater => *b,
+                Ordering::Equal => (a.0, a.1 + b.1),
+            }
+        }
+    }
+
+    // 2x2 matrix product: associative but not commutative
+    struct MatrixProduct;
+
+    impl Monoid for MatrixProduct {
+        type Value = [[i64; 2]; 2];
+
+        fn identity() -> [[i64; 2]; 2] {
+            [[1, 0], [0, 1]]
+        }
+
+        fn combine(a: &[[i64; 2]; 2], b: &[[i64; 2]; 2]) -> [[i64; 2]; 2] {
+            let mut res = [[0; 2]; 2];
+            for (r, row) in res.iter_mut().enumerate() {
+                for (c, cell) in row.iter_mut().enumerate() {
+                    *cell = a[r][0] * b[0][c] + a[r][1] * b[1][c];
+                }
+            }
+            res
+        }
+    }
+
+    #[test]
+    fn test_sum_query() {
+        let tree = SegmentTree::<Sum>::build(&[3, 4, 5, 3, 8, 12, 4]);
+        assert_eq!(39, tree.query(0, 6));
+        assert_eq!(9, tree.query(1, 2));
+        assert_eq!(24, tree.query(4, 6));
+        assert_eq!(5, tree.query(2, 2));
+        assert_eq!(0, tree.query(3, 2));
+    }
+
+    #[test]
+    fn test_update_and_get() {
+        let mut tree = SegmentTree::<Sum>::build(&[3, 4, 5, 3, 8, 12, 4]);
+        tree.update(4, 1);
+        assert_eq!(1, tree.get(4));
+        assert_eq!(32, tree.query(0, 6));
+        assert_eq!(17, tree.query(4, 6));
+        assert_eq!(15, tree.query(0, 3));
+    }
+
+    #[test]
+    fn test_build_empty() {
+        let mut tree = SegmentTree::<MinCount>::build_empty(5);
+        assert_eq!((i32::MAX, 0), tree.query(0, 4));
+        tree.update(2, (7, 1));
+        tree.update(4, (7, 1));
+        assert_eq!((7, 2), tree.query(0, 4));
+        assert_eq!((7, 1), tree.query(0, 3));
+    }
+
+    #[test]
+    fn test_min_count() {
+        let tree = SegmentTree::<MinCount>::build(&[(2, 1), (5, 1), (2, 1), (1, 1), (2, 1)]);
+        assert_eq!((1, 1), tree.query(0, 4));
+        assert_eq!((2, 2), tree.query(0, 2));
+        assert_eq!((2, 1), tree.query(1, 2));
+    }
+
+    #[test]
+    fn test_non_commutative_combine() {
+        let shift = [[1, 1], [0, 1]];
+        let swap = [[0, 1], [1, 0]];
+        let tree = SegmentTree::<MatrixProduct>::build(&[shift, swap, shift, shift]);
+        // shift * swap = [[1, 1], [1, 0]] while swap * shift = [[0, 1], [1, 1]]
+        assert_eq!([[1, 1], [1, 0]], tree.query(0, 1));
+        assert_eq!([[0, 1], [1, 1]], tree.query(1, 2));
+        assert_eq!([[1, 3], [1, 2]], tree.query(0, 3));
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let tree = SegmentTree::<Sum>::build(&[]);
+        assert!(tree.is_empty());
+        assert_eq!(0, tree.query(1, 0));
+        assert!(tree.try_query(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut tree = SegmentTree::<Sum>::build(&[3, 4, 5]);
+        assert_eq!(
+            Err(SegmentTreeError::RangeOutOfBounds {
+                start: 1,
+                end: 3,
+                len: 3
+            }),
+            tree.try_query(1, 3)
+        );
+        assert_eq!(Ok(9), tree.try_query(1, 2));
+        assert_eq!(
+            Err(SegmentTreeError::IndexOutOfBounds { index: 3, len: 3 }),
+            tree.try_update(3, 1)
+        );
+        assert_eq!(12, tree.query(0, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Range [0, 5] out of bounds for 3 elements")]
+    fn test_query_past_the_end_panics() {
+        SegmentTree::<Sum>::build(&[3, 4, 5]).query(0, 5);
+    }
+
+    #[test]
+    fn test_max_right_first_prefix_reaching_k() {
+        let tree = SegmentTree::<Sum>::build(&[3, 4, 5, 3, 8, 12, 4]);
+        // first j >= 1 with sum(1..=j) >= 12: 4 + 5 + 3
+        assert_eq!(3, tree.max_right(1, |&s| s < 12));
+        assert_eq!(1, tree.max_right(1, |&s| s < 1));
+        // the whole suffix sums to less than 100
+        assert_eq!(7, tree.max_right(2, |&s| s < 100));
+        assert_eq!(7, tree.max_right(7, |&s| s < 1));
+    }
+
+    #[test]
+    fn test_min_left() {
+        let tree = SegmentTree::<Sum>::build(&[3, 4, 5, 3, 8, 12, 4]);
+        // smallest l with sum(l..5) <= 16: 5 + 3 + 8
+        assert_eq!(2, tree.min_left(5, |&s| s <= 16));
+        assert_eq!(0, tree.min_left(5, |&s| s <= 100));
+        assert_eq!(5, tree.min_left(5, |&s| s <= 7));
+        assert_eq!(0, tree.min_left(0, |&s| s <= 3));
+    }
+
+    #[test]
+    fn test_leftmost_position_exceeding_threshold() {
+        let tree = SegmentTree::<MinCount>::build(&[(8, 1), (2, 1), (6, 1), (1, 1), (9, 1)]);
+        // leftmost position from 0 whose value is below 3, using the min monoid
+        assert_eq!(1, tree.max_right(0, |&(m, _)| m >= 3));
+        assert_eq!(3, tree.max_right(2, |&(m, _)| m >= 3));
+        assert_eq!(5, tree.max_right(4, |&(m, _)| m >= 3));
+    }
+
+    #[test]
+    fn test_descent_matches_linear_scan() {
+        let a: Vec<u64> = vec![5, 0, 2, 7, 1, 1, 9, 3, 0, 4, 6];
+        let tree = SegmentTree::<Sum>::build(&a);
+        for k in 1..40 {
+            for l in 0..=a.len() {
+                let mut expected = l;
+                while expected < a.len() && a[l..=expected].iter().sum::<u64>() < k {
+                    expected += 1;
+                }
+                assert_eq!(expected, tree.max_right(l, |&s| s < k), "l={l} k={k}");
+            }
+            for r in 0..=a.len() {
+                let mut expected = r;
+                while expected > 0 && a[expected - 1..r].iter().sum::<u64>() <= k {
+                    expected -= 1;
+                }
+                assert_eq!(expected, tree.min_left(r, |&s| s <= k), "r={r} k={k}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut original = SegmentTree::<Sum>::build(&[5, 0, 2, 7, 1]);
+        original.update(3, 4);
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+
+        let loaded = SegmentTree::<Sum>::load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        assert_eq!(12, loaded.query(0, 4));
+        assert_eq!(6, loaded.query(1, 3));
+        assert_eq!(4, loaded.get(3));
+
+        bytes.pop();
+        assert!(SegmentTree::<Sum>::load(&mut bytes.as_slice()).is_err());
+        let mut empty = Vec::new();
+        SegmentTree::<Sum>::build(&[]).save(&mut empty).unwrap();
+        assert!(
+            SegmentTree::<Sum>::load(&mut empty.as_slice())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_dump_text() {
+        let tree = SegmentTree::<Sum>::build(&[4, 2, 7]);
+        assert_eq!(
+            "SegmentTree (format version 1)\n\
+            leaves: 3\n\
+            node 0 [0..2]: 13\n\
+            node 1 [0..1]: 6\n\
+            node 2 [0..0]: 4\n\
+            node 3 [1..1]: 2\n\
+            node 4 [2..2]: 7\n",
+            tree.dump_text()
+        );
+    }
+}
@@ -1,44 +1,11 @@
-use std::cmp::{max, min};
-use std::fmt;
-use std::fmt::{Display, Formatter};
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct Range {
-    start: usize,
-    end: usize,
-}
-
-impl Display for Range {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "[{}..{}]", self.start, self.end)
-    }
-}
-
-impl Range {
-    pub fn new(start: usize, end: usize) -> Self {
-        Range { start, end }
-    }
-
-    pub fn contains(&self, other: Range) -> bool {
-        self.start <= other.start && self.end >= other.end
-    }
-
-    pub fn size(&self) -> usize {
-        self.end - self.start + 1
-    }
-
-    pub fn intersect(&self, other: Range) -> Self {
-        Range::new(max(self.start, other.start), min(self.end, other.end))
-    }
-
-    pub fn no_overlap(&self, other: Range) -> bool {
-        self.end < other.start || other.end < self.start
-    }
-
-    pub fn middle(&self) -> usize {
-        self.start + ((self.end - self.start) / 2)
-    }
-}
+use crate::error::{SegmentTreeError, check_index, check_num_nodes, check_range};
+use crate::range::{Leaves, Range, left_right_child_index};
+use crate::serialization::{
+    FORMAT_VERSION, TreeKind, read_header, read_i32, write_header, write_i32,
+};
+use crate::trace::{TraceEvent, TreeObserver};
+use std::fmt::Write;
+use std::io;
 
 pub struct IntegerSegmentTree {
     tree: Vec<i32>,
@@ -46,6 +13,7 @@ pub struct IntegerSegmentTree {
     num_leaf: usize,
     neutral_value: i32,
     merge_values: fn(i32, i32) -> i32,
+    observer: Option<Box<dyn TreeObserver>>,
 }
 
 impl IntegerSegmentTree {
@@ -58,28 +26,49 @@ impl IntegerSegmentTree {
         neutral_value: i32,
         merge_values: fn(i32, i32) -> i32,
     ) -> Self {
-        let implicit_tree: Vec<i32> = vec![0; 2 * num_elems - 1];
-        let segment_tree = IntegerSegmentTree {
+        Self::try_build_empty(num_elems, neutral_value, merge_values)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `build_empty`, but fails instead of aborting when the tree does not fit in memory.
+    pub fn try_build_empty(
+        num_elems: usize,
+        neutral_value: i32,
+        merge_values: fn(i32, i32) -> i32,
+    ) -> Result<Self, SegmentTreeError> {
+        // each node holds a value and a pending update
+        check_num_nodes(num_elems, 2 * size_of::<i32>())?;
+        let implicit_tree: Vec<i32> = vec![0; Self::num_nodes(num_elems)];
+        Ok(IntegerSegmentTree {
             tree: implicit_tree,
-            pending_updates: vec![0; 2 * num_elems - 1],
+            pending_updates: vec![0; Self::num_nodes(num_elems)],
             num_leaf: num_elems,
             neutral_value,
             merge_values,
-        };
-        segment_tree
+            observer: None,
+        })
     }
 
     pub fn build(a: &[i32], neutral_value: i32, merge_values: fn(i32, i32) -> i32) -> Self {
-        let mut implicit_tree: Vec<i32> = vec![0; 2 * a.len() - 1];
-        Self::build_recursively(&a, &mut implicit_tree, 0, a.len() - 1, 0, merge_values);
-        let segment_tree = IntegerSegmentTree {
-            tree: implicit_tree,
-            pending_updates: vec![0; 2 * a.len() - 1],
-            num_leaf: a.len(),
-            neutral_value,
-            merge_values,
-        };
-        segment_tree
+        Self::try_build(a, neutral_value, merge_values).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `build`, but fails instead of aborting when the tree does not fit in memory.
+    pub fn try_build(
+        a: &[i32],
+        neutral_value: i32,
+        merge_values: fn(i32, i32) -> i32,
+    ) -> Result<Self, SegmentTreeError> {
+        let mut inst = Self::try_build_empty(a.len(), neutral_value, merge_values)?;
+        if !a.is_empty() {
+            Self::build_recursively(a, &mut inst.tree, 0, a.len() - 1, 0, merge_values);
+        }
+        Ok(inst)
+    }
+
+    // an empty tree has no nodes at all
+    fn num_nodes(num_leaf: usize) -> usize {
+        (2 * num_leaf).saturating_sub(1)
     }
 
     fn build_recursively(
@@ -103,49 +92,60 @@ impl IntegerSegmentTree {
         tree[index] = value;
     }
 
+    /// Reports every step of the following queries and range updates to `observer`.
+    pub fn set_observer(&mut self, observer: Box<dyn TreeObserver>) {
+        self.observer = Some(observer);
+    }
+
+    /// Removes the observer, if any, and gives it back.
+    pub fn take_observer(&mut self) -> Option<Box<dyn TreeObserver>> {
+        self.observer.take()
+    }
+
+    fn emit(&mut self, event: TraceEvent) {
+        if let Some(observer) = self.observer.as_mut() {
+            observer.on_event(&event);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_leaf
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_leaf == 0
+    }
+
     pub fn query(&mut self, i: usize, j: usize) -> i32 {
+        self.try_query(i, j).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Returns the merge of the elements in `[i, j]`, the neutral value if the range is
+    /// empty (`i > j`) or an error if it ends past the last element.
+    pub fn try_query(&mut self, i: usize, j: usize) -> Result<i32, SegmentTreeError> {
+        check_range(i, j, self.num_leaf)?;
         if i > j {
-            return self.neutral_value;
+            return Ok(self.neutral_value);
         }
-        self.rec_query(Range::new(i, j), Range::new(0, self.num_leaf - 1), 0)
+        Ok(self.rec_query(Range::new(i, j), Range::new(0, self.num_leaf - 1), 0))
     }
 
     fn rec_query(&mut self, query_range: Range, node_segment: Range, index: usize) -> i32 {
+        let segment = node_segment;
+        self.emit(TraceEvent::Visit { index, segment });
         if node_segment.no_overlap(query_range) {
+            self.emit(TraceEvent::NoOverlap { index, segment });
             return self.neutral_value;
         }
-        let merge = self.merge_values;
-        let tree = &mut self.tree;
-        let pending = &mut self.pending_updates;
-        if pending[index] > 0 {
-            let pending_update = pending[index];
-            let repeated_val = (1..node_segment.size()).fold(pending_update, |acc, _| {
-                merge(acc, pending_update)
-            });
-            tree[index] = merge(tree[index], repeated_val);
-            //propagate updates to his son
-            let middle = node_segment.middle();
-            let num_leaf_left_subtree = middle - node_segment.start + 1;
-            let right_child_index = index + 2 * num_leaf_left_subtree;
-            if node_segment.size() > 1 {
-                println!(
-                    "\t propagate update to left {} and right {} segment",
-                    Range::new(node_segment.start, middle),
-                    Range::new(middle + 1, node_segment.end)
-                );
-                pending[index + 1] =
-                    merge(pending[index + 1], pending_update);
-                pending[right_child_index] =
-                    merge(pending[right_child_index], pending_update);
-            }
-            pending[index] = 0;
-        }
+        self.push_pending_update(node_segment, index);
 
         if query_range.contains(node_segment) {
-            return tree[index];
+            self.emit(TraceEvent::TotalOverlap { index, segment });
+            return self.tree[index];
         }
 
         // partial overlap
+        self.emit(TraceEvent::PartialOverlap { index, segment });
         let middle = node_segment.middle();
         let left_sum = self.rec_query(
             query_range,
@@ -160,11 +160,99 @@ impl IntegerSegmentTree {
             Range::new(middle + 1, node_segment.end),
             index + 2 * num_leaf_left_subtree,
         );
-        merge(left_sum, right_sum)
+        (self.merge_values)(left_sum, right_sum)
+    }
+
+    // applies the update pending on the node, if any, and hands it to its children
+    fn push_pending_update(&mut self, node_segment: Range, index: usize) {
+        if self.pending_updates[index] > 0 && node_segment.size() > 1 {
+            let update = self.pending_updates[index];
+            let segment = node_segment;
+            self.emit(TraceEvent::PushDown {
+                index,
+                segment,
+                update,
+            });
+        }
+        let merge = self.merge_values;
+        let tree = &mut self.tree;
+        let pending = &mut self.pending_updates;
+        if pending[index] > 0 {
+            let pending_update = pending[index];
+            let repeated_val = Self::repeat_merge(merge, pending_update, node_segment.size());
+            tree[index] = merge(tree[index], repeated_val);
+            //propagate updates to his son
+            let middle = node_segment.middle();
+            let num_leaf_left_subtree = middle - node_segment.start + 1;
+            let right_child_index = index + 2 * num_leaf_left_subtree;
+            if node_segment.size() > 1 {
+                pending[index + 1] = merge(pending[index + 1], pending_update);
+                pending[right_child_index] = merge(pending[right_child_index], pending_update);
+            }
+            pending[index] = 0;
+        }
+    }
+
+    // Merges `times >= 1` copies of `value` by repeated doubling, in O(log times) merges
+    // instead of one per copy: this keeps a full push down in O(n), as the logarithms of the
+    // segment sizes add up to O(n) over the whole tree.
+    fn repeat_merge(merge: fn(i32, i32) -> i32, value: i32, times: usize) -> i32 {
+        let mut result = value;
+        let mut power = value;
+        let mut remaining = times - 1;
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = merge(result, power);
+            }
+            remaining >>= 1;
+            // not doubled past what is needed, so that a sum cannot overflow needlessly
+            if remaining > 0 {
+                power = merge(power, power);
+            }
+        }
+        result
+    }
+
+    // pushes every pending update down to the leaves, visiting each node once
+    fn push_all_pending_updates(&mut self, node_segment: Range, index: usize) {
+        self.push_pending_update(node_segment, index);
+        if node_segment.is_single_point() {
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.push_all_pending_updates(node_segment.left_half(), left_child_index);
+        self.push_all_pending_updates(node_segment.right_half(), right_child_index);
+    }
+
+    /// Returns the current value of the element at position `i`.
+    pub fn leaf(&mut self, i: usize) -> i32 {
+        check_index(i, self.num_leaf).unwrap_or_else(|e| panic!("{e}"));
+        self.query(i, i)
+    }
+
+    /// Iterates the current values of the elements from left to right, after pushing every
+    /// pending update down to the leaves in O(n).
+    pub fn leaves(&mut self) -> impl Iterator<Item = i32> + '_ {
+        if !self.is_empty() {
+            self.push_all_pending_updates(Range::new(0, self.num_leaf - 1), 0);
+        }
+        Leaves::new(&self.tree, self.num_leaf).copied()
+    }
+
+    /// Returns the current values of all the elements in O(n).
+    pub fn to_vec(&mut self) -> Vec<i32> {
+        self.leaves().collect()
     }
 
     pub fn add(&mut self, i: usize, val: i32) {
+        self.try_add(i, val).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Merges `val` into the element at position `i`, or fails if there is no such element.
+    pub fn try_add(&mut self, i: usize, val: i32) -> Result<(), SegmentTreeError> {
+        check_index(i, self.num_leaf)?;
         self.add_rec(i, val, 0, self.num_leaf - 1, 0);
+        Ok(())
     }
 
     fn add_rec(&mut self, i: usize, val: i32, l_s: usize, r_s: usize, nav_index: usize) {
@@ -185,6 +273,10 @@ impl IntegerSegmentTree {
     }
 
     fn range_update(&mut self, i: usize, j: usize, val: i32) {
+        check_range(i, j, self.num_leaf).unwrap_or_else(|e| panic!("{e}"));
+        if i > j {
+            return;
+        }
         self.range_update_rec(Range::new(i, j), Range::new(0, self.num_leaf - 1), val, 0);
     }
 
@@ -195,9 +287,17 @@ impl IntegerSegmentTree {
         val: i32,
         nav_index: usize,
     ) {
+        let (index, segment) = (nav_index, node_segment);
+        self.emit(TraceEvent::Visit { index, segment });
         if node_segment.no_overlap(query_range) {
+            self.emit(TraceEvent::NoOverlap { index, segment });
             return;
         }
+        if query_range.contains(node_segment) {
+            self.emit(TraceEvent::TotalOverlap { index, segment });
+        } else {
+            self.emit(TraceEvent::PartialOverlap { index, segment });
+        }
         let merge = self.merge_values;
         let tree = &mut self.tree;
         let pending = &mut self.pending_updates;
@@ -208,15 +308,13 @@ impl IntegerSegmentTree {
                 tree[nav_index] = merge(tree[nav_index], val);
             } else {
                 // save update as pending in order to be applied in successive query
-                pending[nav_index] =
-                    merge(val, pending[nav_index]);
+                pending[nav_index] = merge(val, pending[nav_index]);
             }
             return;
         }
         //partial overlap: query partially contained in node_segment
         let query_segment = query_range.intersect(node_segment);
-        let repeated_val =
-            (1..query_segment.size()).fold(val, |acc, _| merge(acc, val));
+        let repeated_val = Self::repeat_merge(merge, val, query_segment.size());
 
         tree[nav_index] = merge(repeated_val, tree[nav_index]);
 
@@ -238,12 +336,122 @@ impl IntegerSegmentTree {
             nav_index + 2 * num_leaf_left_subtree,
         );
     }
+
+    /// Writes the tree, pending updates included, in the little-endian binary format shared
+    /// by the trees of this crate: a versioned header followed by the neutral value and, for
+    /// each node in implicit order, its value and its pending update. The merge function
+    /// and the observer are not saved.
+    ///
+    /// Every number is written separately, so `writer` should be buffered.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, TreeKind::Integer, self.num_leaf)?;
+        write_i32(writer, self.neutral_value)?;
+        for (&value, &pending) in self.tree.iter().zip(&self.pending_updates) {
+            write_i32(writer, value)?;
+            write_i32(writer, pending)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tree written by `save`, that will merge values with `merge_values`: it must
+    /// be the same merge function the tree has been saved with.
+    pub fn load<R: io::Read>(
+        reader: &mut R,
+        merge_values: fn(i32, i32) -> i32,
+    ) -> io::Result<Self> {
+        let num_leaf = read_header(reader, TreeKind::Integer, 2 * size_of::<i32>())?;
+        let neutral_value = read_i32(reader)?;
+        // the vectors grow as the nodes are read, never trusting the header for the
+        // allocation: a corrupt number of leaves fails on the missing bytes instead
+        let mut tree = Vec::new();
+        let mut pending_updates = Vec::new();
+        for _ in 0..Self::num_nodes(num_leaf) {
+            tree.push(read_i32(reader)?);
+            pending_updates.push(read_i32(reader)?);
+        }
+        Ok(IntegerSegmentTree {
+            tree,
+            pending_updates,
+            num_leaf,
+            neutral_value,
+            merge_values,
+            observer: None,
+        })
+    }
+
+    /// Describes the tree in a human-readable form, one node per line in implicit order.
+    pub fn dump_text(&self) -> String {
+        let mut text = format!(
+            "IntegerSegmentTree (format version {FORMAT_VERSION})\nleaves: {}\nneutral value: {}\n",
+            self.num_leaf, self.neutral_value
+        );
+        if self.num_leaf > 0 {
+            self.dump_text_rec(Range::new(0, self.num_leaf - 1), 0, &mut text);
+        }
+        text
+    }
+
+    // the implicit layout is a preorder visit, so the nodes are dumped by increasing index
+    fn dump_text_rec(&self, node_segment: Range, index: usize, text: &mut String) {
+        // writing to a String never fails
+        writeln!(
+            text,
+            "node {index} {node_segment}: value {}, pending {}",
+            self.tree[index], self.pending_updates[index]
+        )
+        .unwrap();
+        if node_segment.is_single_point() {
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.dump_text_rec(node_segment.left_half(), left_child_index, text);
+        self.dump_text_rec(node_segment.right_half(), right_child_index, text);
+    }
+
+    /// Renders the implicit tree in the Graphviz DOT language: each node shows its index,
+    /// its segment, its value and, if any, the pending update not yet pushed to its children.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph IntegerSegmentTree {\n    node [shape=box];\n");
+        if self.num_leaf > 0 {
+            self.to_dot_rec(Range::new(0, self.num_leaf - 1), 0, &mut dot);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn to_dot_rec(&self, node_segment: Range, index: usize, dot: &mut String) {
+        // writing to a String never fails
+        write!(
+            dot,
+            "    n{index} [label=\"#{index} {node_segment}\\nvalue {}",
+            self.tree[index]
+        )
+        .unwrap();
+        if self.pending_updates[index] != 0 {
+            write!(dot, "\\npending {}", self.pending_updates[index]).unwrap();
+        }
+        dot.push_str("\"];\n");
+        if node_segment.is_single_point() {
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        writeln!(dot, "    n{index} -> n{left_child_index};").unwrap();
+        writeln!(dot, "    n{index} -> n{right_child_index};").unwrap();
+        self.to_dot_rec(node_segment.left_half(), left_child_index, dot);
+        self.to_dot_rec(node_segment.right_half(), right_child_index, dot);
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::error::SegmentTreeError;
     use crate::integer_segment_tree::IntegerSegmentTree;
+    use crate::range::Range;
+    use crate::trace::TraceEvent;
+    use std::cell::RefCell;
     use std::cmp::{max, min};
+    use std::io;
+    use std::rc::Rc;
 
     #[test]
     fn test_creation() {
@@ -289,8 +497,7 @@ mod tests {
 
     #[test]
     fn test_min_query() {
-        let mut result =
-            IntegerSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4, 5], i32::MAX, |x, y| min(x, y));
+        let mut result = IntegerSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4, 5], i32::MAX, min);
         assert_eq!(3, result.query(1, 4));
     }
 
@@ -362,7 +569,7 @@ mod tests {
 
     #[test]
     fn test_range_update_max() {
-        let mut result = IntegerSegmentTree::build_empty(10, 0, |x, y| max(x, y));
+        let mut result = IntegerSegmentTree::build_empty(10, 0, max);
         result.range_update(0, 5, 10);
 
         //[10, 10, 10, 10, 10, 10, 0, 0, 0, 0]
@@ -371,7 +578,7 @@ mod tests {
 
     #[test]
     fn test_range_update_max_directly_child_of_lazy_node() {
-        let mut result = IntegerSegmentTree::build_empty(10, 0, |x, y| max(x, y));
+        let mut result = IntegerSegmentTree::build_empty(10, 0, max);
         result.range_update(0, 5, 10);
 
         //[10, 10, 10, 10, 10, 10, 0, 0, 0, 0]
@@ -380,7 +587,7 @@ mod tests {
 
     #[test]
     fn test_range_update_max_directly_leaf_of_to() {
-        let mut result = IntegerSegmentTree::build_empty(10, 0, |x, y| max(x, y));
+        let mut result = IntegerSegmentTree::build_empty(10, 0, max);
         result.range_update(0, 5, 10);
 
         //[10, 10, 10, 10, 10, 10, 0, 0, 0, 0]
@@ -390,7 +597,7 @@ mod tests {
 
     #[test]
     fn test_range_update_existing_elem() {
-        let mut result = IntegerSegmentTree::build(&[10, 11, 34, 2, 23], 0, |x, y| max(x, y));
+        let mut result = IntegerSegmentTree::build(&[10, 11, 34, 2, 23], 0, max);
         assert_eq!(34, result.query(0, 4));
 
         result.range_update(3, 4, 45);
@@ -401,7 +608,7 @@ mod tests {
 
     #[test]
     fn test_multiple_range_update() {
-        let mut result = IntegerSegmentTree::build_empty(10, 0, |x, y| max(x, y));
+        let mut result = IntegerSegmentTree::build_empty(10, 0, max);
         result.range_update(0, 5, 10);
         result.range_update(2, 5, 20);
 
@@ -412,7 +619,7 @@ mod tests {
 
     #[test]
     fn test_multiple_range_update_first_greater() {
-        let mut result = IntegerSegmentTree::build_empty(10, 0, |x, y| max(x, y));
+        let mut result = IntegerSegmentTree::build_empty(10, 0, max);
         result.range_update(0, 5, 20);
         result.range_update(2, 5, 10);
 
@@ -420,4 +627,264 @@ mod tests {
         assert_eq!(20, result.query(4, 4));
         assert_eq!(20, result.query(1, 1));
     }
+
+    fn record_events(tree: &mut IntegerSegmentTree) -> Rc<RefCell<Vec<TraceEvent>>> {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&events);
+        tree.set_observer(Box::new(move |event: &TraceEvent| {
+            recorded.borrow_mut().push(*event)
+        }));
+        events
+    }
+
+    #[test]
+    fn test_query_events() {
+        let mut result = IntegerSegmentTree::build(&[3, 4, 5, 3], 0, |x, y| x + y);
+        let events = record_events(&mut result);
+        assert_eq!(12, result.query(1, 3));
+        let segment = |start, end| Range::new(start, end);
+        assert_eq!(
+            vec![
+                TraceEvent::Visit {
+                    index: 0,
+                    segment: segment(0, 3)
+                },
+                TraceEvent::PartialOverlap {
+                    index: 0,
+                    segment: segment(0, 3)
+                },
+                TraceEvent::Visit {
+                    index: 1,
+                    segment: segment(0, 1)
+                },
+                TraceEvent::PartialOverlap {
+                    index: 1,
+                    segment: segment(0, 1)
+                },
+                TraceEvent::Visit {
+                    index: 2,
+                    segment: segment(0, 0)
+                },
+                TraceEvent::NoOverlap {
+                    index: 2,
+                    segment: segment(0, 0)
+                },
+                TraceEvent::Visit {
+                    index: 3,
+                    segment: segment(1, 1)
+                },
+                TraceEvent::TotalOverlap {
+                    index: 3,
+                    segment: segment(1, 1)
+                },
+                TraceEvent::Visit {
+                    index: 4,
+                    segment: segment(2, 3)
+                },
+                TraceEvent::TotalOverlap {
+                    index: 4,
+                    segment: segment(2, 3)
+                },
+            ],
+            *events.borrow()
+        );
+    }
+
+    #[test]
+    fn test_push_down_event() {
+        let mut result = IntegerSegmentTree::build_empty(4, 0, |x, y| x + y);
+        result.range_update(0, 3, 2);
+        let events = record_events(&mut result);
+        assert_eq!(4, result.query(0, 1));
+        assert!(events.borrow().contains(&TraceEvent::PushDown {
+            index: 0,
+            segment: Range::new(0, 3),
+            update: 2
+        }));
+        assert_eq!(
+            "push down update 2 from node 0 [0..3] to [0..1] and [2..3]",
+            events.borrow()[1].to_string()
+        );
+        // without an observer nothing is recorded anymore
+        let num_events = events.borrow().len();
+        assert!(result.take_observer().is_some());
+        result.query(0, 3);
+        assert_eq!(num_events, events.borrow().len());
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let mut result = IntegerSegmentTree::build_empty(3, 0, |x, y| x + y);
+        result.range_update(0, 1, 5);
+        assert_eq!(
+            "digraph IntegerSegmentTree {\n    node [shape=box];\n\
+            \x20   n0 [label=\"#0 [0..2]\\nvalue 10\"];\n\
+            \x20   n0 -> n1;\n\
+            \x20   n0 -> n4;\n\
+            \x20   n1 [label=\"#1 [0..1]\\nvalue 0\\npending 5\"];\n\
+            \x20   n1 -> n2;\n\
+            \x20   n1 -> n3;\n\
+            \x20   n2 [label=\"#2 [0..0]\\nvalue 0\"];\n\
+            \x20   n3 [label=\"#3 [1..1]\\nvalue 0\"];\n\
+            \x20   n4 [label=\"#4 [2..2]\\nvalue 0\"];\n\
+            }\n",
+            result.to_dot()
+        );
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let mut result = IntegerSegmentTree::build(&[], i32::MAX, min);
+        assert!(result.is_empty());
+        assert_eq!(i32::MAX, result.query(1, 0));
+        assert_eq!(Ok(i32::MAX), result.try_query(1, 0));
+        let mut result = IntegerSegmentTree::build_empty(0, 0, |x, y| x + y);
+        assert_eq!(0, result.query(3, 2));
+        assert_eq!(
+            Err(SegmentTreeError::RangeOutOfBounds {
+                start: 0,
+                end: 0,
+                len: 0
+            }),
+            result.try_query(0, 0)
+        );
+        assert_eq!(
+            Err(SegmentTreeError::IndexOutOfBounds { index: 0, len: 0 }),
+            result.try_add(0, 1)
+        );
+    }
+
+    #[test]
+    fn test_try_query_and_add() {
+        let mut result = IntegerSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4], 0, |x, y| x + y);
+        assert_eq!(Ok(16), result.try_query(4, 6));
+        assert_eq!(
+            Err(SegmentTreeError::RangeOutOfBounds {
+                start: 4,
+                end: 7,
+                len: 7
+            }),
+            result.try_query(4, 7)
+        );
+        assert_eq!(
+            Err(SegmentTreeError::IndexOutOfBounds { index: 7, len: 7 }),
+            result.try_add(7, 1)
+        );
+        // a rejected add leaves the tree untouched
+        assert_eq!(31, result.query(0, 6));
+        assert_eq!(Ok(()), result.try_add(6, 4));
+        assert_eq!(35, result.query(0, 6));
+    }
+
+    #[test]
+    fn test_try_build_too_many_elements() {
+        let result = IntegerSegmentTree::try_build_empty(usize::MAX / 4, 0, |x, y| x + y);
+        assert_eq!(
+            Some(SegmentTreeError::TooManyElements {
+                len: usize::MAX / 4
+            }),
+            result.err()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 10 out of bounds for 10 elements")]
+    fn test_add_out_of_bounds_panics() {
+        let mut result = IntegerSegmentTree::build_empty(10, 0, |x, y| x + y);
+        result.add(10, 1);
+    }
+
+    #[test]
+    fn test_to_vec_after_range_updates() {
+        let mut result = IntegerSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4], 0, |x, y| x + y);
+        result.range_update(0, 4, 2);
+        result.range_update(3, 6, 1);
+        assert_eq!(vec![5, 6, 7, 6, 11, 13, -3], result.to_vec());
+        // no update is pending anymore, so the values are all in the leaves
+        assert_eq!(6, result.get_index(7));
+        assert_eq!(-3, result.leaf(6));
+        assert_eq!(45, result.query(0, 6));
+    }
+
+    #[test]
+    fn test_range_update_sum_over_odd_sizes() {
+        // 13 and the sizes of its halves are not powers of two
+        let mut result = IntegerSegmentTree::build_empty(13, 0, |x, y| x + y);
+        result.range_update(0, 12, 3);
+        assert_eq!(39, result.query(0, 12));
+        assert_eq!(21, result.query(2, 8));
+        result.range_update(1, 11, 2);
+        assert_eq!(61, result.query(0, 12));
+        assert_eq!(vec![3, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 3], result.to_vec());
+    }
+
+    #[test]
+    fn test_leaves() {
+        let mut result = IntegerSegmentTree::build_empty(6, 0, max);
+        result.range_update(1, 4, 7);
+        result.add(5, 9);
+        assert_eq!(4, result.leaves().filter(|&v| v == 7).count());
+        assert_eq!(vec![0, 7, 7, 7, 7, 9], result.leaves().collect::<Vec<_>>());
+        assert_eq!(0, IntegerSegmentTree::build(&[], 0, max).leaves().count());
+    }
+
+    #[test]
+    fn test_save_and_load_with_pending_updates() {
+        let sum = |x, y| x + y;
+        let mut original = IntegerSegmentTree::build_empty(10, 0, sum);
+        original.range_update(0, 5, 5);
+        original.add(7, 3);
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+        // header, neutral value and two i32 for each of the 19 nodes
+        assert_eq!(15 + 4 + 19 * 8, bytes.len());
+
+        let mut loaded = IntegerSegmentTree::load(&mut bytes.as_slice(), sum).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        for i in 0..10 {
+            for j in i..10 {
+                assert_eq!(original.query(i, j), loaded.query(i, j));
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_truncated() {
+        let original = IntegerSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4], i32::MAX, min);
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+        bytes.pop();
+        assert!(IntegerSegmentTree::load(&mut bytes.as_slice(), min).is_err());
+    }
+
+    #[test]
+    fn test_load_huge_number_of_leaves() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"SGTR");
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.push(1);
+        bytes.extend_from_slice(&(1u64 << 58).to_le_bytes());
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        let error = IntegerSegmentTree::load(&mut bytes.as_slice(), min)
+            .err()
+            .unwrap();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+    }
+
+    #[test]
+    fn test_dump_text() {
+        let mut result = IntegerSegmentTree::build(&[1, 2, 3], 0, |x, y| x + y);
+        result.range_update(0, 1, 1);
+        assert_eq!(
+            "IntegerSegmentTree (format version 1)\n\
+            leaves: 3\n\
+            neutral value: 0\n\
+            node 0 [0..2]: value 8, pending 0\n\
+            node 1 [0..1]: value 3, pending 1\n\
+            node 2 [0..0]: value 1, pending 0\n\
+            node 3 [1..1]: value 2, pending 0\n\
+            node 4 [2..2]: value 3, pending 0\n",
+            result.dump_text()
+        );
+    }
 }
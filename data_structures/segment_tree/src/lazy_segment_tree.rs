@@ -0,0 +1,765 @@
+use crate::error::{SegmentTreeError, check_index, check_num_nodes, check_range};
+use crate::generic_segment_tree::Monoid;
+use crate::range::{Leaves, Range, left_right_child_index};
+use crate::serialization::{Encode, FORMAT_VERSION, TreeKind, read_header, write_header};
+use std::fmt::{Debug, Write};
+use std::io;
+
+/// Describes how range updates act on the values aggregated by a [`LazySegmentTree`].
+///
+/// Values and updates live in two different algebras:
+/// - `ValueMonoid` is used to merge the values of two adjacent segments;
+/// - `UpdateMonoid` is used to compose two updates pending on the same segment:
+///   `combine(older, newer)` must be the update that applies `older` first and then `newer`.
+///
+/// `apply` returns the aggregated value of a segment of `segment_len` elements once
+/// `update` has been applied to each of them.
+pub trait LazyAction {
+    type ValueMonoid: Monoid;
+    type UpdateMonoid: Monoid;
+
+    fn apply(update: &Update<Self>, value: &Value<Self>, segment_len: usize) -> Value<Self>;
+}
+
+pub type Value<A> = <<A as LazyAction>::ValueMonoid as Monoid>::Value;
+pub type Update<A> = <<A as LazyAction>::UpdateMonoid as Monoid>::Value;
+
+/// A segment tree supporting range updates and range queries in O(log n) thanks to
+/// lazy propagation. It uses the same implicit layout as `IntegerSegmentTree`.
+///
+/// A pending update stored in a node has not been applied to the node yet: it is applied
+/// (and pushed down to its children) the first time the node is visited.
+pub struct LazySegmentTree<A: LazyAction> {
+    tree: Vec<Value<A>>,
+    pending_updates: Vec<Option<Update<A>>>,
+    num_leaf: usize,
+}
+
+impl<A: LazyAction> LazySegmentTree<A> {
+    pub fn build_empty(num_elems: usize) -> Self {
+        Self::try_build_empty(num_elems).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `build_empty`, but fails instead of aborting when the tree does not fit in memory.
+    pub fn try_build_empty(num_elems: usize) -> Result<Self, SegmentTreeError> {
+        Self::check_num_nodes(num_elems)?;
+        Ok(Self {
+            tree: vec![A::ValueMonoid::identity(); Self::num_nodes(num_elems)],
+            pending_updates: vec![None; Self::num_nodes(num_elems)],
+            num_leaf: num_elems,
+        })
+    }
+
+    pub fn build(a: &[Value<A>]) -> Self {
+        Self::try_build(a).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `build`, but fails instead of aborting when the tree does not fit in memory.
+    pub fn try_build(a: &[Value<A>]) -> Result<Self, SegmentTreeError> {
+        Self::check_num_nodes(a.len())?;
+        Ok(Self::build_unchecked(a))
+    }
+
+    // each node holds a value and a pending update
+    fn check_num_nodes(num_leaf: usize) -> Result<(), SegmentTreeError> {
+        check_num_nodes(
+            num_leaf,
+            size_of::<Value<A>>() + size_of::<Option<Update<A>>>(),
+        )
+    }
+
+    fn build_unchecked(a: &[Value<A>]) -> Self {
+        let mut implicit_tree = vec![A::ValueMonoid::identity(); Self::num_nodes(a.len())];
+        if !a.is_empty() {
+            Self::build_rec(a, &mut implicit_tree, Range::new(0, a.len() - 1), 0);
+        }
+        Self {
+            tree: implicit_tree,
+            //initially there are no pending updates
+            pending_updates: vec![None; Self::num_nodes(a.len())],
+            num_leaf: a.len(),
+        }
+    }
+
+    fn num_nodes(num_leaf: usize) -> usize {
+        (2 * num_leaf).saturating_sub(1)
+    }
+
+    fn build_rec(a: &[Value<A>], tree: &mut [Value<A>], node_segment: Range, index: usize) {
+        if node_segment.is_single_point() {
+            tree[index] = a[node_segment.start].clone();
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        Self::build_rec(a, tree, node_segment.left_half(), left_child_index);
+        Self::build_rec(a, tree, node_segment.right_half(), right_child_index);
+        tree[index] = A::ValueMonoid::combine(&tree[left_child_index], &tree[right_child_index]);
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_leaf
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_leaf == 0
+    }
+
+    /// Returns the raw value stored in the node with implicit index `i`, which does not
+    /// include the update pending on it (if any).
+    pub fn get_index(&self, i: usize) -> &Value<A> {
+        &self.tree[i]
+    }
+
+    /// Returns the update pending on the node with implicit index `i`.
+    pub fn pending_update(&self, i: usize) -> Option<&Update<A>> {
+        self.pending_updates[i].as_ref()
+    }
+
+    pub fn get(&mut self, i: usize) -> Value<A> {
+        check_index(i, self.num_leaf).unwrap_or_else(|e| panic!("{e}"));
+        self.query(i, i)
+    }
+
+    // applies every pending update down to the leaves, visiting each node once
+    fn push_all_pending_updates(&mut self, node_segment: Range, index: usize) {
+        self.handle_pending_updates(node_segment, index);
+        if node_segment.is_single_point() {
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.push_all_pending_updates(node_segment.left_half(), left_child_index);
+        self.push_all_pending_updates(node_segment.right_half(), right_child_index);
+    }
+
+    /// Iterates the current values of the elements from left to right, after pushing every
+    /// pending update down to the leaves in O(n).
+    pub fn leaves(&mut self) -> impl Iterator<Item = &Value<A>> {
+        if !self.is_empty() {
+            self.push_all_pending_updates(Range::new(0, self.num_leaf - 1), 0);
+        }
+        Leaves::new(&self.tree, self.num_leaf)
+    }
+
+    /// Returns the current values of all the elements in O(n).
+    pub fn to_vec(&mut self) -> Vec<Value<A>> {
+        self.leaves().cloned().collect()
+    }
+
+    pub fn query(&mut self, i: usize, j: usize) -> Value<A> {
+        self.try_query(i, j).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Returns the aggregated value of `[i, j]`, the identity if the range is empty
+    /// (`i > j`) or an error if it ends past the last element.
+    pub fn try_query(&mut self, i: usize, j: usize) -> Result<Value<A>, SegmentTreeError> {
+        check_range(i, j, self.num_leaf)?;
+        if i > j {
+            return Ok(A::ValueMonoid::identity());
+        }
+        Ok(self.query_rec(Range::new(i, j), Range::new(0, self.num_leaf - 1), 0))
+    }
+
+    fn query_rec(&mut self, query_range: Range, node_segment: Range, index: usize) -> Value<A> {
+        // Since we are visiting this node, we must apply any pending lazy updates: in case of
+        // total overlap we must return the fully-updated value and in case of partial overlap
+        // the update must be propagated to the children we are going to visit.
+        self.handle_pending_updates(node_segment, index);
+        if query_range.no_overlap(node_segment) {
+            return A::ValueMonoid::identity();
+        }
+        if query_range.contains(node_segment) {
+            return self.tree[index].clone();
+        }
+        // partial overlap
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        let left_value = self.query_rec(query_range, node_segment.left_half(), left_child_index);
+        let right_value = self.query_rec(query_range, node_segment.right_half(), right_child_index);
+        A::ValueMonoid::combine(&left_value, &right_value)
+    }
+
+    fn handle_pending_updates(&mut self, node_segment: Range, index: usize) {
+        if let Some(pending_update) = self.pending_updates[index].take() {
+            self.update_node_and_propagate(node_segment, index, &pending_update);
+        }
+    }
+
+    fn update_node_and_propagate(&mut self, node_segment: Range, index: usize, update: &Update<A>) {
+        self.tree[index] = A::apply(update, &self.tree[index], node_segment.size());
+        // if the node is not a leaf the update is lazily recorded on its children,
+        // after the ones that are already pending on them
+        if !node_segment.is_single_point() {
+            let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+            for child_index in [left_child_index, right_child_index] {
+                let composed = match &self.pending_updates[child_index] {
+                    Some(older) => A::UpdateMonoid::combine(older, update),
+                    None => update.clone(),
+                };
+                self.pending_updates[child_index] = Some(composed);
+            }
+        }
+    }
+
+    /// Applies `update` to every position in `[i, j]`.
+    pub fn range_update(&mut self, i: usize, j: usize, update: Update<A>) {
+        self.try_range_update(i, j, update)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `range_update`, but fails without touching the tree if `[i, j]` ends past the
+    /// last element.
+    pub fn try_range_update(
+        &mut self,
+        i: usize,
+        j: usize,
+        update: Update<A>,
+    ) -> Result<(), SegmentTreeError> {
+        check_range(i, j, self.num_leaf)?;
+        if i > j {
+            return Ok(());
+        }
+        self.range_update_rec(
+            Range::new(i, j),
+            Range::new(0, self.num_leaf - 1),
+            &update,
+            0,
+        );
+        Ok(())
+    }
+
+    fn range_update_rec(
+        &mut self,
+        query_range: Range,
+        node_segment: Range,
+        update: &Update<A>,
+        index: usize,
+    ) {
+        self.handle_pending_updates(node_segment, index);
+        if node_segment.no_overlap(query_range) {
+            return;
+        }
+        if query_range.contains(node_segment) {
+            // total overlap: the value of the node is needed right now by its partially
+            // overlapped father, while the update is not needed by its children yet
+            self.update_node_and_propagate(node_segment, index, update);
+            return;
+        }
+        // partial overlap: both children are visited, so both of them will be up to date
+        // when the value of the current node is recomputed
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.range_update_rec(
+            query_range,
+            node_segment.left_half(),
+            update,
+            left_child_index,
+        );
+        self.range_update_rec(
+            query_range,
+            node_segment.right_half(),
+            update,
+            right_child_index,
+        );
+        self.tree[index] =
+            A::ValueMonoid::combine(&self.tree[left_child_index], &self.tree[right_child_index]);
+    }
+
+    /// Replaces the value at position `i` with `value`.
+    pub fn update(&mut self, i: usize, value: Value<A>) {
+        self.try_update(i, value).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `update`, but fails if there is no element at position `i`.
+    pub fn try_update(&mut self, i: usize, value: Value<A>) -> Result<(), SegmentTreeError> {
+        check_index(i, self.num_leaf)?;
+        self.update_rec(i, value, Range::new(0, self.num_leaf - 1), 0);
+        Ok(())
+    }
+
+    fn update_rec(&mut self, i: usize, value: Value<A>, node_segment: Range, index: usize) {
+        self.handle_pending_updates(node_segment, index);
+        if node_segment.is_single_point() {
+            self.tree[index] = value;
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        // the sibling that is not on the path must be brought up to date as well,
+        // since its value is used to recompute the current node
+        if i <= node_segment.middle() {
+            self.update_rec(i, value, node_segment.left_half(), left_child_index);
+            self.handle_pending_updates(node_segment.right_half(), right_child_index);
+        } else {
+            self.handle_pending_updates(node_segment.left_half(), left_child_index);
+            self.update_rec(i, value, node_segment.right_half(), right_child_index);
+        }
+        self.tree[index] =
+            A::ValueMonoid::combine(&self.tree[left_child_index], &self.tree[right_child_index]);
+    }
+
+    /// Returns the largest `r` such that `pred` holds on the combination of `a[l..r]`,
+    /// taking into account every pending update.
+    ///
+    /// `pred` must hold on the identity and be monotone, as in `SegmentTree::max_right`.
+    pub fn max_right<P: Fn(&Value<A>) -> bool>(&mut self, l: usize, pred: P) -> usize {
+        assert!(
+            pred(&A::ValueMonoid::identity()),
+            "The predicate must hold on the identity"
+        );
+        if l >= self.num_leaf {
+            return self.num_leaf;
+        }
+        let mut acc = A::ValueMonoid::identity();
+        self.max_right_rec(l, &pred, Range::new(0, self.num_leaf - 1), 0, &mut acc)
+            .unwrap_or(self.num_leaf)
+    }
+
+    fn max_right_rec<P: Fn(&Value<A>) -> bool>(
+        &mut self,
+        l: usize,
+        pred: &P,
+        node_segment: Range,
+        index: usize,
+        acc: &mut Value<A>,
+    ) -> Option<usize> {
+        if node_segment.end < l {
+            return None;
+        }
+        // the value of the node (or of its children) is about to be read
+        self.handle_pending_updates(node_segment, index);
+        if node_segment.start >= l {
+            let extended = A::ValueMonoid::combine(acc, &self.tree[index]);
+            if pred(&extended) {
+                *acc = extended;
+                return None;
+            }
+            if node_segment.is_single_point() {
+                return Some(node_segment.start);
+            }
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        if let Some(failing) =
+            self.max_right_rec(l, pred, node_segment.left_half(), left_child_index, acc)
+        {
+            return Some(failing);
+        }
+        self.max_right_rec(l, pred, node_segment.right_half(), right_child_index, acc)
+    }
+
+    /// Returns the smallest `l` such that `pred` holds on the combination of `a[l..r]`,
+    /// taking into account every pending update.
+    ///
+    /// `pred` must hold on the identity and be monotone, as in `SegmentTree::min_left`.
+    pub fn min_left<P: Fn(&Value<A>) -> bool>(&mut self, r: usize, pred: P) -> usize {
+        assert!(
+            pred(&A::ValueMonoid::identity()),
+            "The predicate must hold on the identity"
+        );
+        assert!(r <= self.num_leaf, "Index {r} out of bounds");
+        if r == 0 {
+            return 0;
+        }
+        let mut acc = A::ValueMonoid::identity();
+        self.min_left_rec(r - 1, &pred, Range::new(0, self.num_leaf - 1), 0, &mut acc)
+            .map_or(0, |failing| failing + 1)
+    }
+
+    fn min_left_rec<P: Fn(&Value<A>) -> bool>(
+        &mut self,
+        last: usize,
+        pred: &P,
+        node_segment: Range,
+        index: usize,
+        acc: &mut Value<A>,
+    ) -> Option<usize> {
+        if node_segment.start > last {
+            return None;
+        }
+        self.handle_pending_updates(node_segment, index);
+        if node_segment.end <= last {
+            let extended = A::ValueMonoid::combine(&self.tree[index], acc);
+            if pred(&extended) {
+                *acc = extended;
+                return None;
+            }
+            if node_segment.is_single_point() {
+                return Some(node_segment.start);
+            }
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        if let Some(failing) = self.min_left_rec(
+            last,
+            pred,
+            node_segment.right_half(),
+            right_child_index,
+            acc,
+        ) {
+            return Some(failing);
+        }
+        self.min_left_rec(last, pred, node_segment.left_half(), left_child_index, acc)
+    }
+}
+
+impl<A: LazyAction> LazySegmentTree<A>
+where
+    Value<A>: Encode,
+    Update<A>: Encode,
+{
+    /// Writes the tree, pending updates included, in the little-endian binary format shared
+    /// by the trees of this crate: a versioned header followed by, for each node in implicit
+    /// order, its value and its pending update, both written by their `Encode` impl.
+    ///
+    /// The action is not saved, so the tree must be loaded with the same `A`.
+    /// Every number is written separately, so `writer` should be buffered.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, TreeKind::Lazy, self.num_leaf)?;
+        for (value, pending) in self.tree.iter().zip(&self.pending_updates) {
+            value.encode(writer)?;
+            pending.encode(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tree written by `save`.
+    pub fn load<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let num_leaf = read_header(
+            reader,
+            TreeKind::Lazy,
+            size_of::<Value<A>>() + size_of::<Option<Update<A>>>(),
+        )?;
+        // the vectors grow as the nodes are read, so a corrupt number of leaves fails on
+        // the missing bytes instead of allocating
+        let mut tree = Vec::new();
+        let mut pending_updates = Vec::new();
+        for _ in 0..Self::num_nodes(num_leaf) {
+            tree.push(Value::<A>::decode(reader)?);
+            pending_updates.push(Option::<Update<A>>::decode(reader)?);
+        }
+        Ok(Self {
+            tree,
+            pending_updates,
+            num_leaf,
+        })
+    }
+}
+
+impl<A: LazyAction> LazySegmentTree<A>
+where
+    Value<A>: Debug,
+    Update<A>: Debug,
+{
+    /// Describes the tree in a human-readable form, one node per line in implicit order.
+    pub fn dump_text(&self) -> String {
+        let mut text = format!(
+            "LazySegmentTree (format version {FORMAT_VERSION})\nleaves: {}\n",
+            self.num_leaf
+        );
+        if self.num_leaf > 0 {
+            self.dump_text_rec(Range::new(0, self.num_leaf - 1), 0, &mut text);
+        }
+        text
+    }
+
+    // the implicit layout is a preorder visit, so the nodes are dumped by increasing index
+    fn dump_text_rec(&self, node_segment: Range, index: usize, text: &mut String) {
+        // writing to a String never fails
+        writeln!(
+            text,
+            "node {index} {node_segment}: value {:?}, pending {:?}",
+            self.tree[index], self.pending_updates[index]
+        )
+        .unwrap();
+        if node_segment.is_single_point() {
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.dump_text_rec(node_segment.left_half(), left_child_index, text);
+        self.dump_text_rec(node_segment.right_half(), right_child_index, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::SegmentTreeError;
+    use crate::generic_segment_tree::Monoid;
+    use crate::lazy_segment_tree::{LazyAction, LazySegmentTree};
+
+    struct Sum;
+
+    impl Monoid for Sum {
+        type Value = i64;
+
+        fn identity() -> i64 {
+            0
+        }
+
+        fn combine(a: &i64, b: &i64) -> i64 {
+            a + b
+        }
+    }
+
+    struct Min;
+
+    impl Monoid for Min {
+        type Value = i64;
+
+        fn identity() -> i64 {
+            i64::MAX
+        }
+
+        fn combine(a: &i64, b: &i64) -> i64 {
+            *a.min(b)
+        }
+    }
+
+    // range add, range sum
+    struct AddSum;
+
+    impl LazyAction for AddSum {
+        type ValueMonoid = Sum;
+        type UpdateMonoid = Sum;
+
+        fn apply(update: &i64, value: &i64, segment_len: usize) -> i64 {
+            value + update * segment_len as i64
+        }
+    }
+
+    // range assignment: the newest assignment overrides the older one
+    struct Assign;
+
+    impl Monoid for Assign {
+        type Value = Option<i64>;
+
+        fn identity() -> Option<i64> {
+            None
+        }
+
+        fn combine(older: &Option<i64>, newer: &Option<i64>) -> Option<i64> {
+            newer.or(*older)
+        }
+    }
+
+    // range assign, range min
+    struct AssignMin;
+
+    impl LazyAction for AssignMin {
+        type ValueMonoid = Min;
+        type UpdateMonoid = Assign;
+
+        fn apply(update: &Option<i64>, value: &i64, _segment_len: usize) -> i64 {
+            update.unwrap_or(*value)
+        }
+    }
+
+    // x -> b * x + c, represented as (b, c)
+    struct Affine;
+
+    impl Monoid for Affine {
+        type Value = (i64, i64);
+
+        fn identity() -> (i64, i64) {
+            (1, 0)
+        }
+
+        fn combine(older: &(i64, i64), newer: &(i64, i64)) -> (i64, i64) {
+            // newer(older(x)) = b2 * (b1 * x + c1) + c2
+            (newer.0 * older.0, newer.0 * older.1 + newer.1)
+        }
+    }
+
+    // range affine, range sum
+    struct AffineSum;
+
+    impl LazyAction for AffineSum {
+        type ValueMonoid = Sum;
+        type UpdateMonoid = Affine;
+
+        fn apply(update: &(i64, i64), value: &i64, segment_len: usize) -> i64 {
+            update.0 * value + update.1 * segment_len as i64
+        }
+    }
+
+    #[test]
+    fn test_range_add_sum() {
+        let mut tree = LazySegmentTree::<AddSum>::build(&[3, 4, 5, 3, 8, 12, -4]);
+        tree.range_update(1, 4, 2);
+        assert_eq!(39, tree.query(0, 6));
+        assert_eq!(13, tree.query(1, 2));
+        assert_eq!(10, tree.query(4, 4));
+        assert_eq!(18, tree.query(4, 6));
+    }
+
+    #[test]
+    fn test_range_add_negative_update() {
+        let mut tree = LazySegmentTree::<AddSum>::build_empty(10);
+        tree.range_update(0, 5, 5);
+        tree.range_update(2, 7, -3);
+        //[5, 5, 2, 2, 2, 2, -3, -3, 0, 0]
+        assert_eq!(12, tree.query(0, 9));
+        assert_eq!(2, tree.query(3, 3));
+        assert_eq!(-1, tree.query(5, 6));
+        assert_eq!(-6, tree.query(6, 9));
+    }
+
+    #[test]
+    fn test_range_assign_min() {
+        let mut tree = LazySegmentTree::<AssignMin>::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+        tree.range_update(0, 4, Some(10));
+        assert_eq!(10, tree.query(0, 4));
+        assert_eq!(2, tree.query(0, 9));
+        tree.range_update(3, 9, Some(20));
+        //[10, 10, 10, 20, 20, 20, 20, 20, 20, 20]
+        assert_eq!(10, tree.query(0, 9));
+        assert_eq!(20, tree.query(3, 9));
+        assert_eq!(20, tree.get(6));
+    }
+
+    #[test]
+    fn test_range_affine_sum() {
+        let mut tree = LazySegmentTree::<AffineSum>::build(&[1, 2, 3, 4, 5]);
+        tree.range_update(0, 3, (2, 1));
+        //[3, 5, 7, 9, 5]
+        tree.range_update(2, 4, (3, 0));
+        //[3, 5, 21, 27, 15]
+        assert_eq!(71, tree.query(0, 4));
+        assert_eq!(26, tree.query(1, 2));
+        assert_eq!(27, tree.get(3));
+    }
+
+    #[test]
+    fn test_point_update_with_pending_sibling() {
+        let mut tree = LazySegmentTree::<AddSum>::build_empty(8);
+        tree.range_update(0, 7, 1);
+        tree.update(2, 10);
+        assert_eq!(17, tree.query(0, 7));
+        assert_eq!(12, tree.query(1, 3));
+    }
+
+    #[test]
+    fn test_pending_updates_are_lazy() {
+        let mut tree = LazySegmentTree::<AddSum>::build(&[1, 1, 1, 1]);
+        tree.range_update(0, 3, 1);
+        // the root is up to date while its children still hold the update as pending
+        assert_eq!(8, *tree.get_index(0));
+        assert_eq!(None, tree.pending_update(0));
+        assert_eq!(2, *tree.get_index(1));
+        assert_eq!(Some(&1), tree.pending_update(1));
+
+        assert_eq!(4, tree.query(0, 1));
+        assert_eq!(4, *tree.get_index(1));
+        assert_eq!(None, tree.pending_update(1));
+    }
+    #[test]
+    fn test_max_right_with_pending_updates() {
+        let mut tree = LazySegmentTree::<AddSum>::build(&[1, 1, 1, 1, 1, 1, 1, 1]);
+        tree.range_update(2, 5, 3);
+        //[1, 1, 4, 4, 4, 4, 1, 1]
+        // first j >= 0 with sum(0..=j) >= 10
+        assert_eq!(3, tree.max_right(0, |&s| s < 10));
+        assert_eq!(8, tree.max_right(4, |&s| s < 100));
+        tree.range_update(0, 7, -1);
+        //[0, 0, 3, 3, 3, 3, 0, 0]
+        assert_eq!(2, tree.max_right(0, |&s| s < 1));
+        assert_eq!(4, tree.max_right(1, |&s| s <= 6));
+    }
+
+    #[test]
+    fn test_min_left_with_pending_updates() {
+        let mut tree = LazySegmentTree::<AssignMin>::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+        tree.range_update(0, 6, Some(10));
+        //[10, 10, 10, 10, 10, 10, 10, 31, 4, 16]
+        // smallest l such that every value in a[l..r] is at least the threshold
+        assert_eq!(0, tree.min_left(8, |&m| m >= 10));
+        assert_eq!(7, tree.min_left(8, |&m| m >= 11));
+        assert_eq!(9, tree.min_left(9, |&m| m >= 5));
+        assert_eq!(9, tree.min_left(10, |&m| m >= 16));
+    }
+
+    #[test]
+    fn test_try_operations() {
+        let mut tree = LazySegmentTree::<AddSum>::build(&[3, 4, 5]);
+        let out_of_bounds = SegmentTreeError::RangeOutOfBounds {
+            start: 1,
+            end: 3,
+            len: 3,
+        };
+        assert_eq!(Err(out_of_bounds), tree.try_range_update(1, 3, 10));
+        assert_eq!(Err(out_of_bounds), tree.try_query(1, 3));
+        assert_eq!(
+            Err(SegmentTreeError::IndexOutOfBounds { index: 3, len: 3 }),
+            tree.try_update(3, 0)
+        );
+        // rejected operations leave the tree untouched
+        assert_eq!(Ok(12), tree.try_query(0, 2));
+        assert_eq!(Ok(()), tree.try_range_update(1, 2, 10));
+        assert_eq!(Ok(()), tree.try_update(0, 1));
+        assert_eq!(Ok(30), tree.try_query(0, 2));
+        assert_eq!(Ok(0), tree.try_query(2, 1));
+    }
+
+    #[test]
+    fn test_build_empty() {
+        let mut tree = LazySegmentTree::<AddSum>::build_empty(4);
+        tree.range_update(1, 3, 2);
+        assert_eq!(vec![0, 2, 2, 2], tree.to_vec());
+        assert_eq!(
+            Some(SegmentTreeError::TooManyElements { len: usize::MAX }),
+            LazySegmentTree::<AddSum>::try_build_empty(usize::MAX).err()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 out of bounds for 3 elements")]
+    fn test_get_out_of_bounds() {
+        LazySegmentTree::<AddSum>::build(&[3, 4, 5]).get(3);
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let mut tree = LazySegmentTree::<AddSum>::build(&[]);
+        assert_eq!(0, tree.query(1, 0));
+        tree.range_update(1, 0, 5);
+        assert!(tree.try_query(0, 0).is_err());
+    }
+
+    #[test]
+    fn test_to_vec() {
+        let mut tree = LazySegmentTree::<AffineSum>::build(&[1, 2, 3, 4, 5]);
+        tree.range_update(0, 3, (2, 0));
+        tree.range_update(2, 4, (1, -1));
+        assert_eq!(vec![2, 4, 5, 7, 4], tree.to_vec());
+        for index in 0..9 {
+            assert_eq!(None, tree.pending_update(index));
+        }
+        assert_eq!(22, tree.query(0, 4));
+    }
+
+    #[test]
+    fn test_save_and_load_with_pending_updates() {
+        let mut original = LazySegmentTree::<AffineSum>::build(&[1, 2, 3, 4, 5]);
+        original.range_update(0, 3, (2, 0));
+        original.range_update(2, 4, (1, -1));
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+
+        let mut loaded = LazySegmentTree::<AffineSum>::load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        assert_eq!(original.to_vec(), loaded.to_vec());
+
+        bytes.pop();
+        assert!(LazySegmentTree::<AffineSum>::load(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_dump_text() {
+        let mut tree = LazySegmentTree::<AssignMin>::build(&[4, 2, 7]);
+        tree.range_update(0, 1, Some(5));
+        assert_eq!(
+            "LazySegmentTree (format version 1)\n\
+            leaves: 3\n\
+            node 0 [0..2]: value 5, pending None\n\
+            node 1 [0..1]: value 5, pending None\n\
+            node 2 [0..0]: value 4, pending Some(Some(5))\n\
+            node 3 [1..1]: value 2, pending Some(Some(5))\n\
+            node 4 [2..2]: value 7, pending None\n",
+            tree.dump_text()
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use crate::dynamic_segment_tree::Span;
+use crate::error::check_num_nodes;
+use crate::range::{Range, left_right_child_index};
+
+/// The line `y = k * x + m`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line {
+    pub k: i64,
+    pub m: i64,
+}
+
+impl Line {
+    pub fn new(k: i64, m: i64) -> Self {
+        Line { k, m }
+    }
+
+    /// Evaluates the line at `x` on i128, which cannot overflow for any i64 input.
+    pub fn eval(&self, x: i64) -> i128 {
+        self.k as i128 * x as i128 + self.m as i128
+    }
+}
+
+// Whether a tree keeps, at every x, the lowest or the highest line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Objective {
+    Min,
+    Max,
+}
+
+impl Objective {
+    fn better(&self, a: i128, b: i128) -> bool {
+        match self {
+            Objective::Min => a < b,
+            Objective::Max => a > b,
+        }
+    }
+
+    fn best(&self, current: Option<i128>, candidate: i128) -> Option<i128> {
+        match current {
+            Some(value) if !self.better(candidate, value) => Some(value),
+            _ => Some(candidate),
+        }
+    }
+}
+
+// The step shared by both trees. The node over [start, end] keeps the line that wins at its
+// middle point; since two lines cross at most once, the other line can only win on one side
+// of the middle, and it is returned together with that side (true for the left half). At
+// the leaves the loser can be dropped.
+fn keep_better(
+    stored: &mut Option<Line>,
+    new: Line,
+    start: i64,
+    middle: i64,
+    objective: Objective,
+) -> Option<(Line, bool)> {
+    let Some(current) = *stored else {
+        *stored = Some(new);
+        return None;
+    };
+    let new_wins_start = objective.better(new.eval(start), current.eval(start));
+    let new_wins_middle = objective.better(new.eval(middle), current.eval(middle));
+    let loser = if new_wins_middle {
+        *stored = Some(new);
+        current
+    } else {
+        new
+    };
+    // if the winner changes between start and middle the lines cross in the left half
+    Some((loser, new_wins_start != new_wins_middle))
+}
+
+/// A Li Chao tree over the integer x-domain `[min_x, max_x]`: it stores a set of lines and
+/// returns the minimum (or maximum) of their values at a given x.
+///
+/// Every node keeps at most one line, the best one at the middle of its segment, so a
+/// point query only looks at the O(log C) nodes on the path to its leaf, where C is the size
+/// of the domain. A line is inserted in O(log C) and a line restricted to a segment of the
+/// domain in O(log² C). The nodes use the same implicit layout as `IntegerSegmentTree`, so
+/// the tree takes O(C) memory: see [`DynamicLiChaoTree`] for wide domains.
+pub struct LiChaoTree {
+    tree: Vec<Option<Line>>,
+    min_x: i64,
+    num_points: usize,
+    objective: Objective,
+}
+
+impl LiChaoTree {
+    /// Creates a tree answering the minimum of the lines at each x.
+    pub fn new_min(min_x: i64, max_x: i64) -> Self {
+        Self::new(min_x, max_x, Objective::Min)
+    }
+
+    /// Creates a tree answering the maximum of the lines at each x.
+    pub fn new_max(min_x: i64, max_x: i64) -> Self {
+        Self::new(min_x, max_x, Objective::Max)
+    }
+
+    fn new(min_x: i64, max_x: i64, objective: Objective) -> Self {
+        assert!(min_x <= max_x, "Empty x-domain");
+        let num_points = usize::try_from(max_x as i128 - min_x as i128 + 1)
+            .unwrap_or_else(|_| panic!("x-domain [{min_x}, {max_x}] too large"));
+        check_num_nodes(num_points, size_of::<Option<Line>>()).unwrap_or_else(|e| panic!("{e}"));
+        Self {
+            tree: vec![None; 2 * num_points - 1],
+            min_x,
+            num_points,
+            objective,
+        }
+    }
+
+    fn x(&self, offset: usize) -> i64 {
+        self.min_x + offset as i64
+    }
+
+    fn offset(&self, x: i64) -> usize {
+        assert!(
+            x >= self.min_x && self.x(self.num_points - 1) >= x,
+            "Point {x} out of bounds"
+        );
+        (x as i128 - self.min_x as i128) as usize
+    }
+
+    /// Inserts `line` on the whole domain.
+    pub fn add_line(&mut self, line: Line) {
+        self.insert_rec(line, Range::new(0, self.num_points - 1), 0);
+    }
+
+    /// Inserts `line` only on the points `x1 <= x <= x2`: it will be ignored elsewhere.
+    pub fn add_segment(&mut self, line: Line, x1: i64, x2: i64) {
+        if x1 > x2 {
+            return;
+        }
+        let segment = Range::new(self.offset(x1), self.offset(x2));
+        self.add_segment_rec(line, segment, Range::new(0, self.num_points - 1), 0);
+    }
+
+    fn insert_rec(&mut self, line: Line, node_segment: Range, index: usize) {
+        let start = self.x(node_segment.start);
+        let middle = self.x(node_segment.middle());
+        let Some((loser, go_left)) =
+            keep_better(&mut self.tree[index], line, start, middle, self.objective)
+        else {
+            return;
+        };
+        if node_segment.is_single_point() {
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        if go_left {
+            self.insert_rec(loser, node_segment.left_half(), left_child_index);
+        } else {
+            self.insert_rec(loser, node_segment.right_half(), right_child_index);
+        }
+    }
+
+    // the segment is split as in a range update, and the line is inserted in each of the
+    // O(log C) nodes covering it
+    fn add_segment_rec(&mut self, line: Line, segment: Range, node_segment: Range, index: usize) {
+        if segment.no_overlap(node_segment) {
+            return;
+        }
+        if segment.contains(node_segment) {
+            self.insert_rec(line, node_segment, index);
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+        self.add_segment_rec(line, segment, node_segment.left_half(), left_child_index);
+        self.add_segment_rec(line, segment, node_segment.right_half(), right_child_index);
+    }
+
+    /// Returns the minimum (or maximum) value at `x` of the lines inserted there, or `None`
+    /// if there is none.
+    pub fn query(&self, x: i64) -> Option<i128> {
+        let offset = self.offset(x);
+        let mut node_segment = Range::new(0, self.num_points - 1);
+        let mut index = 0;
+        let mut best = None;
+        loop {
+            if let Some(line) = self.tree[index] {
+                best = self.objective.best(best, line.eval(x));
+            }
+            if node_segment.is_single_point() {
+                return best;
+            }
+            let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+            if offset <= node_segment.middle() {
+                node_segment = node_segment.left_half();
+                index = left_child_index;
+            } else {
+                node_segment = node_segment.right_half();
+                index = right_child_index;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Node {
+    line: Option<Line>,
+    id_left: Option<usize>,
+    id_right: Option<usize>,
+}
+
+/// A Li Chao tree over a huge x-domain (up to the whole i64 range) that allocates its nodes
+/// only when a line reaches them: inserting a line creates at most O(log C) nodes, and
+/// inserting a segment O(log² C).
+pub struct DynamicLiChaoTree {
+    nodes: Vec<Node>,
+    universe: Span,
+    objective: Objective,
+}
+
+impl DynamicLiChaoTree {
+    /// Creates a tree answering the minimum of the lines at each x.
+    pub fn new_min(min_x: i64, max_x: i64) -> Self {
+        Self::new(min_x, max_x, Objective::Min)
+    }
+
+    /// Creates a tree answering the maximum of the lines at each x.
+    pub fn new_max(min_x: i64, max_x: i64) -> Self {
+        Self::new(min_x, max_x, Objective::Max)
+    }
+
+    fn new(min_x: i64, max_x: i64, objective: Objective) -> Self {
+        assert!(min_x <= max_x, "Empty x-domain");
+        Self {
+            // the root always exists
+            nodes: vec![Node::default()],
+            universe: Span::new(min_x, max_x),
+            objective,
+        }
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Inserts `line` on the whole domain.
+    pub fn add_line(&mut self, line: Line) {
+        self.insert_rec(line, self.universe, 0);
+    }
+
+    /// Inserts `line` only on the points `x1 <= x <= x2`: it will be ignored elsewhere.
+    pub fn add_segment(&mut self, line: Line, x1: i64, x2: i64) {
+        if x1 > x2 {
+            return;
+        }
+        let segment = Span::new(x1, x2);
+        assert!(
+            self.universe.contains(segment),
+            "Range [{x1}, {x2}] out of bounds"
+        );
+        self.add_segment_rec(line, segment, self.universe, 0);
+    }
+
+    // returns the id of a child of `node_id`, creating it if it does not exist yet
+    fn child(&mut self, node_id: usize, left: bool) -> usize {
+        let existing = if left {
+            self.nodes[node_id].id_left
+        } else {
+            self.nodes[node_id].id_right
+        };
+        if let Some(id) = existing {
+            return id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(Node::default());
+        if left {
+            self.nodes[node_id].id_left = Some(id);
+        } else {
+            self.nodes[node_id].id_right = Some(id);
+        }
+        id
+    }
+
+    fn insert_rec(&mut self, line: Line, node_segment: Span, node_id: usize) {
+        let Some((loser, go_left)) = keep_better(
+            &mut self.nodes[node_id].line,
+            line,
+            node_segment.start,
+            node_segment.middle(),
+            self.objective,
+        ) else {
+            return;
+        };
+        if node_segment.start == node_segment.end {
+            return;
+        }
+        let child_id = self.child(node_id, go_left);
+        let child_segment = if go_left {
+            node_segment.left_half()
+        } else {
+            node_segment.right_half()
+        };
+        self.insert_rec(loser, child_segment, child_id);
+    }
+
+    fn add_segment_rec(&mut self, line: Line, segment: Span, node_segment: Span, node_id: usize) {
+        if segment.contains(node_segment) {
+            self.insert_rec(line, node_segment, node_id);
+            return;
+        }
+        // partial overlap: only the children that overlap the segment are created
+        for (child_segment, left) in [
+            (node_segment.left_half(), true),
+            (node_segment.right_half(), false),
+        ] {
+            if !segment.no_overlap(child_segment) {
+                let child_id = self.child(node_id, left);
+                self.add_segment_rec(line, segment, child_segment, child_id);
+            }
+        }
+    }
+
+    /// Returns the minimum (or maximum) value at `x` of the lines inserted there, or `None`
+    /// if there is none.
+    pub fn query(&self, x: i64) -> Option<i128> {
+        assert!(
+            self.universe.contains(Span::new(x, x)),
+            "Point {x} out of bounds"
+        );
+        let mut node_segment = self.universe;
+        let mut node_id = Some(0);
+        let mut best = None;
+        while let Some(id) = node_id {
+            let node = &self.nodes[id];
+            if let Some(line) = node.line {
+                best = self.objective.best(best, line.eval(x));
+            }
+            if x <= node_segment.middle() {
+                node_segment = node_segment.left_half();
+                node_id = node.id_left;
+            } else {
+                node_segment = node_segment.right_half();
+                node_id = node.id_right;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::li_chao_tree::{DynamicLiChaoTree, LiChaoTree, Line};
+
+    // Lines over [0, 15], whose root splits into [0, 7] and [8, 15]: a and b cross at x = 3,
+    // in the left half, b and c at x = 12 and a and e at x = 13, in the right half.
+    fn segments() -> Vec<(Line, i64, i64)> {
+        vec![
+            // a: y = x
+            (Line::new(1, 0), 0, 15),
+            // b: y = 6 - x
+            (Line::new(-1, 6), 0, 15),
+            // d: y = -1
+            (Line::new(0, -1), 2, 5),
+            // c: y = 3x - 42
+            (Line::new(3, -42), 8, 15),
+            // e: y = 52 - 3x
+            (Line::new(-3, 52), 9, 15),
+        ]
+    }
+
+    // the minimum is a, then d, b and c, and b again after x = 12
+    const MIN_VALUES: [i128; 16] = [
+        0, 1, -1, -1, -1, -1, 0, -1, -18, -15, -12, -9, -6, -7, -8, -9,
+    ];
+    // the maximum is b up to x = 3, then a, e from x = 9 and a again after x = 13
+    const MAX_VALUES: [i128; 16] = [6, 5, 4, 3, 4, 5, 6, 7, 8, 25, 22, 19, 16, 13, 14, 15];
+
+    #[test]
+    fn test_min_of_lines() {
+        let mut tree = LiChaoTree::new_min(-10, 10);
+        assert_eq!(None, tree.query(0));
+        tree.add_line(Line::new(1, 0));
+        tree.add_line(Line::new(-1, 0));
+        tree.add_line(Line::new(0, -3));
+        assert_eq!(Some(-10), tree.query(-10));
+        assert_eq!(Some(-3), tree.query(0));
+        assert_eq!(Some(-3), tree.query(3));
+        assert_eq!(Some(-4), tree.query(4));
+        assert_eq!(Some(-10), tree.query(10));
+    }
+
+    #[test]
+    fn test_max_of_lines() {
+        let mut tree = LiChaoTree::new_max(0, 100);
+        tree.add_line(Line::new(2, -50));
+        tree.add_line(Line::new(-1, 40));
+        tree.add_line(Line::new(0, 10));
+        assert_eq!(Some(40), tree.query(0));
+        assert_eq!(Some(10), tree.query(30));
+        assert_eq!(Some(150), tree.query(100));
+    }
+
+    #[test]
+    fn test_segments() {
+        let mut min_tree = LiChaoTree::new_min(0, 15);
+        let mut max_tree = LiChaoTree::new_max(0, 15);
+        for (line, x1, x2) in segments() {
+            min_tree.add_segment(line, x1, x2);
+            max_tree.add_segment(line, x1, x2);
+        }
+        for x in 0..16 {
+            assert_eq!(Some(MIN_VALUES[x as usize]), min_tree.query(x));
+            assert_eq!(Some(MAX_VALUES[x as usize]), max_tree.query(x));
+        }
+    }
+
+    #[test]
+    fn test_dynamic_segments() {
+        let mut min_tree = DynamicLiChaoTree::new_min(0, 15);
+        let mut max_tree = DynamicLiChaoTree::new_max(0, 15);
+        for (line, x1, x2) in segments() {
+            min_tree.add_segment(line, x1, x2);
+            max_tree.add_segment(line, x1, x2);
+        }
+        for x in 0..16 {
+            assert_eq!(Some(MIN_VALUES[x as usize]), min_tree.query(x));
+            assert_eq!(Some(MAX_VALUES[x as usize]), max_tree.query(x));
+        }
+    }
+
+    #[test]
+    fn test_dynamic_wide_domain() {
+        let mut tree = DynamicLiChaoTree::new_max(i64::MIN, i64::MAX);
+        assert_eq!(None, tree.query(0));
+        tree.add_line(Line::new(i64::MAX, 0));
+        tree.add_line(Line::new(-1, 5));
+        tree.add_segment(
+            Line::new(0, i64::MAX),
+            -1_000_000_000_000,
+            1_000_000_000_000,
+        );
+        assert_eq!(
+            Some(i64::MAX as i128 * i64::MAX as i128),
+            tree.query(i64::MAX)
+        );
+        assert_eq!(Some(-(i64::MIN as i128) + 5), tree.query(i64::MIN));
+        assert_eq!(Some(i64::MAX as i128), tree.query(-1_000_000_000_000));
+        assert_eq!(Some(1_000_000_000_006), tree.query(-1_000_000_000_001));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_query_out_of_bounds() {
+        LiChaoTree::new_min(0, 9).query(10);
+    }
+}
@@ -1,7 +1,41 @@
 #![allow(unused)]
 
+mod affine_segment_tree;
+mod assign_add_segment_tree;
+mod beats_segment_tree;
+mod bottom_up_segment_tree;
+mod dynamic_segment_tree;
+mod error;
 mod generic_segment_tree;
 mod integer_segment_tree;
+mod lazy_segment_tree;
+mod li_chao_tree;
+mod max_subarray_segment_tree;
+mod mod_int;
+mod ordered_multiset;
+mod persistent_segment_tree;
+mod range;
+mod segment_tree_2d;
+mod serialization;
+mod trace;
+mod wavelet_matrix;
 
+pub use affine_segment_tree::AffineSegmentTree;
+pub use assign_add_segment_tree::AssignAddSegmentTree;
+pub use beats_segment_tree::SegmentTreeBeats;
+pub use bottom_up_segment_tree::BottomUpSegmentTree;
+pub use dynamic_segment_tree::DynamicSegmentTree;
+pub use error::SegmentTreeError;
+pub use generic_segment_tree::{Monoid, SegmentTree};
 pub use integer_segment_tree::IntegerSegmentTree;
-
+pub use lazy_segment_tree::{LazyAction, LazySegmentTree, Update, Value};
+pub use li_chao_tree::{DynamicLiChaoTree, LiChaoTree, Line};
+pub use max_subarray_segment_tree::{MaxSubarraySegmentTree, Subarray};
+pub use mod_int::ModInt;
+pub use ordered_multiset::{DynamicOrderedMultiset, OrderedMultiset};
+pub use persistent_segment_tree::{PersistentSegmentTree, RangeKthSmallest, Version};
+pub use range::Range;
+pub use segment_tree_2d::SegmentTree2D;
+pub use serialization::Encode;
+pub use trace::{TraceEvent, TreeObserver};
+pub use wavelet_matrix::WaveletMatrix;
@@ -0,0 +1,208 @@
+use crate::error::check_range;
+use crate::generic_segment_tree::{Monoid, SegmentTree};
+
+/// A non-empty subarray `[start, end]` together with the sum of its elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subarray {
+    pub sum: i64,
+    pub start: usize,
+    pub end: usize,
+}
+
+// What a segment needs to know to be merged with its neighbours: its total, its best
+// prefix, its best suffix and its best subarray. The best subarray of the union of two
+// adjacent segments either lies in one of them or is a suffix of the left segment followed
+// by a prefix of the right one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SubarrayStats {
+    total: i64,
+    prefix: Subarray,
+    suffix: Subarray,
+    best: Subarray,
+}
+
+impl SubarrayStats {
+    fn single(i: usize, value: i64) -> Self {
+        let subarray = Subarray {
+            sum: value,
+            start: i,
+            end: i,
+        };
+        Self {
+            total: value,
+            prefix: subarray,
+            suffix: subarray,
+            best: subarray,
+        }
+    }
+
+    fn merge(left: &SubarrayStats, right: &SubarrayStats) -> Self {
+        let whole_left_then_prefix = Subarray {
+            sum: left.total + right.prefix.sum,
+            start: left.prefix.start,
+            end: right.prefix.end,
+        };
+        let suffix_then_whole_right = Subarray {
+            sum: left.suffix.sum + right.total,
+            start: left.suffix.start,
+            end: right.suffix.end,
+        };
+        let crossing = Subarray {
+            sum: left.suffix.sum + right.prefix.sum,
+            start: left.suffix.start,
+            end: right.prefix.end,
+        };
+        // on ties the leftmost candidate wins
+        let best_of = |a: Subarray, b: Subarray| if b.sum > a.sum { b } else { a };
+        Self {
+            total: left.total + right.total,
+            prefix: best_of(left.prefix, whole_left_then_prefix),
+            suffix: best_of(suffix_then_whole_right, right.suffix),
+            best: best_of(best_of(left.best, crossing), right.best),
+        }
+    }
+}
+
+// `None` is the empty segment, the identity of the merge
+struct MaxSubarray;
+
+impl Monoid for MaxSubarray {
+    type Value = Option<SubarrayStats>;
+
+    fn identity() -> Option<SubarrayStats> {
+        None
+    }
+
+    fn combine(a: &Option<SubarrayStats>, b: &Option<SubarrayStats>) -> Option<SubarrayStats> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(SubarrayStats::merge(a, b)),
+            _ => a.or(*b),
+        }
+    }
+}
+
+/// A segment tree answering "maximum subarray sum within `[i, j]`" in O(log n), together
+/// with the position of a subarray reaching it, and supporting point updates in O(log n).
+pub struct MaxSubarraySegmentTree {
+    st: SegmentTree<MaxSubarray>,
+}
+
+impl MaxSubarraySegmentTree {
+    pub fn build(a: &[i64]) -> Self {
+        let leaves: Vec<Option<SubarrayStats>> = a
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| Some(SubarrayStats::single(i, v)))
+            .collect();
+        Self {
+            st: SegmentTree::build(&leaves),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.st.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.st.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> i64 {
+        self.st.get(i).map_or(0, |stats| stats.total)
+    }
+
+    /// Sets the element at position `i` to `value`.
+    pub fn update(&mut self, i: usize, value: i64) {
+        self.st.update(i, Some(SubarrayStats::single(i, value)));
+    }
+
+    /// Returns the non-empty subarray of `[i, j]` with the largest sum, or `None` if the
+    /// range is empty (`i > j`). On ties the subarray found first from the left is returned.
+    pub fn query(&self, i: usize, j: usize) -> Option<Subarray> {
+        check_range(i, j, self.len()).unwrap_or_else(|e| panic!("{e}"));
+        self.st.query(i, j).map(|stats| stats.best)
+    }
+
+    /// Returns the largest sum of a non-empty subarray of `[i, j]`, or `None` if the range
+    /// is empty (`i > j`).
+    pub fn max_sum(&self, i: usize, j: usize) -> Option<i64> {
+        self.query(i, j).map(|best| best.sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::max_subarray_segment_tree::{MaxSubarraySegmentTree, Subarray};
+
+    // every query must return the best sum and a subarray of the range reaching it
+    fn check_against_naive(tree: &MaxSubarraySegmentTree, a: &[i64]) {
+        for i in 0..a.len() {
+            for j in i..a.len() {
+                let best = (i..=j)
+                    .flat_map(|s| (s..=j).map(move |e| a[s..=e].iter().sum::<i64>()))
+                    .max()
+                    .unwrap();
+                let found = tree.query(i, j).unwrap();
+                assert_eq!(best, found.sum);
+                assert!(i <= found.start && found.start <= found.end && found.end <= j);
+                assert_eq!(best, a[found.start..=found.end].iter().sum::<i64>());
+            }
+        }
+    }
+
+    #[test]
+    fn test_query() {
+        let tree = MaxSubarraySegmentTree::build(&[-2, 1, -3, 4, -1, 2, 1, -5, 4]);
+        assert_eq!(
+            Some(Subarray {
+                sum: 6,
+                start: 3,
+                end: 6
+            }),
+            tree.query(0, 8)
+        );
+        assert_eq!(Some(4), tree.max_sum(0, 4));
+        assert_eq!(Some(4), tree.max_sum(7, 8));
+        assert_eq!(Some(-3), tree.max_sum(2, 2));
+        assert_eq!(None, tree.query(5, 4));
+    }
+
+    #[test]
+    fn test_all_negative() {
+        let tree = MaxSubarraySegmentTree::build(&[-5, -2, -8, -1, -9]);
+        assert_eq!(
+            Some(Subarray {
+                sum: -1,
+                start: 3,
+                end: 3
+            }),
+            tree.query(0, 4)
+        );
+        assert_eq!(Some(-2), tree.max_sum(0, 2));
+    }
+
+    #[test]
+    fn test_update() {
+        let mut a = vec![5, 4, -1, 7, 8, -20, 3, 6];
+        let mut tree = MaxSubarraySegmentTree::build(&a);
+        assert_eq!(Some(23), tree.max_sum(0, 7));
+        for (i, value) in [(5, 0), (2, -30), (0, -1), (7, 40), (4, -8)] {
+            tree.update(i, value);
+            a[i] = value;
+            assert_eq!(value, tree.get(i));
+            check_against_naive(&tree, &a);
+        }
+    }
+
+    #[test]
+    fn test_against_naive() {
+        let a: Vec<i64> = (0..40).map(|x| (x * 37 % 23) - 11).collect();
+        check_against_naive(&MaxSubarraySegmentTree::build(&a), &a);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_query_out_of_bounds() {
+        MaxSubarraySegmentTree::build(&[1, 2, 3]).query(1, 3);
+    }
+}
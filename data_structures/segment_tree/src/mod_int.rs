@@ -0,0 +1,178 @@
+use crate::serialization::{Encode, invalid_data};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// An integer modulo the prime `P`, always kept reduced in `[0, P)`.
+///
+/// `P` must be smaller than 2^63, so that the sum of two reduced values fits in a `u64`.
+/// Products are computed on `u128` and never overflow, whatever the size of `P`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ModInt<const P: u64>(u64);
+
+impl<const P: u64> ModInt<P> {
+    pub fn new(value: u64) -> Self {
+        const { assert!(P > 1 && P < 1 << 63, "The modulus must be in [2, 2^63)") };
+        ModInt(value % P)
+    }
+
+    /// Reduces a possibly negative value, e.g. `-1` becomes `P - 1`.
+    pub fn from_i64(value: i64) -> Self {
+        Self::new(value.rem_euclid(P as i64) as u64)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    /// Computes `self^exp` by repeated squaring in O(log exp).
+    pub fn pow(&self, mut exp: u64) -> Self {
+        let mut base = *self;
+        let mut result = Self::new(1);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result *= base;
+            }
+            base *= base;
+            exp >>= 1;
+        }
+        result
+    }
+
+    /// Returns the multiplicative inverse by Fermat's little theorem, or `None` for zero.
+    pub fn inv(&self) -> Option<Self> {
+        if self.0 == 0 {
+            return None;
+        }
+        Some(self.pow(P - 2))
+    }
+}
+
+impl<const P: u64> From<u64> for ModInt<P> {
+    fn from(value: u64) -> Self {
+        Self::new(value)
+    }
+}
+
+// saved as its reduced value, which is checked when reading it back
+impl<const P: u64> Encode for ModInt<P> {
+    fn encode<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.0.encode(writer)
+    }
+
+    fn decode<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let value = u64::decode(reader)?;
+        if value >= P {
+            return Err(invalid_data(format!(
+                "Value {value} not reduced modulo {P}"
+            )));
+        }
+        Ok(ModInt(value))
+    }
+}
+
+impl<const P: u64> Display for ModInt<P> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<const P: u64> Add for ModInt<P> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        // both values are below P < 2^63, so the sum cannot overflow
+        let sum = self.0 + other.0;
+        ModInt(if sum >= P { sum - P } else { sum })
+    }
+}
+
+impl<const P: u64> Sub for ModInt<P> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        ModInt(if self.0 >= other.0 {
+            self.0 - other.0
+        } else {
+            self.0 + P - other.0
+        })
+    }
+}
+
+impl<const P: u64> Mul for ModInt<P> {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        ModInt((self.0 as u128 * other.0 as u128 % P as u128) as u64)
+    }
+}
+
+impl<const P: u64> Neg for ModInt<P> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(0) - self
+    }
+}
+
+impl<const P: u64> AddAssign for ModInt<P> {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl<const P: u64> SubAssign for ModInt<P> {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl<const P: u64> MulAssign for ModInt<P> {
+    fn mul_assign(&mut self, other: Self) {
+        *self = *self * other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mod_int::ModInt;
+
+    type Mint = ModInt<998_244_353>;
+    // the Mersenne prime 2^61 - 1, whose products overflow 64 bits
+    type BigMint = ModInt<{ (1 << 61) - 1 }>;
+
+    #[test]
+    fn test_arithmetic() {
+        let a = Mint::new(998_244_350);
+        let b = Mint::new(5);
+        assert_eq!(2, (a + b).value());
+        assert_eq!(8, (b - a).value());
+        assert_eq!(998_244_338, (a * b).value());
+        assert_eq!(998_244_348, (-b).value());
+        assert_eq!(Mint::new(998_244_352), Mint::from_i64(-1));
+        assert_eq!(Mint::new(0), Mint::new(998_244_353));
+    }
+
+    #[test]
+    fn test_large_modulus_multiplication() {
+        let p = (1u128 << 61) - 1;
+        let (x, y) = ((1u64 << 61) - 2, (1u64 << 60) + 12_345);
+        let expected = (x as u128 * y as u128 % p) as u64;
+        assert_eq!(expected, (BigMint::new(x) * BigMint::new(y)).value());
+        assert_eq!(1, (BigMint::new(x) * BigMint::new(x)).value());
+    }
+
+    #[test]
+    fn test_pow_and_inverse() {
+        assert_eq!(1024, Mint::new(2).pow(10).value());
+        assert_eq!(1, Mint::new(3).pow(998_244_352).value());
+        for x in [1, 2, 12_345, 998_244_352] {
+            let x = Mint::new(x);
+            assert_eq!(Mint::new(1), x * x.inv().unwrap());
+        }
+        assert_eq!(None, Mint::new(0).inv());
+        let x = BigMint::new(987_654_321_987);
+        assert_eq!(BigMint::new(1), x * x.inv().unwrap());
+    }
+}
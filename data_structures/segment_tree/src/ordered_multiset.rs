@@ -0,0 +1,318 @@
+use crate::dynamic_segment_tree::DynamicSegmentTree;
+use crate::generic_segment_tree::{Monoid, SegmentTree};
+use std::ops::{Bound, RangeBounds};
+
+struct Count;
+
+impl Monoid for Count {
+    type Value = usize;
+
+    fn identity() -> usize {
+        0
+    }
+
+    fn combine(a: &usize, b: &usize) -> usize {
+        a + b
+    }
+}
+
+/// A multiset over a universe of values known in advance, answering order statistics in
+/// O(log u), where u is the size of the universe.
+///
+/// The universe is sorted and deduplicated, so that each value is replaced by its index
+/// (coordinate compression), and a counting segment tree stores how many copies of each
+/// value the multiset holds. See [`DynamicOrderedMultiset`] when the values are not known
+/// in advance.
+pub struct OrderedMultiset<T: Ord> {
+    universe: Vec<T>,
+    counts: SegmentTree<Count>,
+    len: usize,
+}
+
+impl<T: Ord + Clone> OrderedMultiset<T> {
+    /// Creates an empty multiset that can hold the values of `universe`.
+    pub fn new(universe: impl IntoIterator<Item = T>) -> Self {
+        let mut universe: Vec<T> = universe.into_iter().collect();
+        universe.sort_unstable();
+        universe.dedup();
+        Self {
+            counts: SegmentTree::build_empty(universe.len()),
+            universe,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn index(&self, x: &T) -> usize {
+        self.universe
+            .binary_search(x)
+            .unwrap_or_else(|_| panic!("Value not in the universe"))
+    }
+
+    /// Adds `multiplicity` copies of `x`.
+    ///
+    /// # Panics
+    /// if `x` is not in the universe.
+    pub fn insert(&mut self, x: &T, multiplicity: usize) {
+        let i = self.index(x);
+        self.counts.update(i, self.counts.get(i) + multiplicity);
+        self.len += multiplicity;
+    }
+
+    /// Removes up to `multiplicity` copies of `x`, returning how many were removed.
+    pub fn remove(&mut self, x: &T, multiplicity: usize) -> usize {
+        let Ok(i) = self.universe.binary_search(x) else {
+            return 0;
+        };
+        let count = self.counts.get(i);
+        let removed = multiplicity.min(count);
+        self.counts.update(i, count - removed);
+        self.len -= removed;
+        removed
+    }
+
+    /// Returns how many copies of `x` the multiset holds.
+    pub fn count(&self, x: &T) -> usize {
+        self.universe
+            .binary_search(x)
+            .map_or(0, |i| self.counts.get(i))
+    }
+
+    /// Returns the number of elements smaller than `x`.
+    pub fn rank(&self, x: &T) -> usize {
+        match self.universe.partition_point(|v| v < x) {
+            0 => 0,
+            bound => self.counts.query(0, bound - 1),
+        }
+    }
+
+    /// Returns the element at position `k` (starting from 0) of the multiset in sorted
+    /// order, so that `kth(rank(x)) == x` for any `x` in it, or `None` if `k >= len()`.
+    pub fn kth(&self, k: usize) -> Option<T> {
+        if k >= self.len {
+            return None;
+        }
+        // the first index whose prefix count exceeds k
+        let i = self.counts.max_right(0, |&count| count <= k);
+        Some(self.universe[i].clone())
+    }
+
+    /// Returns the number of elements in `range`.
+    pub fn count_in<R: RangeBounds<T>>(&self, range: R) -> usize {
+        let start = match range.start_bound() {
+            Bound::Included(x) => self.universe.partition_point(|v| v < x),
+            Bound::Excluded(x) => self.universe.partition_point(|v| v <= x),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(x) => self.universe.partition_point(|v| v <= x),
+            Bound::Excluded(x) => self.universe.partition_point(|v| v < x),
+            Bound::Unbounded => self.universe.len(),
+        };
+        if start >= end {
+            return 0;
+        }
+        self.counts.query(start, end - 1)
+    }
+
+    /// Returns the largest element smaller than `x`, if any.
+    pub fn predecessor(&self, x: &T) -> Option<T> {
+        self.rank(x).checked_sub(1).and_then(|k| self.kth(k))
+    }
+
+    /// Returns the smallest element greater than `x`, if any.
+    pub fn successor(&self, x: &T) -> Option<T> {
+        self.kth(self.rank(x) + self.count(x))
+    }
+}
+
+/// A multiset of i64 values answering order statistics in O(log U), where U = 2^64 is the
+/// size of the i64 range.
+///
+/// The values need no compression: a [`DynamicSegmentTree`] over the whole i64 range counts
+/// the copies of each value, allocating O(log U) nodes for each value inserted, so values
+/// can arrive online.
+#[derive(Default)]
+pub struct DynamicOrderedMultiset {
+    counts: DynamicSegmentTree,
+    len: usize,
+}
+
+impl DynamicOrderedMultiset {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds `multiplicity` copies of `x`.
+    pub fn insert(&mut self, x: i64, multiplicity: usize) {
+        self.counts.add(x, multiplicity as i64);
+        self.len += multiplicity;
+    }
+
+    /// Removes up to `multiplicity` copies of `x`, returning how many were removed.
+    pub fn remove(&mut self, x: i64, multiplicity: usize) -> usize {
+        let removed = multiplicity.min(self.count(x));
+        self.counts.add(x, -(removed as i64));
+        self.len -= removed;
+        removed
+    }
+
+    /// Returns how many copies of `x` the multiset holds.
+    pub fn count(&self, x: i64) -> usize {
+        self.counts.query(x, x) as usize
+    }
+
+    /// Returns the number of elements smaller than `x`.
+    pub fn rank(&self, x: i64) -> usize {
+        match x.checked_sub(1) {
+            Some(below) => self.counts.query(i64::MIN, below) as usize,
+            None => 0,
+        }
+    }
+
+    /// Returns the element at position `k` (starting from 0) of the multiset in sorted
+    /// order, so that `kth(rank(x)) == x` for any `x` in it, or `None` if `k >= len()`.
+    pub fn kth(&self, k: usize) -> Option<i64> {
+        if k >= self.len {
+            return None;
+        }
+        self.counts.lower_bound(k as i128 + 1)
+    }
+
+    /// Returns the number of elements in `range`.
+    pub fn count_in<R: RangeBounds<i64>>(&self, range: R) -> usize {
+        let start = match range.start_bound() {
+            Bound::Included(&x) => x,
+            Bound::Excluded(&x) => match x.checked_add(1) {
+                Some(start) => start,
+                None => return 0,
+            },
+            Bound::Unbounded => i64::MIN,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&x) => x,
+            Bound::Excluded(&x) => match x.checked_sub(1) {
+                Some(end) => end,
+                None => return 0,
+            },
+            Bound::Unbounded => i64::MAX,
+        };
+        self.counts.query(start, end) as usize
+    }
+
+    /// Returns the largest element smaller than `x`, if any.
+    pub fn predecessor(&self, x: i64) -> Option<i64> {
+        self.rank(x).checked_sub(1).and_then(|k| self.kth(k))
+    }
+
+    /// Returns the smallest element greater than `x`, if any.
+    pub fn successor(&self, x: i64) -> Option<i64> {
+        self.kth(self.rank(x) + self.count(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ordered_multiset::{DynamicOrderedMultiset, OrderedMultiset};
+    use std::ops::Bound;
+
+    #[test]
+    fn test_order_statistics() {
+        let mut set = OrderedMultiset::new(["pear", "apple", "fig", "kiwi", "apple"]);
+        set.insert(&"fig", 2);
+        set.insert(&"apple", 1);
+        set.insert(&"pear", 3);
+        // apple, fig, fig, pear, pear, pear
+        assert_eq!(6, set.len());
+        assert_eq!(3, set.rank(&"kiwi"));
+        assert_eq!(1, set.rank(&"fig"));
+        assert_eq!(Some("fig"), set.kth(2));
+        assert_eq!(Some("pear"), set.kth(5));
+        assert_eq!(None, set.kth(6));
+        assert_eq!(2, set.count_in("b".."kiwi"));
+        assert_eq!(5, set.count_in("fig"..));
+        assert_eq!(0, set.count_in("kiwi"..="kiwi"));
+        assert_eq!(Some("fig"), set.predecessor(&"kiwi"));
+        assert_eq!(Some("pear"), set.successor(&"fig"));
+        assert_eq!(None, set.successor(&"pear"));
+        assert_eq!(None, set.predecessor(&"apple"));
+
+        assert_eq!(2, set.remove(&"pear", 2));
+        assert_eq!(1, set.remove(&"pear", 2));
+        assert_eq!(0, set.remove(&"banana", 1));
+        assert_eq!(3, set.len());
+        assert_eq!(None, set.successor(&"fig"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Value not in the universe")]
+    fn test_insert_outside_universe() {
+        OrderedMultiset::new([1, 2, 3]).insert(&4, 1);
+    }
+
+    #[test]
+    fn test_dynamic_extreme_values() {
+        let mut set = DynamicOrderedMultiset::new();
+        set.insert(i64::MIN, 1);
+        set.insert(i64::MAX, 2);
+        set.insert(0, 1);
+        assert_eq!(Some(i64::MIN), set.kth(0));
+        assert_eq!(Some(i64::MAX), set.kth(3));
+        assert_eq!(0, set.rank(i64::MIN));
+        assert_eq!(2, set.rank(i64::MAX));
+        assert_eq!(Some(0), set.predecessor(i64::MAX));
+        assert_eq!(None, set.predecessor(i64::MIN));
+        assert_eq!(Some(i64::MAX), set.successor(0));
+        assert_eq!(0, set.count_in(..i64::MIN));
+        assert_eq!(
+            0,
+            set.count_in((Bound::Excluded(i64::MAX), Bound::Unbounded))
+        );
+        assert_eq!(4, set.count_in(..));
+    }
+
+    #[test]
+    fn test_dynamic_order_statistics() {
+        let mut set = DynamicOrderedMultiset::new();
+        set.insert(-5, 2);
+        set.insert(3, 1);
+        set.insert(10, 3);
+        set.insert(-100, 1);
+        // -100, -5, -5, 3, 10, 10, 10
+        assert_eq!(7, set.len());
+        assert_eq!(3, set.rank(3));
+        assert_eq!(3, set.rank(0));
+        assert_eq!(1, set.rank(-5));
+        assert_eq!(Some(-5), set.kth(2));
+        assert_eq!(Some(3), set.kth(3));
+        assert_eq!(Some(10), set.kth(6));
+        assert_eq!(None, set.kth(7));
+        assert_eq!(3, set.count_in(-5..=3));
+        assert_eq!(1, set.count_in(-4..10));
+        assert_eq!(Some(-5), set.predecessor(3));
+        assert_eq!(Some(3), set.successor(-5));
+        assert_eq!(None, set.successor(10));
+
+        assert_eq!(2, set.remove(-5, 5));
+        assert_eq!(0, set.remove(7, 1));
+        assert_eq!(5, set.len());
+        assert_eq!(0, set.count(-5));
+        assert_eq!(Some(3), set.kth(1));
+        assert_eq!(Some(-100), set.predecessor(3));
+    }
+}
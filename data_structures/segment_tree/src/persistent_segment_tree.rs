@@ -0,0 +1,515 @@
+use crate::error::{SegmentTreeError, check_index, check_range};
+use crate::range::Range;
+use crate::serialization::{
+    FORMAT_VERSION, TreeKind, invalid_data, read_header, read_i64, read_u64, write_header,
+    write_i64, write_u64,
+};
+use std::fmt::Write;
+use std::io;
+
+/// Handle to one of the states of a [`PersistentSegmentTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version(usize);
+
+struct Node {
+    sum: i64,
+    id_left: usize,
+    id_right: usize,
+}
+
+// The node with id NULL_NODE is the root of the all-zero tree: its children are itself,
+// so an all-zero tree of any size is represented by a single node and is built in O(1).
+const NULL_NODE: usize = 0;
+
+/// A sum segment tree that keeps every past version of the array.
+///
+/// Nodes are stored in an arena and never modified once created: a point update copies
+/// only the O(log n) nodes on the path from the root to the updated leaf and shares all
+/// the others with the version it starts from.
+pub struct PersistentSegmentTree {
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+    num_leaf: usize,
+}
+
+impl PersistentSegmentTree {
+    /// Creates a tree whose first version holds `num_elems` zeros.
+    pub fn build_empty(num_elems: usize) -> Self {
+        Self {
+            nodes: vec![Node {
+                sum: 0,
+                id_left: NULL_NODE,
+                id_right: NULL_NODE,
+            }],
+            roots: vec![NULL_NODE],
+            num_leaf: num_elems,
+        }
+    }
+
+    /// Creates a tree whose first version holds the values in `a`.
+    pub fn build(a: &[i64]) -> Self {
+        let mut inst = Self::build_empty(a.len());
+        if !a.is_empty() {
+            let root = inst.build_rec(a, Range::new(0, a.len() - 1));
+            inst.roots[0] = root;
+        }
+        inst
+    }
+
+    fn build_rec(&mut self, a: &[i64], node_segment: Range) -> usize {
+        if node_segment.is_single_point() {
+            return self.new_node(a[node_segment.start], NULL_NODE, NULL_NODE);
+        }
+        let id_left = self.build_rec(a, node_segment.left_half());
+        let id_right = self.build_rec(a, node_segment.right_half());
+        self.new_node(
+            self.nodes[id_left].sum + self.nodes[id_right].sum,
+            id_left,
+            id_right,
+        )
+    }
+
+    fn new_node(&mut self, sum: i64, id_left: usize, id_right: usize) -> usize {
+        self.nodes.push(Node {
+            sum,
+            id_left,
+            id_right,
+        });
+        self.nodes.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_leaf
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_leaf == 0
+    }
+
+    /// The version the tree has been built with.
+    pub fn initial(&self) -> Version {
+        Version(0)
+    }
+
+    /// The most recently created version.
+    pub fn latest(&self) -> Version {
+        Version(self.roots.len() - 1)
+    }
+
+    pub fn num_versions(&self) -> usize {
+        self.roots.len()
+    }
+
+    /// Creates a new version, equal to `version` except for position `i` that becomes `value`.
+    pub fn update(&mut self, version: Version, i: usize, value: i64) -> Version {
+        let old = self.get(version, i);
+        self.add(version, i, value - old)
+    }
+
+    /// Creates a new version, equal to `version` except for position `i` that is increased by `delta`.
+    pub fn add(&mut self, version: Version, i: usize, delta: i64) -> Version {
+        self.try_add(version, i, delta)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `add`, but fails without creating a version if there is no position `i`.
+    pub fn try_add(
+        &mut self,
+        version: Version,
+        i: usize,
+        delta: i64,
+    ) -> Result<Version, SegmentTreeError> {
+        check_index(i, self.num_leaf)?;
+        let root = self.add_rec(
+            self.roots[version.0],
+            Range::new(0, self.num_leaf - 1),
+            i,
+            delta,
+        );
+        self.roots.push(root);
+        Ok(self.latest())
+    }
+
+    // returns the id of the copy of `node_id` that includes the update
+    fn add_rec(&mut self, node_id: usize, node_segment: Range, i: usize, delta: i64) -> usize {
+        let Node {
+            sum,
+            id_left,
+            id_right,
+        } = self.nodes[node_id];
+        if node_segment.is_single_point() {
+            return self.new_node(sum + delta, NULL_NODE, NULL_NODE);
+        }
+        // only the child containing i is copied, the other one is shared with the old version
+        if i <= node_segment.middle() {
+            let new_left = self.add_rec(id_left, node_segment.left_half(), i, delta);
+            self.new_node(sum + delta, new_left, id_right)
+        } else {
+            let new_right = self.add_rec(id_right, node_segment.right_half(), i, delta);
+            self.new_node(sum + delta, id_left, new_right)
+        }
+    }
+
+    pub fn get(&self, version: Version, i: usize) -> i64 {
+        check_index(i, self.num_leaf).unwrap_or_else(|e| panic!("{e}"));
+        self.query(version, i, i)
+    }
+
+    /// Returns the sum of the positions in `[i, j]` as they were in `version`.
+    pub fn query(&self, version: Version, i: usize, j: usize) -> i64 {
+        self.try_query(version, i, j)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Like `query`, returning 0 if the range is empty (`i > j`) or an error if it ends
+    /// past the last element.
+    pub fn try_query(&self, version: Version, i: usize, j: usize) -> Result<i64, SegmentTreeError> {
+        check_range(i, j, self.num_leaf)?;
+        if i > j {
+            return Ok(0);
+        }
+        Ok(self.query_rec(
+            self.roots[version.0],
+            Range::new(i, j),
+            Range::new(0, self.num_leaf - 1),
+        ))
+    }
+
+    fn query_rec(&self, node_id: usize, query_range: Range, node_segment: Range) -> i64 {
+        if query_range.no_overlap(node_segment) || node_id == NULL_NODE {
+            return 0;
+        }
+        let node = &self.nodes[node_id];
+        if query_range.contains(node_segment) {
+            return node.sum;
+        }
+        self.query_rec(node.id_left, query_range, node_segment.left_half())
+            + self.query_rec(node.id_right, query_range, node_segment.right_half())
+    }
+
+    /// Writes every version in the little-endian binary format shared by the trees of this
+    /// crate: a versioned header, the number of nodes followed by the sum and the ids of the
+    /// children of each node, by increasing id, and the number of versions followed by the
+    /// id of the root of each version.
+    ///
+    /// The nodes shared by several versions are written once. Every number is written
+    /// separately, so `writer` should be buffered.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, TreeKind::Persistent, self.num_leaf)?;
+        write_u64(writer, self.nodes.len() as u64)?;
+        for node in &self.nodes {
+            write_i64(writer, node.sum)?;
+            write_u64(writer, node.id_left as u64)?;
+            write_u64(writer, node.id_right as u64)?;
+        }
+        write_u64(writer, self.roots.len() as u64)?;
+        for &root in &self.roots {
+            write_u64(writer, root as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tree written by `save`, with the same versions.
+    ///
+    /// A node is always created after its children, so every id is checked to refer to an
+    /// earlier node: a corrupt file cannot make the tree loop or point outside the arena.
+    pub fn load<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let num_leaf = read_header(reader, TreeKind::Persistent, size_of::<Node>())?;
+        // the arena grows as the nodes are read, so a corrupt count fails on the missing
+        // bytes instead of allocating
+        let num_nodes = read_u64(reader)?;
+        let mut nodes = Vec::new();
+        while (nodes.len() as u64) < num_nodes {
+            let node = Node {
+                sum: read_i64(reader)?,
+                id_left: read_id(reader, nodes.len())?,
+                id_right: read_id(reader, nodes.len())?,
+            };
+            nodes.push(node);
+        }
+        match nodes.first() {
+            Some(Node {
+                sum: 0,
+                id_left: NULL_NODE,
+                id_right: NULL_NODE,
+            }) => {}
+            _ => return Err(invalid_data("Missing all-zero node".to_string())),
+        }
+        let num_roots = read_u64(reader)?;
+        if num_roots == 0 {
+            return Err(invalid_data("No version saved".to_string()));
+        }
+        let mut roots = Vec::new();
+        while (roots.len() as u64) < num_roots {
+            roots.push(read_id(reader, nodes.len())?);
+        }
+        Ok(Self {
+            nodes,
+            roots,
+            num_leaf,
+        })
+    }
+
+    /// Describes the tree in a human-readable form: the root of each version, then one
+    /// node per line by increasing id.
+    pub fn dump_text(&self) -> String {
+        let mut text = format!(
+            "PersistentSegmentTree (format version {FORMAT_VERSION})\nleaves: {}\nroots: {:?}\n",
+            self.num_leaf, self.roots
+        );
+        for (id, node) in self.nodes.iter().enumerate() {
+            // writing to a String never fails
+            writeln!(
+                text,
+                "node {id}: sum {}, children {} {}",
+                node.sum, node.id_left, node.id_right
+            )
+            .unwrap();
+        }
+        text
+    }
+}
+
+// reads the id of a node, that must be smaller than `bound`, except for the all-zero node
+// that is its own child
+fn read_id<R: io::Read>(reader: &mut R, bound: usize) -> io::Result<usize> {
+    let id = read_u64(reader)?;
+    match usize::try_from(id) {
+        Ok(id) if id < bound.max(1) => Ok(id),
+        _ => Err(invalid_data(format!("Invalid node id {id}"))),
+    }
+}
+
+/// Answers "k-th smallest value in a[l..=r]" on a static array in O(log n).
+///
+/// Values are replaced by their rank among the sorted distinct values and version `p` of a
+/// persistent counting tree over the ranks holds the frequencies of the prefix `a[0..p]`.
+/// The frequencies of `a[l..=r]` are the difference between versions `r + 1` and `l`, so the
+/// k-th smallest is found by descending the two versions at the same time.
+pub struct RangeKthSmallest {
+    counts: PersistentSegmentTree,
+    // prefix_versions[p] is the version holding the frequencies of a[0..p]
+    prefix_versions: Vec<Version>,
+    sorted_values: Vec<i64>,
+}
+
+impl RangeKthSmallest {
+    pub fn build(a: &[i64]) -> Self {
+        let mut sorted_values = a.to_vec();
+        sorted_values.sort_unstable();
+        sorted_values.dedup();
+        let mut counts = PersistentSegmentTree::build_empty(sorted_values.len());
+        let mut prefix_versions = Vec::with_capacity(a.len() + 1);
+        prefix_versions.push(counts.initial());
+        for value in a {
+            let rank = sorted_values.binary_search(value).unwrap();
+            let version = counts.add(counts.latest(), rank, 1);
+            prefix_versions.push(version);
+        }
+        Self {
+            counts,
+            prefix_versions,
+            sorted_values,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.prefix_versions.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the `k`-th smallest value (starting from `k = 1`) among the positions in
+    /// `[l, r]`, or `None` if the range holds less than `k` values.
+    ///
+    /// # Panics
+    /// if the range is not empty and ends past the last element.
+    pub fn kth_smallest(&self, l: usize, r: usize, k: usize) -> Option<i64> {
+        check_range(l, r, self.len()).unwrap_or_else(|e| panic!("{e}"));
+        if l > r || k == 0 || k > r - l + 1 {
+            return None;
+        }
+        let nodes = &self.counts.nodes;
+        let mut older = self.counts.roots[self.prefix_versions[l].0];
+        let mut newer = self.counts.roots[self.prefix_versions[r + 1].0];
+        let mut node_segment = Range::new(0, self.sorted_values.len() - 1);
+        let mut k = k as i64;
+        while !node_segment.is_single_point() {
+            // number of values in a[l..=r] whose rank falls in the left half
+            let in_left = nodes[nodes[newer].id_left].sum - nodes[nodes[older].id_left].sum;
+            if k <= in_left {
+                older = nodes[older].id_left;
+                newer = nodes[newer].id_left;
+                node_segment = node_segment.left_half();
+            } else {
+                k -= in_left;
+                older = nodes[older].id_right;
+                newer = nodes[newer].id_right;
+                node_segment = node_segment.right_half();
+            }
+        }
+        Some(self.sorted_values[node_segment.start])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::SegmentTreeError;
+    use crate::persistent_segment_tree::{PersistentSegmentTree, RangeKthSmallest};
+
+    #[test]
+    fn test_build_and_query() {
+        let tree = PersistentSegmentTree::build(&[3, 4, 5, 3, 8, 12, -4]);
+        let v0 = tree.initial();
+        assert_eq!(31, tree.query(v0, 0, 6));
+        assert_eq!(9, tree.query(v0, 1, 2));
+        assert_eq!(16, tree.query(v0, 4, 6));
+        assert_eq!(8, tree.get(v0, 4));
+    }
+
+    #[test]
+    fn test_old_versions_are_preserved() {
+        let mut tree = PersistentSegmentTree::build(&[1, 2, 3, 4, 5]);
+        let v0 = tree.initial();
+        let v1 = tree.update(v0, 2, 10);
+        let v2 = tree.add(v1, 0, -1);
+        assert_eq!(15, tree.query(v0, 0, 4));
+        assert_eq!(22, tree.query(v1, 0, 4));
+        assert_eq!(21, tree.query(v2, 0, 4));
+        assert_eq!(3, tree.get(v0, 2));
+        assert_eq!(10, tree.get(v2, 2));
+        assert_eq!(1, tree.get(v1, 0));
+        assert_eq!(0, tree.get(v2, 0));
+        assert_eq!(3, tree.num_versions());
+    }
+
+    #[test]
+    fn test_branching_versions() {
+        let mut tree = PersistentSegmentTree::build_empty(8);
+        let v0 = tree.initial();
+        let left = tree.update(v0, 1, 5);
+        // a second branch starting again from the initial version
+        let right = tree.update(v0, 6, 7);
+        assert_eq!(5, tree.query(left, 0, 7));
+        assert_eq!(7, tree.query(right, 0, 7));
+        assert_eq!(0, tree.query(v0, 0, 7));
+        assert_eq!(right, tree.latest());
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut tree = PersistentSegmentTree::build(&[1, 2, 3]);
+        let v0 = tree.initial();
+        assert_eq!(
+            Err(SegmentTreeError::IndexOutOfBounds { index: 3, len: 3 }),
+            tree.try_add(v0, 3, 1)
+        );
+        assert_eq!(1, tree.num_versions());
+        assert_eq!(
+            Err(SegmentTreeError::RangeOutOfBounds {
+                start: 2,
+                end: 3,
+                len: 3
+            }),
+            tree.try_query(v0, 2, 3)
+        );
+        assert_eq!(Ok(5), tree.try_query(v0, 1, 2));
+        assert_eq!(0, tree.query(v0, 2, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 out of bounds for 3 elements")]
+    fn test_update_out_of_bounds_panics() {
+        let mut tree = PersistentSegmentTree::build(&[1, 2, 3]);
+        tree.update(tree.initial(), 3, 7);
+    }
+
+    #[test]
+    fn test_path_copying_shares_nodes() {
+        let mut tree = PersistentSegmentTree::build(&[1; 16]);
+        let nodes_before = tree.nodes.len();
+        tree.add(tree.initial(), 5, 1);
+        // one new node per level: 16 leaves -> 5 levels
+        assert_eq!(nodes_before + 5, tree.nodes.len());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut original = PersistentSegmentTree::build(&[3, 1, 4, 1, 5]);
+        let v1 = original.add(original.initial(), 2, 10);
+        let v2 = original.update(original.initial(), 0, -3);
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+
+        let loaded = PersistentSegmentTree::load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        assert_eq!(3, loaded.num_versions());
+        assert_eq!(14, loaded.query(loaded.initial(), 0, 4));
+        assert_eq!(24, loaded.query(v1, 0, 4));
+        assert_eq!(8, loaded.query(v2, 0, 4));
+        assert_eq!(-3, loaded.get(v2, 0));
+
+        let mut truncated = bytes.clone();
+        truncated.pop();
+        assert!(PersistentSegmentTree::load(&mut truncated.as_slice()).is_err());
+        // after the header and the number of nodes, the left child of node 1 (the first
+        // leaf) is made to point to the node itself
+        let mut looping = bytes.clone();
+        looping[15 + 8 + 24 + 8] = 1;
+        assert!(PersistentSegmentTree::load(&mut looping.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_dump_text() {
+        let mut tree = PersistentSegmentTree::build(&[2, 5]);
+        tree.add(tree.initial(), 1, 1);
+        assert_eq!(
+            "PersistentSegmentTree (format version 1)\n\
+            leaves: 2\n\
+            roots: [3, 5]\n\
+            node 0: sum 0, children 0 0\n\
+            node 1: sum 2, children 0 0\n\
+            node 2: sum 5, children 0 0\n\
+            node 3: sum 7, children 1 2\n\
+            node 4: sum 6, children 0 0\n\
+            node 5: sum 8, children 1 4\n",
+            tree.dump_text()
+        );
+    }
+
+    #[test]
+    fn test_kth_smallest() {
+        let kth = RangeKthSmallest::build(&[5, 1, 4, 3, 2, 4, -7]);
+        assert_eq!(Some(1), kth.kth_smallest(0, 4, 1));
+        assert_eq!(Some(3), kth.kth_smallest(0, 4, 3));
+        assert_eq!(Some(5), kth.kth_smallest(0, 4, 5));
+        assert_eq!(Some(4), kth.kth_smallest(2, 5, 3));
+        assert_eq!(Some(4), kth.kth_smallest(2, 5, 4));
+        assert_eq!(Some(-7), kth.kth_smallest(0, 6, 1));
+        assert_eq!(Some(2), kth.kth_smallest(4, 4, 1));
+        assert_eq!(None, kth.kth_smallest(4, 4, 2));
+        assert_eq!(None, kth.kth_smallest(1, 3, 0));
+        assert_eq!(None, kth.kth_smallest(3, 1, 1));
+    }
+
+    #[test]
+    fn test_kth_smallest_empty_array() {
+        let kth = RangeKthSmallest::build(&[]);
+        assert!(kth.is_empty());
+        assert_eq!(None, kth.kth_smallest(1, 0, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Range [0, 0] out of bounds for 0 elements")]
+    fn test_kth_smallest_on_empty_array_panics() {
+        RangeKthSmallest::build(&[]).kth_smallest(0, 0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range [1, 3] out of bounds for 3 elements")]
+    fn test_kth_smallest_out_of_bounds() {
+        RangeKthSmallest::build(&[2, 1, 3]).kth_smallest(1, 3, 1);
+    }
+}
@@ -0,0 +1,102 @@
+use std::cmp::{max, min};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// A closed interval `[start, end]` of positions, such as the segment covered by a node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Display for Range {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "[{}..{}]", self.start, self.end)
+    }
+}
+
+impl Range {
+    pub fn new(start: usize, end: usize) -> Self {
+        Range { start, end }
+    }
+
+    pub fn contains(&self, other: Range) -> bool {
+        self.start <= other.start && self.end >= other.end
+    }
+
+    pub fn size(&self) -> usize {
+        self.end - self.start + 1
+    }
+
+    pub fn is_single_point(&self) -> bool {
+        self.size() == 1
+    }
+
+    pub fn intersect(&self, other: Range) -> Self {
+        Range::new(max(self.start, other.start), min(self.end, other.end))
+    }
+
+    pub fn no_overlap(&self, other: Range) -> bool {
+        self.end < other.start || other.end < self.start
+    }
+
+    pub fn middle(&self) -> usize {
+        self.start + ((self.end - self.start) / 2)
+    }
+
+    pub fn left_half(&self) -> Self {
+        Range::new(self.start, self.middle())
+    }
+
+    pub fn right_half(&self) -> Self {
+        Range::new(self.middle() + 1, self.end)
+    }
+}
+
+pub(crate) fn left_right_child_index(node_segment: Range, father_index: usize) -> (usize, usize) {
+    let middle = node_segment.middle();
+    // since the left subtree has num_leaf_left_subtree leaf it will have in total
+    // 2 * num_leaf_left_subtree - 1 nodes leaf included so the index of the right
+    // child will be the next after this nodes
+    let num_leaf_left_subtree = middle - node_segment.start + 1;
+    let left_child_index = father_index + 1;
+    let right_child_index = father_index + 2 * num_leaf_left_subtree;
+    (left_child_index, right_child_index)
+}
+
+/// Iterates the nodes of an implicit tree over `num_leaf` leaves that are leaves, from the
+/// leftmost to the rightmost one, keeping on a stack the O(log n) nodes still to visit.
+pub(crate) struct Leaves<'a, T> {
+    tree: &'a [T],
+    // the next node to visit is on top
+    stack: Vec<(Range, usize)>,
+}
+
+impl<'a, T> Leaves<'a, T> {
+    pub(crate) fn new(tree: &'a [T], num_leaf: usize) -> Self {
+        let stack = if num_leaf == 0 {
+            Vec::new()
+        } else {
+            vec![(Range::new(0, num_leaf - 1), 0)]
+        };
+        Leaves { tree, stack }
+    }
+}
+
+impl<'a, T> Iterator for Leaves<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        while let Some((node_segment, index)) = self.stack.pop() {
+            if node_segment.is_single_point() {
+                return Some(&self.tree[index]);
+            }
+            let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
+            self.stack
+                .push((node_segment.right_half(), right_child_index));
+            self.stack
+                .push((node_segment.left_half(), left_child_index));
+        }
+        None
+    }
+}
@@ -0,0 +1,538 @@
+use crate::error::check_num_nodes;
+use crate::range::{Range, left_right_child_index};
+use crate::serialization::{
+    FORMAT_VERSION, TreeKind, invalid_data, read_header, read_i32, read_u64, write_header,
+    write_i32, write_u64,
+};
+use std::fmt::Write;
+use std::io;
+
+/// A segment tree of segment trees answering rectangle queries on a grid.
+///
+/// The outer tree splits the rows with the same implicit layout as `IntegerSegmentTree` and
+/// each of its nodes stores an inner tree over the columns, again with the same layout, whose
+/// leaf `c` holds the merge of column `c` over the rows of the node. The merge function must be
+/// associative and commutative (sum, min, max, ...) since a rectangle is aggregated block by
+/// block.
+pub struct SegmentTree2D {
+    // tree[outer_index][inner_index]
+    tree: Vec<Vec<i32>>,
+    num_rows: usize,
+    num_cols: usize,
+    neutral_value: i32,
+    merge_values: fn(i32, i32) -> i32,
+}
+
+impl SegmentTree2D {
+    pub fn build_empty(
+        num_rows: usize,
+        num_cols: usize,
+        neutral_value: i32,
+        merge_values: fn(i32, i32) -> i32,
+    ) -> Self {
+        Self::build(
+            &vec![vec![0; num_cols]; num_rows],
+            neutral_value,
+            merge_values,
+        )
+    }
+
+    /// Builds the tree over `grid`, whose rows must all have the same length, in O(rows * cols).
+    pub fn build(grid: &[Vec<i32>], neutral_value: i32, merge_values: fn(i32, i32) -> i32) -> Self {
+        let num_rows = grid.len();
+        let num_cols = grid.first().map_or(0, |row| row.len());
+        assert!(
+            grid.iter().all(|row| row.len() == num_cols),
+            "All the rows must have the same length"
+        );
+        let mut inst = SegmentTree2D {
+            tree: vec![
+                vec![neutral_value; (2 * num_cols).saturating_sub(1)];
+                (2 * num_rows).saturating_sub(1)
+            ],
+            num_rows,
+            num_cols,
+            neutral_value,
+            merge_values,
+        };
+        if num_rows > 0 && num_cols > 0 {
+            inst.build_rec(grid, Range::new(0, num_rows - 1), 0);
+        }
+        inst
+    }
+
+    fn build_rec(&mut self, grid: &[Vec<i32>], row_segment: Range, index: usize) {
+        if row_segment.is_single_point() {
+            let row = &grid[row_segment.start];
+            let col_segment = Range::new(0, self.num_cols - 1);
+            Self::build_inner(
+                row,
+                &mut self.tree[index],
+                col_segment,
+                0,
+                self.merge_values,
+            );
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(row_segment, index);
+        self.build_rec(grid, row_segment.left_half(), left_child_index);
+        self.build_rec(grid, row_segment.right_half(), right_child_index);
+        // the children have the same inner layout, so every inner node of the father is the
+        // merge of the inner nodes in the same position of its children
+        for k in 0..self.tree[index].len() {
+            self.tree[index][k] = (self.merge_values)(
+                self.tree[left_child_index][k],
+                self.tree[right_child_index][k],
+            );
+        }
+    }
+
+    fn build_inner(
+        row: &[i32],
+        inner: &mut [i32],
+        col_segment: Range,
+        index: usize,
+        merge_values: fn(i32, i32) -> i32,
+    ) {
+        if col_segment.is_single_point() {
+            inner[index] = row[col_segment.start];
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(col_segment, index);
+        Self::build_inner(
+            row,
+            inner,
+            col_segment.left_half(),
+            left_child_index,
+            merge_values,
+        );
+        Self::build_inner(
+            row,
+            inner,
+            col_segment.right_half(),
+            right_child_index,
+            merge_values,
+        );
+        inner[index] = merge_values(inner[left_child_index], inner[right_child_index]);
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn num_cols(&self) -> usize {
+        self.num_cols
+    }
+
+    pub fn get(&self, r: usize, c: usize) -> i32 {
+        self.query(r, c, r, c)
+    }
+
+    /// Sets the cell in row `r` and column `c` to `v` in O(log(rows) * log(cols)).
+    pub fn update(&mut self, r: usize, c: usize, v: i32) {
+        assert!(
+            r < self.num_rows && c < self.num_cols,
+            "Cell ({r}, {c}) out of bounds"
+        );
+        self.update_rec(r, c, v, Range::new(0, self.num_rows - 1), 0);
+    }
+
+    fn update_rec(&mut self, r: usize, c: usize, v: i32, row_segment: Range, index: usize) {
+        let col_segment = Range::new(0, self.num_cols - 1);
+        if row_segment.is_single_point() {
+            Self::update_inner(
+                &mut self.tree[index],
+                col_segment,
+                0,
+                c,
+                v,
+                self.merge_values,
+            );
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(row_segment, index);
+        if r <= row_segment.middle() {
+            self.update_rec(r, c, v, row_segment.left_half(), left_child_index);
+        } else {
+            self.update_rec(r, c, v, row_segment.right_half(), right_child_index);
+        }
+        self.merge_inner_path(
+            index,
+            left_child_index,
+            right_child_index,
+            col_segment,
+            0,
+            c,
+        );
+    }
+
+    fn update_inner(
+        inner: &mut [i32],
+        col_segment: Range,
+        index: usize,
+        c: usize,
+        v: i32,
+        merge_values: fn(i32, i32) -> i32,
+    ) {
+        if col_segment.is_single_point() {
+            inner[index] = v;
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(col_segment, index);
+        if c <= col_segment.middle() {
+            Self::update_inner(
+                inner,
+                col_segment.left_half(),
+                left_child_index,
+                c,
+                v,
+                merge_values,
+            );
+        } else {
+            Self::update_inner(
+                inner,
+                col_segment.right_half(),
+                right_child_index,
+                c,
+                v,
+                merge_values,
+            );
+        }
+        inner[index] = merge_values(inner[left_child_index], inner[right_child_index]);
+    }
+
+    // after an update in column c only the inner nodes on the path to the leaf c have changed
+    // in the children of the outer node `index`, so only those are merged again
+    fn merge_inner_path(
+        &mut self,
+        index: usize,
+        left_child_index: usize,
+        right_child_index: usize,
+        col_segment: Range,
+        inner_index: usize,
+        c: usize,
+    ) {
+        self.tree[index][inner_index] = (self.merge_values)(
+            self.tree[left_child_index][inner_index],
+            self.tree[right_child_index][inner_index],
+        );
+        if col_segment.is_single_point() {
+            return;
+        }
+        let (inner_left, inner_right) = left_right_child_index(col_segment, inner_index);
+        if c <= col_segment.middle() {
+            let half = col_segment.left_half();
+            self.merge_inner_path(
+                index,
+                left_child_index,
+                right_child_index,
+                half,
+                inner_left,
+                c,
+            );
+        } else {
+            let half = col_segment.right_half();
+            self.merge_inner_path(
+                index,
+                left_child_index,
+                right_child_index,
+                half,
+                inner_right,
+                c,
+            );
+        }
+    }
+
+    /// Returns the merge of the cells in rows `[r1, r2]` and columns `[c1, c2]`.
+    pub fn query(&self, r1: usize, c1: usize, r2: usize, c2: usize) -> i32 {
+        if r1 > r2 || c1 > c2 {
+            return self.neutral_value;
+        }
+        assert!(
+            r2 < self.num_rows && c2 < self.num_cols,
+            "Cell ({r2}, {c2}) out of bounds"
+        );
+        self.rec_query(
+            Range::new(r1, r2),
+            Range::new(c1, c2),
+            Range::new(0, self.num_rows - 1),
+            0,
+        )
+    }
+
+    fn rec_query(
+        &self,
+        row_range: Range,
+        col_range: Range,
+        row_segment: Range,
+        index: usize,
+    ) -> i32 {
+        if row_segment.no_overlap(row_range) {
+            return self.neutral_value;
+        }
+        if row_range.contains(row_segment) {
+            let col_segment = Range::new(0, self.num_cols - 1);
+            return self.rec_query_inner(&self.tree[index], col_range, col_segment, 0);
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(row_segment, index);
+        let left = self.rec_query(
+            row_range,
+            col_range,
+            row_segment.left_half(),
+            left_child_index,
+        );
+        let right = self.rec_query(
+            row_range,
+            col_range,
+            row_segment.right_half(),
+            right_child_index,
+        );
+        (self.merge_values)(left, right)
+    }
+
+    fn rec_query_inner(
+        &self,
+        inner: &[i32],
+        col_range: Range,
+        col_segment: Range,
+        index: usize,
+    ) -> i32 {
+        if col_segment.no_overlap(col_range) {
+            return self.neutral_value;
+        }
+        if col_range.contains(col_segment) {
+            return inner[index];
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(col_segment, index);
+        let left =
+            self.rec_query_inner(inner, col_range, col_segment.left_half(), left_child_index);
+        let right = self.rec_query_inner(
+            inner,
+            col_range,
+            col_segment.right_half(),
+            right_child_index,
+        );
+        (self.merge_values)(left, right)
+    }
+
+    /// Writes the tree in the little-endian binary format shared by the trees of this
+    /// crate: a versioned header holding the number of rows, the number of columns, the
+    /// neutral value and then the inner trees of the outer nodes, all in implicit order. The
+    /// merge function is not saved.
+    ///
+    /// Every number is written separately, so `writer` should be buffered.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, TreeKind::TwoDimensional, self.num_rows)?;
+        write_u64(writer, self.num_cols as u64)?;
+        write_i32(writer, self.neutral_value)?;
+        for inner in &self.tree {
+            for &value in inner {
+                write_i32(writer, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a tree written by `save`, that will merge values with `merge_values`: it must
+    /// be the same merge function the tree has been saved with.
+    pub fn load<R: io::Read>(
+        reader: &mut R,
+        merge_values: fn(i32, i32) -> i32,
+    ) -> io::Result<Self> {
+        let num_rows = read_header(reader, TreeKind::TwoDimensional, size_of::<Vec<i32>>())?;
+        let num_cols = usize::try_from(read_u64(reader)?)
+            .map_err(|_| invalid_data("Number of columns too large".to_string()))?;
+        check_num_nodes(num_cols, size_of::<i32>()).map_err(|e| invalid_data(e.to_string()))?;
+        let neutral_value = read_i32(reader)?;
+        // the vectors grow as the values are read, so corrupt sizes fail on the missing
+        // bytes instead of allocating. Without columns no cell is ever read, and neither is
+        // any inner tree, so none is created
+        let mut tree = Vec::new();
+        if num_cols > 0 {
+            for _ in 0..(2 * num_rows).saturating_sub(1) {
+                let mut inner = Vec::new();
+                for _ in 0..2 * num_cols - 1 {
+                    inner.push(read_i32(reader)?);
+                }
+                tree.push(inner);
+            }
+        }
+        Ok(SegmentTree2D {
+            tree,
+            num_rows,
+            num_cols,
+            neutral_value,
+            merge_values,
+        })
+    }
+
+    /// Describes the tree in a human-readable form: one line per outer node in implicit
+    /// order, with the values of its inner tree in implicit order.
+    pub fn dump_text(&self) -> String {
+        let mut text = format!(
+            "SegmentTree2D (format version {FORMAT_VERSION})\nrows: {}\ncolumns: {}\nneutral value: {}\n",
+            self.num_rows, self.num_cols, self.neutral_value
+        );
+        if self.num_rows > 0 && self.num_cols > 0 {
+            self.dump_text_rec(Range::new(0, self.num_rows - 1), 0, &mut text);
+        }
+        text
+    }
+
+    // the implicit layout is a preorder visit, so the nodes are dumped by increasing index
+    fn dump_text_rec(&self, row_segment: Range, index: usize, text: &mut String) {
+        // writing to a String never fails
+        writeln!(text, "node {index} {row_segment}: {:?}", self.tree[index]).unwrap();
+        if row_segment.is_single_point() {
+            return;
+        }
+        let (left_child_index, right_child_index) = left_right_child_index(row_segment, index);
+        self.dump_text_rec(row_segment.left_half(), left_child_index, text);
+        self.dump_text_rec(row_segment.right_half(), right_child_index, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::{max, min};
+
+    use crate::segment_tree_2d::SegmentTree2D;
+
+    type Merge = fn(i32, i32) -> i32;
+
+    fn sum(a: i32, b: i32) -> i32 {
+        a + b
+    }
+
+    fn grid() -> Vec<Vec<i32>> {
+        vec![vec![1, 3, 1, 4], vec![1, 5, 1, -2], vec![4, 2, 1, 7]]
+    }
+
+    fn naive(
+        grid: &[Vec<i32>],
+        r1: usize,
+        c1: usize,
+        r2: usize,
+        c2: usize,
+        neutral: i32,
+        merge: Merge,
+    ) -> i32 {
+        grid[r1..=r2]
+            .iter()
+            .flat_map(|row| row[c1..=c2].iter())
+            .fold(neutral, |acc, &v| merge(acc, v))
+    }
+
+    #[test]
+    fn test_sum_query() {
+        let tree = SegmentTree2D::build(&grid(), 0, sum);
+        assert_eq!(28, tree.query(0, 0, 2, 3));
+        assert_eq!(10, tree.query(0, 1, 1, 2));
+        assert_eq!(5, tree.query(1, 3, 2, 3));
+        assert_eq!(5, tree.get(1, 1));
+        assert_eq!(0, tree.query(2, 1, 1, 1));
+    }
+
+    #[test]
+    fn test_min_max_query() {
+        let min_tree = SegmentTree2D::build(&grid(), i32::MAX, min);
+        let max_tree = SegmentTree2D::build(&grid(), i32::MIN, max);
+        assert_eq!(-2, min_tree.query(0, 0, 2, 3));
+        assert_eq!(1, min_tree.query(0, 0, 2, 2));
+        assert_eq!(7, max_tree.query(0, 0, 2, 3));
+        assert_eq!(5, max_tree.query(0, 0, 1, 2));
+        assert_eq!(4, max_tree.query(0, 3, 1, 3));
+    }
+
+    #[test]
+    fn test_update() {
+        let mut tree = SegmentTree2D::build_empty(3, 5, 0, sum);
+        tree.update(0, 0, 3);
+        tree.update(2, 4, 5);
+        tree.update(1, 2, -1);
+        assert_eq!(7, tree.query(0, 0, 2, 4));
+        assert_eq!(2, tree.query(0, 0, 1, 2));
+        tree.update(1, 2, 10);
+        assert_eq!(18, tree.query(0, 0, 2, 4));
+        assert_eq!(15, tree.query(1, 2, 2, 4));
+        assert_eq!(10, tree.get(1, 2));
+    }
+
+    #[test]
+    fn test_against_naive() {
+        let mut grid: Vec<Vec<i32>> = (0..7)
+            .map(|r| (0..6).map(|c| (r * 31 + c * 17) % 23 - 11).collect())
+            .collect();
+        let merges: [(i32, Merge); 3] = [(0, sum), (i32::MAX, min), (i32::MIN, max)];
+        for (neutral, merge) in merges {
+            let mut tree = SegmentTree2D::build(&grid, neutral, merge);
+            for step in 0..20 {
+                let (r, c) = (step * 5 % 7, step * 3 % 6);
+                let v = (step as i32 * 13) % 19 - 9;
+                grid[r][c] = v;
+                tree.update(r, c, v);
+                for r1 in 0..7 {
+                    for r2 in r1..7 {
+                        for c1 in 0..6 {
+                            for c2 in c1..6 {
+                                assert_eq!(
+                                    naive(&grid, r1, c1, r2, c2, neutral, merge),
+                                    tree.query(r1, c1, r2, c2)
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut original = SegmentTree2D::build(&grid(), 0, sum);
+        original.update(1, 3, 6);
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+
+        let loaded = SegmentTree2D::load(&mut bytes.as_slice(), sum).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        assert_eq!(36, loaded.query(0, 0, 2, 3));
+        assert_eq!(22, loaded.query(1, 1, 2, 3));
+        assert_eq!(6, loaded.get(1, 3));
+
+        bytes.pop();
+        assert!(SegmentTree2D::load(&mut bytes.as_slice(), sum).is_err());
+
+        // rows without any cell are kept, without reading anything for them
+        let mut no_columns = Vec::new();
+        SegmentTree2D::build(&[vec![], vec![]], 0, sum)
+            .save(&mut no_columns)
+            .unwrap();
+        let loaded = SegmentTree2D::load(&mut no_columns.as_slice(), sum).unwrap();
+        assert_eq!(2, loaded.num_rows());
+        assert_eq!(0, loaded.query(0, 1, 1, 0));
+    }
+
+    #[test]
+    fn test_dump_text() {
+        let tree = SegmentTree2D::build(&[vec![1, 2], vec![3, 4]], i32::MIN, max);
+        assert_eq!(
+            "SegmentTree2D (format version 1)\n\
+            rows: 2\n\
+            columns: 2\n\
+            neutral value: -2147483648\n\
+            node 0 [0..1]: [4, 3, 4]\n\
+            node 1 [0..0]: [2, 1, 2]\n\
+            node 2 [1..1]: [4, 3, 4]\n",
+            tree.dump_text()
+        );
+    }
+
+    #[test]
+    fn test_empty_grid() {
+        let tree = SegmentTree2D::build(&[], 0, sum);
+        assert_eq!(0, tree.num_rows());
+        assert_eq!(0, tree.num_cols());
+        assert_eq!(0, tree.query(1, 1, 0, 0));
+    }
+}
@@ -0,0 +1,227 @@
+use crate::error::check_num_nodes;
+use std::io;
+use std::io::{Read, Write};
+
+// Every saved tree starts with the same header, all integers being little-endian:
+// - the magic bytes "SGTR";
+// - the version of the format as a u16, bumped at every incompatible change;
+// - the kind of tree as a u8, so that a file is never loaded into the wrong type;
+// - the number of leaves as a u64 (the number of nodes for `DynamicSegmentTree`, whose
+//   universe may have 2^64 leaves).
+// The nodes follow, in the order of the tree's implicit layout, or by increasing id for the
+// trees that allocate their nodes one by one.
+const MAGIC: [u8; 4] = *b"SGTR";
+pub(crate) const FORMAT_VERSION: u16 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TreeKind {
+    Integer = 1,
+    BottomUp = 2,
+    Beats = 3,
+    Lazy = 4,
+    Generic = 5,
+    Persistent = 6,
+    Dynamic = 7,
+    TwoDimensional = 8,
+    Wavelet = 9,
+}
+
+/// A value that can be written in, and read back from, the binary format of the trees of
+/// this crate: the values of a [`SegmentTree`](crate::SegmentTree), and the values and the
+/// updates of a [`LazySegmentTree`](crate::LazySegmentTree), must implement it for the tree
+/// to be saved.
+pub trait Encode: Sized {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+
+    fn decode<R: Read>(reader: &mut R) -> io::Result<Self>;
+}
+
+macro_rules! impl_encode_for_integer {
+    ($($t:ty),*) => {$(
+        impl Encode for $t {
+            fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(&self.to_le_bytes())
+            }
+
+            fn decode<R: Read>(reader: &mut R) -> io::Result<Self> {
+                let mut bytes = [0; size_of::<$t>()];
+                reader.read_exact(&mut bytes)?;
+                Ok(<$t>::from_le_bytes(bytes))
+            }
+        }
+    )*};
+}
+
+impl_encode_for_integer!(i32, i64, i128, u32, u64);
+
+// a tag byte, 0 for None and 1 for Some, followed by the value if any
+impl<T: Encode> Encode for Option<T> {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            None => writer.write_all(&[0]),
+            Some(value) => {
+                writer.write_all(&[1])?;
+                value.encode(writer)
+            }
+        }
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut tag = [0; 1];
+        reader.read_exact(&mut tag)?;
+        match tag[0] {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(reader)?)),
+            tag => Err(invalid_data(format!("Invalid option tag {tag}"))),
+        }
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.0.encode(writer)?;
+        self.1.encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok((A::decode(reader)?, B::decode(reader)?))
+    }
+}
+
+pub(crate) fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn write_header<W: Write>(
+    writer: &mut W,
+    kind: TreeKind,
+    num_leaf: usize,
+) -> io::Result<()> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&[kind as u8])?;
+    writer.write_all(&(num_leaf as u64).to_le_bytes())
+}
+
+// checks the header and returns the number of leaves, whose nodes of `node_size` bytes
+// must fit in memory
+pub(crate) fn read_header<R: Read>(
+    reader: &mut R,
+    kind: TreeKind,
+    node_size: usize,
+) -> io::Result<usize> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid_data(format!(
+            "Not a segment tree: magic bytes {magic:?}"
+        )));
+    }
+    let mut version = [0; 2];
+    reader.read_exact(&mut version)?;
+    let version = u16::from_le_bytes(version);
+    if version != FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported format version {version}, expected {FORMAT_VERSION}"
+        )));
+    }
+    let mut saved_kind = [0; 1];
+    reader.read_exact(&mut saved_kind)?;
+    if saved_kind[0] != kind as u8 {
+        return Err(invalid_data(format!(
+            "Saved tree of kind {}, expected {kind:?}",
+            saved_kind[0]
+        )));
+    }
+    let num_leaf = usize::try_from(read_u64(reader)?)
+        .map_err(|_| invalid_data("Number of leaves too large".to_string()))?;
+    check_num_nodes(num_leaf, node_size).map_err(|e| invalid_data(e.to_string()))?;
+    Ok(num_leaf)
+}
+
+pub(crate) fn write_i32<W: Write>(writer: &mut W, value: i32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_i64<W: Write>(writer: &mut W, value: i64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn read_i32<R: Read>(reader: &mut R) -> io::Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_i64<R: Read>(reader: &mut R) -> io::Result<i64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(i64::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serialization::{Encode, TreeKind, read_header, write_header};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_header_round_trip() {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, TreeKind::Beats, 1_000_000).unwrap();
+        assert_eq!(15, bytes.len());
+        assert_eq!(b"SGTR", &bytes[..4]);
+        assert_eq!(
+            1_000_000,
+            read_header(&mut bytes.as_slice(), TreeKind::Beats, 8).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_headers() {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, TreeKind::Integer, 10).unwrap();
+        let kind_error = read_header(&mut bytes.as_slice(), TreeKind::Beats, 8).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, kind_error.kind());
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 2;
+        let version_error =
+            read_header(&mut wrong_version.as_slice(), TreeKind::Integer, 8).unwrap_err();
+        assert!(version_error.to_string().contains("version 2"));
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(read_header(&mut wrong_magic.as_slice(), TreeKind::Integer, 8).is_err());
+
+        let truncated_error = read_header(&mut &bytes[..10], TreeKind::Integer, 8).unwrap_err();
+        assert_eq!(ErrorKind::UnexpectedEof, truncated_error.kind());
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let mut bytes = Vec::new();
+        (Some(-3i64), 7u32).encode(&mut bytes).unwrap();
+        None::<i128>.encode(&mut bytes).unwrap();
+        assert_eq!(1 + 8 + 4 + 1, bytes.len());
+        let mut reader = bytes.as_slice();
+        assert_eq!(
+            (Some(-3), 7),
+            <(Option<i64>, u32)>::decode(&mut reader).unwrap()
+        );
+        assert_eq!(None, Option::<i128>::decode(&mut reader).unwrap());
+        assert!(reader.is_empty());
+
+        let error = Option::<i32>::decode(&mut [2u8].as_slice()).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+    }
+}
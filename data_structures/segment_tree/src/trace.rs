@@ -0,0 +1,64 @@
+use crate::range::Range;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// A step taken by `IntegerSegmentTree` while it descends the tree, reported to its
+/// [`TreeObserver`] together with the implicit index and the segment of the node involved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceEvent {
+    /// The descent reached the node.
+    Visit { index: usize, segment: Range },
+    /// The pending update of the node has been applied to it and handed to its children.
+    PushDown {
+        index: usize,
+        segment: Range,
+        update: i32,
+    },
+    /// The segment of the node is entirely inside the range of the operation.
+    TotalOverlap { index: usize, segment: Range },
+    /// The segment of the node is only partially inside the range, so the descent goes on.
+    PartialOverlap { index: usize, segment: Range },
+    /// The segment of the node is outside the range, so the descent stops.
+    NoOverlap { index: usize, segment: Range },
+}
+
+impl Display for TraceEvent {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            TraceEvent::Visit { index, segment } => write!(f, "visit node {index} {segment}"),
+            TraceEvent::PushDown {
+                index,
+                segment,
+                update,
+            } => write!(
+                f,
+                "push down update {update} from node {index} {segment} to {} and {}",
+                segment.left_half(),
+                segment.right_half()
+            ),
+            TraceEvent::TotalOverlap { index, segment } => {
+                write!(f, "total overlap on node {index} {segment}")
+            }
+            TraceEvent::PartialOverlap { index, segment } => {
+                write!(f, "partial overlap on node {index} {segment}")
+            }
+            TraceEvent::NoOverlap { index, segment } => {
+                write!(f, "no overlap on node {index} {segment}")
+            }
+        }
+    }
+}
+
+/// Receives the [`TraceEvent`]s of a tree, e.g. to log them or to count the visited nodes.
+///
+/// Any `FnMut(&TraceEvent)` closure is an observer, so printing every step is just
+/// `tree.set_observer(Box::new(|event: &TraceEvent| println!("{event}")))`.
+pub trait TreeObserver {
+    fn on_event(&mut self, event: &TraceEvent);
+}
+
+impl<F: FnMut(&TraceEvent)> TreeObserver for F {
+    fn on_event(&mut self, event: &TraceEvent) {
+        self(event)
+    }
+}
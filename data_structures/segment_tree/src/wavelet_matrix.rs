@@ -0,0 +1,476 @@
+use crate::error::{SegmentTreeError, check_range};
+use crate::serialization::{
+    FORMAT_VERSION, TreeKind, invalid_data, read_header, read_i64, read_u64, write_header,
+    write_i64, write_u64,
+};
+use std::fmt::Write;
+use std::io;
+
+// A fixed sequence of bits with O(1) rank: ones_before[w] is the number of ones in the
+// words before w, so rank only needs a popcount on the last partial word.
+struct BitVector {
+    words: Vec<u64>,
+    ones_before: Vec<usize>,
+}
+
+impl BitVector {
+    fn build(bits: &[bool]) -> Self {
+        let mut words = vec![0u64; bits.len() / 64 + 1];
+        for (i, _) in bits.iter().enumerate().filter(|(_, bit)| **bit) {
+            words[i / 64] |= 1 << (i % 64);
+        }
+        Self::from_words(words)
+    }
+
+    fn from_words(words: Vec<u64>) -> Self {
+        let ones_before = words
+            .iter()
+            .scan(0, |acc, word| {
+                let before = *acc;
+                *acc += word.count_ones() as usize;
+                Some(before)
+            })
+            .collect();
+        Self { words, ones_before }
+    }
+
+    // number of ones in the positions [0, i)
+    fn rank1(&self, i: usize) -> usize {
+        let mask = (1u64 << (i % 64)) - 1;
+        self.ones_before[i / 64] + (self.words[i / 64] & mask).count_ones() as usize
+    }
+
+    // number of zeros in the positions [0, i)
+    fn rank0(&self, i: usize) -> usize {
+        i - self.rank1(i)
+    }
+
+    fn rank(&self, bit: bool, i: usize) -> usize {
+        if bit { self.rank1(i) } else { self.rank0(i) }
+    }
+
+    // position of the (t + 1)-th `bit`, found by binary searching the first prefix holding
+    // t + 1 of them: rank is monotone in the prefix length
+    fn select(&self, bit: bool, t: usize, len: usize) -> usize {
+        let (mut lo, mut hi) = (0, len);
+        while lo < hi {
+            let middle = lo + (hi - lo) / 2;
+            if self.rank(bit, middle + 1) > t {
+                hi = middle;
+            } else {
+                lo = middle + 1;
+            }
+        }
+        lo
+    }
+}
+
+/// A wavelet matrix answering order statistics on the values of a static array.
+///
+/// Values are replaced by their rank among the sorted distinct values, written with
+/// `num_levels` bits. Level `l` stores, for each element, bit `l` of its rank (starting from
+/// the most significant one), and the elements are then stably partitioned by that bit (zeros
+/// first) before building the next level. A range `[i, j]` is followed from one level to the
+/// next with two rank queries, so counting and k-th smallest queries cost O(log σ), where σ
+/// is the number of distinct values. `position` also walks back up with a select on each
+/// level, found by binary search on rank, so it costs O(log σ · log n). The whole structure
+/// takes n log σ bits plus the sorted values.
+pub struct WaveletMatrix {
+    levels: Vec<BitVector>,
+    // num_zeros[l] is the number of zeros in level l, i.e. where the ones start in level l + 1
+    num_zeros: Vec<usize>,
+    sorted_values: Vec<i64>,
+    num_elems: usize,
+}
+
+impl WaveletMatrix {
+    pub fn build(a: &[i64]) -> Self {
+        let mut sorted_values = a.to_vec();
+        sorted_values.sort_unstable();
+        sorted_values.dedup();
+        // enough bits to write the largest rank, sorted_values.len() - 1
+        let num_levels =
+            (usize::BITS - sorted_values.len().saturating_sub(1).leading_zeros()) as usize;
+        let mut ranks: Vec<usize> = a
+            .iter()
+            .map(|value| sorted_values.binary_search(value).unwrap())
+            .collect();
+        let mut levels = Vec::with_capacity(num_levels);
+        let mut num_zeros = Vec::with_capacity(num_levels);
+        for level in 0..num_levels {
+            let shift = num_levels - 1 - level;
+            let bits: Vec<bool> = ranks.iter().map(|rank| (rank >> shift) & 1 == 1).collect();
+            let (zeros, ones): (Vec<usize>, Vec<usize>) =
+                ranks.iter().partition(|rank| (*rank >> shift) & 1 == 0);
+            levels.push(BitVector::build(&bits));
+            num_zeros.push(zeros.len());
+            ranks = zeros;
+            ranks.extend(ones);
+        }
+        Self {
+            levels,
+            num_zeros,
+            sorted_values,
+            num_elems: a.len(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_elems
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_elems == 0
+    }
+
+    fn bit(&self, rank: usize, level: usize) -> bool {
+        (rank >> (self.levels.len() - 1 - level)) & 1 == 1
+    }
+
+    // the half-open range [l, r) of the elements of `level` that move to the side `bit` of
+    // the next level
+    fn next_level_range(&self, level: usize, l: usize, r: usize, bit: bool) -> (usize, usize) {
+        let bits = &self.levels[level];
+        let offset = if bit { self.num_zeros[level] } else { 0 };
+        (offset + bits.rank(bit, l), offset + bits.rank(bit, r))
+    }
+
+    fn check_range(&self, i: usize, j: usize) {
+        check_range(i, j, self.num_elems).unwrap_or_else(|e| panic!("{e}"));
+    }
+
+    /// Like `count`, but fails if `[i, j]` ends past the last element.
+    pub fn try_count(&self, i: usize, j: usize, value: i64) -> Result<usize, SegmentTreeError> {
+        check_range(i, j, self.num_elems)?;
+        Ok(self.count(i, j, value))
+    }
+
+    /// Returns how many positions in `[i, j]` hold `value`.
+    pub fn count(&self, i: usize, j: usize, value: i64) -> usize {
+        if i > j {
+            return 0;
+        }
+        self.check_range(i, j);
+        let Ok(rank) = self.sorted_values.binary_search(&value) else {
+            return 0;
+        };
+        let (mut l, mut r) = (i, j + 1);
+        for level in 0..self.levels.len() {
+            (l, r) = self.next_level_range(level, l, r, self.bit(rank, level));
+        }
+        r - l
+    }
+
+    /// Returns how many positions in `[i, j]` hold a value smaller than `value`.
+    pub fn count_less(&self, i: usize, j: usize, value: i64) -> usize {
+        if i > j {
+            return 0;
+        }
+        self.check_range(i, j);
+        // the values smaller than `value` are exactly those with a smaller rank than the
+        // first distinct value >= `value`
+        let bound = self.sorted_values.partition_point(|v| *v < value);
+        if bound == self.sorted_values.len() {
+            return j - i + 1;
+        }
+        let (mut l, mut r) = (i, j + 1);
+        let mut less = 0;
+        for level in 0..self.levels.len() {
+            let bit = self.bit(bound, level);
+            if bit {
+                // the elements with a zero here agree with `bound` on the previous bits, so
+                // they are smaller
+                let (zl, zr) = self.next_level_range(level, l, r, false);
+                less += zr - zl;
+            }
+            (l, r) = self.next_level_range(level, l, r, bit);
+        }
+        less
+    }
+
+    /// Returns the `k`-th smallest value (starting from `k = 1`) among the positions in
+    /// `[i, j]`, or `None` if the range holds less than `k` values.
+    pub fn kth_smallest(&self, i: usize, j: usize, k: usize) -> Option<i64> {
+        if i > j || k == 0 || k > j - i + 1 {
+            return None;
+        }
+        self.check_range(i, j);
+        let (mut l, mut r) = (i, j + 1);
+        let mut k = k - 1;
+        let mut rank = 0;
+        for level in 0..self.levels.len() {
+            let (zl, zr) = self.next_level_range(level, l, r, false);
+            let bit = k >= zr - zl;
+            if bit {
+                k -= zr - zl;
+                rank |= 1 << (self.levels.len() - 1 - level);
+            }
+            (l, r) = self.next_level_range(level, l, r, bit);
+        }
+        Some(self.sorted_values[rank])
+    }
+
+    /// Like `position`, but fails if `[i, j]` ends past the last element.
+    pub fn try_position(
+        &self,
+        i: usize,
+        j: usize,
+        value: i64,
+    ) -> Result<Option<usize>, SegmentTreeError> {
+        check_range(i, j, self.num_elems)?;
+        Ok(self.position(i, j, value))
+    }
+
+    /// Returns the first position in `[i, j]` holding `value`, if any, in O(log σ · log n).
+    pub fn position(&self, i: usize, j: usize, value: i64) -> Option<usize> {
+        if i > j {
+            return None;
+        }
+        self.check_range(i, j);
+        let rank = self.sorted_values.binary_search(&value).ok()?;
+        if self.count(i, j, value) == 0 {
+            return None;
+        }
+        // the occurrences before i come first in the last level too, since every partition
+        // is stable: the wanted one is the next after them
+        let mut l = 0;
+        let mut before = i;
+        for level in 0..self.levels.len() {
+            let bit = self.bit(rank, level);
+            (l, before) = self.next_level_range(level, l, before, bit);
+        }
+        let mut p = before;
+        // walk back up to the first level, mapping the position to the one it came from
+        for level in (0..self.levels.len()).rev() {
+            let bit = self.bit(rank, level);
+            let offset = if bit { self.num_zeros[level] } else { 0 };
+            p = self.levels[level].select(bit, p - offset, self.num_elems);
+        }
+        Some(p)
+    }
+
+    /// Writes the matrix in the little-endian binary format shared by the trees of this
+    /// crate: a versioned header holding the number of elements, the number of distinct
+    /// values followed by the values in increasing order, and the bits of each level as
+    /// 64-bit words. The ranks are computed again on load.
+    ///
+    /// Every number is written separately, so `writer` should be buffered.
+    pub fn save<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, TreeKind::Wavelet, self.num_elems)?;
+        write_u64(writer, self.sorted_values.len() as u64)?;
+        for &value in &self.sorted_values {
+            write_i64(writer, value)?;
+        }
+        for level in &self.levels {
+            for &word in &level.words {
+                write_u64(writer, word)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a matrix written by `save`.
+    ///
+    /// Any bits describe the ranks of some sequence, so the matrix is only checked to hold
+    /// increasing values, and a rank smaller than the number of values for each element.
+    pub fn load<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let num_elems = read_header(reader, TreeKind::Wavelet, size_of::<u64>())?;
+        let num_values = read_u64(reader)?;
+        if num_values > num_elems as u64 || (num_values == 0) != (num_elems == 0) {
+            return Err(invalid_data(format!(
+                "{num_values} distinct values for {num_elems} elements"
+            )));
+        }
+        // the vectors grow as they are read, so corrupt sizes fail on the missing bytes
+        // instead of allocating
+        let mut sorted_values: Vec<i64> = Vec::new();
+        while (sorted_values.len() as u64) < num_values {
+            let value = read_i64(reader)?;
+            if sorted_values.last().is_some_and(|&last| last >= value) {
+                return Err(invalid_data("Values not in increasing order".to_string()));
+            }
+            sorted_values.push(value);
+        }
+        let num_levels =
+            (usize::BITS - sorted_values.len().saturating_sub(1).leading_zeros()) as usize;
+        let mut levels = Vec::new();
+        let mut num_zeros = Vec::new();
+        for _ in 0..num_levels {
+            let mut words = Vec::new();
+            for _ in 0..num_elems / 64 + 1 {
+                words.push(read_u64(reader)?);
+            }
+            // the bits past the last element are never set
+            if num_elems % 64 != 0 && words[num_elems / 64] >> (num_elems % 64) != 0 {
+                return Err(invalid_data("Bits set past the last element".to_string()));
+            }
+            let level = BitVector::from_words(words);
+            num_zeros.push(level.rank0(num_elems));
+            levels.push(level);
+        }
+        let matrix = Self {
+            levels,
+            num_zeros,
+            sorted_values,
+            num_elems,
+        };
+        matrix.check_ranks()?;
+        Ok(matrix)
+    }
+
+    // Follows every element down the levels, rebuilding its rank bit by bit, and checks that
+    // the rank is the one of a value.
+    fn check_ranks(&self) -> io::Result<()> {
+        for i in 0..self.num_elems {
+            let mut p = i;
+            let mut rank = 0;
+            for level in 0..self.levels.len() {
+                let bit = (self.levels[level].words[p / 64] >> (p % 64)) & 1 == 1;
+                rank = 2 * rank + bit as usize;
+                (p, _) = self.next_level_range(level, p, p, bit);
+            }
+            if rank >= self.sorted_values.len() {
+                return Err(invalid_data(format!("Invalid rank {rank} of element {i}")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Describes the matrix in a human-readable form: the sorted values, then one line per
+    /// level with its bits, from the first element to the last.
+    pub fn dump_text(&self) -> String {
+        let mut text = format!(
+            "WaveletMatrix (format version {FORMAT_VERSION})\nelements: {}\nvalues: {:?}\n",
+            self.num_elems, self.sorted_values
+        );
+        for (level, bits) in self.levels.iter().enumerate() {
+            // writing to a String never fails
+            write!(text, "level {level} ({} zeros): ", self.num_zeros[level]).unwrap();
+            text.extend((0..self.num_elems).map(|p| {
+                if (bits.words[p / 64] >> (p % 64)) & 1 == 1 {
+                    '1'
+                } else {
+                    '0'
+                }
+            }));
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wavelet_matrix::WaveletMatrix;
+
+    fn check_against_naive(a: &[i64]) {
+        let wm = WaveletMatrix::build(a);
+        let mut values = a.to_vec();
+        values.extend([i64::MIN, -100, 0, 100, i64::MAX]);
+        for i in 0..a.len() {
+            for j in i..a.len() {
+                let range = &a[i..=j];
+                let mut sorted = range.to_vec();
+                sorted.sort_unstable();
+                for k in 1..=range.len() {
+                    assert_eq!(Some(sorted[k - 1]), wm.kth_smallest(i, j, k));
+                }
+                assert_eq!(None, wm.kth_smallest(i, j, range.len() + 1));
+                for &v in &values {
+                    assert_eq!(range.iter().filter(|x| **x == v).count(), wm.count(i, j, v));
+                    assert_eq!(
+                        range.iter().filter(|x| **x < v).count(),
+                        wm.count_less(i, j, v)
+                    );
+                    assert_eq!(
+                        range.iter().position(|x| *x == v).map(|p| p + i),
+                        wm.position(i, j, v)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_queries() {
+        let wm = WaveletMatrix::build(&[5, 1, 4, 3, 2, 4, -7, 4]);
+        assert_eq!(3, wm.count(0, 7, 4));
+        assert_eq!(1, wm.count(3, 5, 4));
+        assert_eq!(0, wm.count(0, 7, 6));
+        assert_eq!(4, wm.count_less(0, 7, 4));
+        assert_eq!(2, wm.count_less(3, 6, 3));
+        assert_eq!(Some(-7), wm.kth_smallest(0, 7, 1));
+        assert_eq!(Some(4), wm.kth_smallest(1, 5, 4));
+        assert_eq!(Some(2), wm.position(0, 7, 4));
+        assert_eq!(Some(5), wm.position(3, 7, 4));
+        assert_eq!(None, wm.position(3, 4, 4));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let a = [5, 1, 4, 3, 2, 4, -7, 4];
+        let original = WaveletMatrix::build(&a);
+        let mut bytes = Vec::new();
+        original.save(&mut bytes).unwrap();
+
+        let loaded = WaveletMatrix::load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(original.dump_text(), loaded.dump_text());
+        assert_eq!(3, loaded.count(0, 7, 4));
+        assert_eq!(4, loaded.count_less(0, 7, 4));
+        assert_eq!(Some(4), loaded.kth_smallest(1, 5, 4));
+        assert_eq!(Some(5), loaded.position(3, 7, 4));
+
+        let mut truncated = bytes.clone();
+        truncated.pop();
+        assert!(WaveletMatrix::load(&mut truncated.as_slice()).is_err());
+        // 6 distinct values take 3 levels, so the ranks 6 and 7 are not values: setting
+        // every bit of the levels gives rank 7 to each element
+        let mut invalid_ranks = bytes.clone();
+        let levels_start = 15 + 8 + 6 * 8;
+        for level in 0..3 {
+            invalid_ranks[levels_start + 8 * level] = 0xff;
+        }
+        assert!(WaveletMatrix::load(&mut invalid_ranks.as_slice()).is_err());
+
+        let mut empty = Vec::new();
+        WaveletMatrix::build(&[]).save(&mut empty).unwrap();
+        assert!(
+            WaveletMatrix::load(&mut empty.as_slice())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_dump_text() {
+        // ranks 2, 0, 1, 2: 10 00 01 10
+        let wm = WaveletMatrix::build(&[9, -1, 3, 9]);
+        assert_eq!(
+            "WaveletMatrix (format version 1)\n\
+            elements: 4\n\
+            values: [-1, 3, 9]\n\
+            level 0 (2 zeros): 1001\n\
+            level 1 (3 zeros): 0100\n",
+            wm.dump_text()
+        );
+    }
+
+    #[test]
+    fn test_against_naive() {
+        check_against_naive(&[3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5, 8, 9, 7, 9, 3, 2, 3, 8, 4]);
+        check_against_naive(&[7; 10]);
+        check_against_naive(&[i64::MAX, i64::MIN, 0, -1, 1, i64::MIN]);
+        let a: Vec<i64> = (0..70).map(|x| (x * 37 % 61) - 30).collect();
+        check_against_naive(&a);
+    }
+
+    #[test]
+    fn test_empty() {
+        let wm = WaveletMatrix::build(&[]);
+        assert!(wm.is_empty());
+        assert_eq!(0, wm.count(1, 0, 3));
+        assert_eq!(None, wm.kth_smallest(1, 0, 1));
+        assert_eq!(Ok(None), wm.try_position(1, 0, 3));
+        assert!(wm.try_count(0, 0, 3).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+/// A static structure answering range queries for any associative merge (sum, product,
+/// matrix product, ...) in O(1) after an O(n log n) build.
+///
+/// At level `h` the positions are split into blocks of length `2^h`, and every block is
+/// split at its middle: `table[h][i]` holds the merge from `i` to the middle of its block
+/// for the left half, and from the middle to `i` for the right half. Positions `i < j`
+/// first fall into different halves at the level given by the highest bit in which they
+/// differ, so `[i, j]` is the merge of exactly two disjoint precomputed values. Unlike
+/// [`SparseTable`](crate::SparseTable) no element is merged twice, so the merge need not
+/// be idempotent, nor commutative.
+pub struct DisjointSparseTable<T: Copy> {
+    // table[0] is the array itself
+    table: Vec<Vec<T>>,
+    merge_values: fn(T, T) -> T,
+}
+
+impl<T: Copy> DisjointSparseTable<T> {
+    pub fn build(a: &[T], merge_values: fn(T, T) -> T) -> Self {
+        let n = a.len();
+        let mut table = vec![a.to_vec()];
+        let mut half = 1;
+        while half < n {
+            let mut level = a.to_vec();
+            for middle in (half..n).step_by(2 * half) {
+                // suffixes of the left half, ending right before the middle
+                for i in (middle - half..middle - 1).rev() {
+                    level[i] = merge_values(a[i], level[i + 1]);
+                }
+                // prefixes of the right half, starting from the middle
+                for i in middle + 1..(middle + half).min(n) {
+                    level[i] = merge_values(level[i - 1], a[i]);
+                }
+            }
+            table.push(level);
+            half *= 2;
+        }
+        Self {
+            table,
+            merge_values,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.table[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the merge of the elements in `[i, j]`, from left to right.
+    pub fn query(&self, i: usize, j: usize) -> T {
+        assert!(i <= j && j < self.len(), "Invalid range [{i}, {j}]");
+        if i == j {
+            return self.table[0][i];
+        }
+        // the level whose blocks have i and j in the same block but in different halves
+        let h = (i ^ j).ilog2() as usize + 1;
+        (self.merge_values)(self.table[h][i], self.table[h][j])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disjoint_sparse_table::DisjointSparseTable;
+
+    type Matrix = [[i64; 2]; 2];
+
+    fn multiply(a: Matrix, b: Matrix) -> Matrix {
+        let mut c = [[0; 2]; 2];
+        for (i, row) in c.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = (0..2).map(|k| a[i][k] * b[k][j]).sum();
+            }
+        }
+        c
+    }
+
+    #[test]
+    fn test_sum() {
+        let table = DisjointSparseTable::build(&[3, 4, 5, 3, 8, 12, -4], |x, y| x + y);
+        assert_eq!(31, table.query(0, 6));
+        assert_eq!(9, table.query(1, 2));
+        assert_eq!(16, table.query(4, 6));
+        assert_eq!(12, table.query(5, 5));
+    }
+
+    #[test]
+    fn test_sum_against_naive() {
+        for n in 1..40 {
+            let a: Vec<i64> = (0..n).map(|x| (x * 7919) % 31 - 15).collect();
+            let table = DisjointSparseTable::build(&a, |x, y| x + y);
+            for i in 0..a.len() {
+                for j in i..a.len() {
+                    assert_eq!(a[i..=j].iter().sum::<i64>(), table.query(i, j));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_non_commutative() {
+        let a: Vec<Matrix> = (0..13).map(|x| [[1, x], [x % 3, 1]]).collect();
+        let table = DisjointSparseTable::build(&a, multiply);
+        for i in 0..a.len() {
+            for j in i..a.len() {
+                let expected = a[i + 1..=j].iter().fold(a[i], |acc, m| multiply(acc, *m));
+                assert_eq!(expected, table.query(i, j));
+            }
+        }
+    }
+}
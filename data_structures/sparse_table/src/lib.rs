@@ -0,0 +1,5 @@
+mod disjoint_sparse_table;
+mod sparse_table;
+
+pub use disjoint_sparse_table::DisjointSparseTable;
+pub use sparse_table::SparseTable;
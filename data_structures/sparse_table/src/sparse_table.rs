@@ -0,0 +1,94 @@
+/// A static structure answering range queries for an idempotent merge (min, max, gcd,
+/// bitwise and/or, ...) in O(1) after an O(n log n) build.
+///
+/// `table[k][i]` holds the merge of `a[i..i + 2^k]`. Any range `[i, j]` is covered by the
+/// two, possibly overlapping, blocks of length `2^k` starting at `i` and ending at `j`,
+/// with `2^k` the largest power of two not longer than the range: merging an element twice
+/// does not change the result precisely because the merge is idempotent.
+pub struct SparseTable<T: Copy> {
+    table: Vec<Vec<T>>,
+    merge_values: fn(T, T) -> T,
+}
+
+impl<T: Copy> SparseTable<T> {
+    pub fn build(a: &[T], merge_values: fn(T, T) -> T) -> Self {
+        let mut table = vec![a.to_vec()];
+        let mut length = 1;
+        // each level merges two adjacent blocks of the previous one
+        while 2 * length <= a.len() {
+            let previous = table.last().unwrap();
+            let level = (0..=a.len() - 2 * length)
+                .map(|i| merge_values(previous[i], previous[i + length]))
+                .collect();
+            table.push(level);
+            length *= 2;
+        }
+        Self {
+            table,
+            merge_values,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.table[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the merge of the elements in `[i, j]`.
+    pub fn query(&self, i: usize, j: usize) -> T {
+        assert!(i <= j && j < self.len(), "Invalid range [{i}, {j}]");
+        let k = (j - i + 1).ilog2() as usize;
+        (self.merge_values)(self.table[k][i], self.table[k][j + 1 - (1 << k)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sparse_table::SparseTable;
+    use std::cmp::{max, min};
+
+    fn gcd(a: u64, b: u64) -> u64 {
+        if b == 0 { a } else { gcd(b, a % b) }
+    }
+
+    #[test]
+    fn test_min_max() {
+        let a = [3, 4, 5, 3, 8, 12, -4, 7];
+        let min_table = SparseTable::build(&a, min);
+        let max_table = SparseTable::build(&a, max);
+        assert_eq!(-4, min_table.query(0, 7));
+        assert_eq!(3, min_table.query(0, 5));
+        assert_eq!(12, max_table.query(2, 6));
+        assert_eq!(5, max_table.query(1, 3));
+        assert_eq!(8, max_table.query(4, 4));
+    }
+
+    #[test]
+    fn test_gcd_and_bitwise() {
+        let a: [u64; 6] = [12, 28, 60, 36, 7, 14];
+        let gcd_table = SparseTable::build(&a, gcd);
+        assert_eq!(4, gcd_table.query(0, 3));
+        assert_eq!(1, gcd_table.query(3, 4));
+        assert_eq!(7, gcd_table.query(4, 5));
+        let and_table = SparseTable::build(&a, |x, y| x & y);
+        let or_table = SparseTable::build(&a, |x, y| x | y);
+        assert_eq!(12, and_table.query(0, 2));
+        assert_eq!(47, or_table.query(3, 5));
+    }
+
+    #[test]
+    fn test_against_naive() {
+        for n in 1..40 {
+            let a: Vec<i32> = (0..n).map(|x| (x * 7919) % 31 - 15).collect();
+            let table = SparseTable::build(&a, min);
+            for i in 0..a.len() {
+                for j in i..a.len() {
+                    assert_eq!(*a[i..=j].iter().min().unwrap(), table.query(i, j));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use segment_tree::Monoid;
+use std::mem;
+
+struct Node<V> {
+    value: V,
+    // the combination of the values of the subtree, from left to right and from right to
+    // left: the latter becomes the former when the subtree is reversed
+    aggregate: V,
+    reversed_aggregate: V,
+    size: usize,
+    priority: u64,
+    // the node itself has already been reversed, its children still have to be
+    reverse_pending: bool,
+    id_left: Option<usize>,
+    id_right: Option<usize>,
+}
+
+/// A sequence supporting insertion, deletion, reversal and aggregate queries on any range in
+/// expected O(log n), stored as an implicit treap.
+///
+/// The nodes form a binary tree whose in-order visit is the sequence, and which is also a
+/// heap on random priorities, so its expected height is O(log n). Nodes store no position:
+/// the position of a node is the number of nodes before it, found from the subtree sizes.
+/// Every operation splits the tree around the range it works on and merges the pieces back.
+///
+/// The nodes live in an arena and refer to their children by index, as in
+/// `handson1::tree::Tree`; the slots of erased nodes are reused by later insertions.
+pub struct ImplicitTreap<M: Monoid> {
+    nodes: Vec<Node<M::Value>>,
+    root: Option<usize>,
+    free_ids: Vec<usize>,
+    // state of the xorshift generator of the priorities
+    seed: u64,
+}
+
+impl<M: Monoid> Default for ImplicitTreap<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: Monoid> ImplicitTreap<M> {
+    pub fn new() -> Self {
+        Self::with_seed(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn with_seed(seed: u64) -> Self {
+        Self {
+            nodes: Vec::new(),
+            root: None,
+            free_ids: Vec::new(),
+            seed,
+        }
+    }
+
+    pub fn build(a: &[M::Value]) -> Self {
+        let mut treap = Self::new();
+        for value in a {
+            let id = treap.new_node(value.clone());
+            treap.root = treap.merge_nodes(treap.root, Some(id));
+        }
+        treap
+    }
+
+    pub fn len(&self) -> usize {
+        self.size(self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    fn next_priority(&mut self) -> u64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+
+    fn new_node(&mut self, value: M::Value) -> usize {
+        let node = Node {
+            aggregate: value.clone(),
+            reversed_aggregate: value.clone(),
+            value,
+            size: 1,
+            priority: self.next_priority(),
+            reverse_pending: false,
+            id_left: None,
+            id_right: None,
+        };
+        self.alloc(node)
+    }
+
+    // stores a node in a free slot of the arena, if any, and returns its id
+    fn alloc(&mut self, node: Node<M::Value>) -> usize {
+        match self.free_ids.pop() {
+            Some(id) => {
+                self.nodes[id] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn size(&self, id: Option<usize>) -> usize {
+        id.map_or(0, |id| self.nodes[id].size)
+    }
+
+    fn aggregate(&self, id: Option<usize>) -> M::Value {
+        id.map_or_else(M::identity, |id| self.nodes[id].aggregate.clone())
+    }
+
+    fn reversed_aggregate(&self, id: Option<usize>) -> M::Value {
+        id.map_or_else(M::identity, |id| self.nodes[id].reversed_aggregate.clone())
+    }
+
+    // recomputes size and aggregates of a node from its children
+    fn pull_up(&mut self, id: usize) {
+        let (id_left, id_right) = (self.nodes[id].id_left, self.nodes[id].id_right);
+        let value = &self.nodes[id].value;
+        let aggregate = M::combine(
+            &M::combine(&self.aggregate(id_left), value),
+            &self.aggregate(id_right),
+        );
+        let reversed_aggregate = M::combine(
+            &M::combine(&self.reversed_aggregate(id_right), value),
+            &self.reversed_aggregate(id_left),
+        );
+        let size = self.size(id_left) + 1 + self.size(id_right);
+        let node = &mut self.nodes[id];
+        node.aggregate = aggregate;
+        node.reversed_aggregate = reversed_aggregate;
+        node.size = size;
+    }
+
+    // reverses the subtree of a node in O(1), leaving the rest of the work to its children
+    fn toggle_reverse(&mut self, id: usize) {
+        let node = &mut self.nodes[id];
+        mem::swap(&mut node.id_left, &mut node.id_right);
+        mem::swap(&mut node.aggregate, &mut node.reversed_aggregate);
+        node.reverse_pending = !node.reverse_pending;
+    }
+
+    fn push_down(&mut self, id: usize) {
+        if !self.nodes[id].reverse_pending {
+            return;
+        }
+        self.nodes[id].reverse_pending = false;
+        for child in [self.nodes[id].id_left, self.nodes[id].id_right]
+            .into_iter()
+            .flatten()
+        {
+            self.toggle_reverse(child);
+        }
+    }
+
+    // splits the subtree of `root` into its first `k` elements and the rest
+    fn split_nodes(&mut self, root: Option<usize>, k: usize) -> (Option<usize>, Option<usize>) {
+        let Some(id) = root else {
+            return (None, None);
+        };
+        self.push_down(id);
+        let left_size = self.size(self.nodes[id].id_left);
+        if k <= left_size {
+            let (left, right) = self.split_nodes(self.nodes[id].id_left, k);
+            self.nodes[id].id_left = right;
+            self.pull_up(id);
+            (left, Some(id))
+        } else {
+            let (left, right) = self.split_nodes(self.nodes[id].id_right, k - left_size - 1);
+            self.nodes[id].id_right = left;
+            self.pull_up(id);
+            (Some(id), right)
+        }
+    }
+
+    // concatenates two subtrees, keeping as root the node with the highest priority
+    fn merge_nodes(&mut self, left: Option<usize>, right: Option<usize>) -> Option<usize> {
+        let (Some(l), Some(r)) = (left, right) else {
+            return left.or(right);
+        };
+        if self.nodes[l].priority > self.nodes[r].priority {
+            self.push_down(l);
+            self.nodes[l].id_right = self.merge_nodes(self.nodes[l].id_right, right);
+            self.pull_up(l);
+            left
+        } else {
+            self.push_down(r);
+            self.nodes[r].id_left = self.merge_nodes(left, self.nodes[r].id_left);
+            self.pull_up(r);
+            right
+        }
+    }
+
+    fn check_range(&self, l: usize, r: usize) {
+        assert!(
+            l <= r && r < self.len(),
+            "Range [{l}, {r}] out of bounds for {} elements",
+            self.len()
+        );
+    }
+
+    // detaches the range [l, r] from the sequence, returning the roots of the elements
+    // before it, of the range and of the elements after it
+    fn split_range(&mut self, l: usize, r: usize) -> (Option<usize>, Option<usize>, Option<usize>) {
+        let (rest, after) = self.split_nodes(self.root, r + 1);
+        let (before, range) = self.split_nodes(rest, l);
+        (before, range, after)
+    }
+
+    fn join_range(&mut self, before: Option<usize>, range: Option<usize>, after: Option<usize>) {
+        let rest = self.merge_nodes(before, range);
+        self.root = self.merge_nodes(rest, after);
+    }
+
+    /// Inserts `value` at position `pos`, shifting the following elements to the right.
+    pub fn insert(&mut self, pos: usize, value: M::Value) {
+        assert!(pos <= self.len(), "Index {pos} out of bounds");
+        let id = self.new_node(value);
+        let (before, after) = self.split_nodes(self.root, pos);
+        self.join_range(before, Some(id), after);
+    }
+
+    pub fn push_back(&mut self, value: M::Value) {
+        self.insert(self.len(), value);
+    }
+
+    /// Removes the elements in `[l, r]`.
+    pub fn erase(&mut self, l: usize, r: usize) {
+        self.check_range(l, r);
+        let (before, range, after) = self.split_range(l, r);
+        self.free_subtree(range);
+        self.root = self.merge_nodes(before, after);
+    }
+
+    fn free_subtree(&mut self, root: Option<usize>) {
+        let mut stack: Vec<usize> = root.into_iter().collect();
+        while let Some(id) = stack.pop() {
+            stack.extend(self.nodes[id].id_left);
+            stack.extend(self.nodes[id].id_right);
+            self.free_ids.push(id);
+        }
+    }
+
+    /// Reverses the order of the elements in `[l, r]`.
+    pub fn reverse(&mut self, l: usize, r: usize) {
+        self.check_range(l, r);
+        let (before, range, after) = self.split_range(l, r);
+        if let Some(id) = range {
+            self.toggle_reverse(id);
+        }
+        self.join_range(before, range, after);
+    }
+
+    /// Combines, from left to right, the values in positions `[l, r]`.
+    pub fn query(&mut self, l: usize, r: usize) -> M::Value {
+        self.check_range(l, r);
+        let (before, range, after) = self.split_range(l, r);
+        let result = self.aggregate(range);
+        self.join_range(before, range, after);
+        result
+    }
+
+    pub fn get(&mut self, i: usize) -> M::Value {
+        assert!(i < self.len(), "Index {i} out of bounds");
+        self.query(i, i)
+    }
+
+    /// Returns the elements of the sequence, in order, in O(n).
+    pub fn to_vec(&mut self) -> Vec<M::Value> {
+        let mut result = Vec::with_capacity(self.len());
+        let mut stack = Vec::new();
+        let mut current = self.root;
+        // iterative in-order visit, pushing pending reversals on the way down
+        while current.is_some() || !stack.is_empty() {
+            while let Some(id) = current {
+                self.push_down(id);
+                stack.push(id);
+                current = self.nodes[id].id_left;
+            }
+            let id = stack.pop().unwrap();
+            result.push(self.nodes[id].value.clone());
+            current = self.nodes[id].id_right;
+        }
+        result
+    }
+
+    /// Keeps the first `k` elements and returns a new sequence with the others.
+    ///
+    /// The split itself takes expected O(log n), but the detached nodes are then moved to
+    /// the arena of the new sequence, so the whole operation is O(log n + n - k).
+    pub fn split(&mut self, k: usize) -> Self {
+        assert!(k <= self.len(), "Index {k} out of bounds");
+        let (left, right) = self.split_nodes(self.root, k);
+        self.root = left;
+        let mut other = Self::with_seed(self.next_priority());
+        other.root = self.move_subtree(right, &mut other);
+        other
+    }
+
+    /// Appends the elements of `other` at the end of the sequence, in O(log n + m) where m
+    /// is the length of `other`, since its nodes are moved to the arena of `self`.
+    pub fn merge(&mut self, mut other: Self) {
+        let moved = other.move_subtree(other.root, self);
+        self.root = self.merge_nodes(self.root, moved);
+    }
+
+    // moves the nodes of a subtree to the arena of `dest`, keeping shape, priorities and
+    // pending reversals, and returns the id of its root in `dest`
+    fn move_subtree(&mut self, root: Option<usize>, dest: &mut Self) -> Option<usize> {
+        let id = root?;
+        let id_left = self.move_subtree(self.nodes[id].id_left, dest);
+        let id_right = self.move_subtree(self.nodes[id].id_right, dest);
+        let node = &self.nodes[id];
+        let moved = dest.alloc(Node {
+            value: node.value.clone(),
+            aggregate: node.aggregate.clone(),
+            reversed_aggregate: node.reversed_aggregate.clone(),
+            size: node.size,
+            priority: node.priority,
+            reverse_pending: node.reverse_pending,
+            id_left,
+            id_right,
+        });
+        self.free_ids.push(id);
+        Some(moved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::implicit_treap::ImplicitTreap;
+    use segment_tree::Monoid;
+
+    struct Sum;
+
+    impl Monoid for Sum {
+        type Value = i64;
+
+        fn identity() -> i64 {
+            0
+        }
+
+        fn combine(a: &i64, b: &i64) -> i64 {
+            a + b
+        }
+    }
+
+    // concatenation is not commutative, so it catches aggregates combined in the wrong order
+    struct Concat;
+
+    impl Monoid for Concat {
+        type Value = String;
+
+        fn identity() -> String {
+            String::new()
+        }
+
+        fn combine(a: &String, b: &String) -> String {
+            format!("{a}{b}")
+        }
+    }
+
+    fn chars(s: &str) -> Vec<String> {
+        s.chars().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_insert_erase_query() {
+        let mut treap = ImplicitTreap::<Sum>::build(&[3, 4, 5, 3, 8, 12, -4]);
+        assert_eq!(31, treap.query(0, 6));
+        treap.insert(2, 10);
+        //[3, 4, 10, 5, 3, 8, 12, -4]
+        assert_eq!(19, treap.query(1, 3));
+        treap.erase(4, 6);
+        //[3, 4, 10, 5, -4]
+        assert_eq!(vec![3, 4, 10, 5, -4], treap.to_vec());
+        assert_eq!(18, treap.query(0, 4));
+        assert_eq!(5, treap.get(3));
+        treap.push_back(7);
+        assert_eq!(6, treap.len());
+    }
+
+    #[test]
+    fn test_reverse_non_commutative() {
+        let mut treap = ImplicitTreap::<Concat>::build(&chars("abcdefgh"));
+        treap.reverse(1, 5);
+        assert_eq!("afedcbgh", treap.query(0, 7));
+        treap.reverse(0, 3);
+        assert_eq!("defacbgh", treap.query(0, 7));
+        assert_eq!("fac", treap.query(2, 4));
+        treap.reverse(2, 7);
+        assert_eq!("dehgbcaf", treap.query(0, 7));
+        assert_eq!(chars("dehgbcaf"), treap.to_vec());
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let mut left = ImplicitTreap::<Concat>::build(&chars("hello world"));
+        let mut right = left.split(5);
+        assert_eq!("hello", left.query(0, 4));
+        assert_eq!(" world", right.query(0, 5));
+        right.reverse(1, 5);
+        left.merge(right);
+        assert_eq!("hello dlrow", left.query(0, 10));
+        left.reverse(0, 10);
+        assert_eq!("world olleh", left.query(0, 10));
+
+        let mut empty = left.split(11);
+        assert!(empty.is_empty());
+        empty.merge(left);
+        assert_eq!(11, empty.len());
+    }
+
+    #[test]
+    fn test_erased_slots_are_reused() {
+        let mut treap = ImplicitTreap::<Sum>::build(&[1; 10]);
+        treap.erase(2, 8);
+        for i in 0..7 {
+            treap.insert(i, 2);
+        }
+        assert_eq!(10, treap.nodes.len());
+        assert_eq!(17, treap.query(0, 9));
+    }
+
+    #[test]
+    fn test_updates_inside_reversed_ranges() {
+        let mut treap = ImplicitTreap::<Concat>::build(&chars("abcdefgh"));
+        treap.reverse(2, 5);
+        // the insertion and the erasure split nodes whose reversal is still pending
+        treap.insert(3, "X".to_string());
+        assert_eq!("abfXedcgh", treap.query(0, 8));
+        treap.erase(1, 2);
+        assert_eq!("aXedcgh", treap.query(0, 6));
+        // reversing twice cancels out
+        treap.reverse(0, 6);
+        assert_eq!("hgcdeXa", treap.query(0, 6));
+        treap.reverse(0, 6);
+        treap.reverse(1, 3);
+        assert_eq!("eXc", treap.query(2, 4));
+        assert_eq!("d", treap.get(1));
+        assert_eq!(chars("adeXcgh"), treap.to_vec());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_erase_out_of_bounds() {
+        ImplicitTreap::<Sum>::build(&[1, 2, 3]).erase(1, 3);
+    }
+}
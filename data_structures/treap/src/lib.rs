@@ -0,0 +1,4 @@
+mod implicit_treap;
+
+pub use implicit_treap::ImplicitTreap;
+pub use segment_tree::Monoid;
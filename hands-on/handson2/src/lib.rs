@@ -1,240 +1,74 @@
 #![allow(unused)]
-mod range {
-    use std::cmp::{max, min};
-    use std::fmt;
-    use std::fmt::{Display, Formatter};
-
-    #[derive(Debug, Clone, Copy, PartialEq)]
-    pub(crate) struct Range {
-        pub(crate) start: usize,
-        pub(crate) end: usize,
-    }
-
-    impl Display for Range {
-        fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-            write!(f, "[{}..{}]", self.start, self.end)
-        }
-    }
-
-    impl Range {
-        pub fn new(start: usize, end: usize) -> Self {
-            Range { start, end }
-        }
-
-        pub fn total_overlap(&self, other: Range) -> bool {
-            self.start <= other.start && self.end >= other.end
-        }
-
-        pub fn size(&self) -> usize {
-            self.end - self.start + 1
-        }
-
-        pub fn is_single_point(&self) -> bool {
-            self.size() == 1
-        }
-
-        pub fn intersect(&self, other: Range) -> Self {
-            Range::new(max(self.start, other.start), min(self.end, other.end))
-        }
-
-        pub fn no_overlap(&self, other: Range) -> bool {
-            self.end < other.start || other.end < self.start
-        }
-
-        pub fn middle(&self) -> usize {
-            self.start + ((self.end - self.start) / 2)
-        }
-    }
-
-    pub fn left_right_child_index(node_segment: Range, father_index: usize) -> (usize, usize) {
-        let middle = node_segment.middle();
-        // since the left subtree has num_leaf_left_subtree leaf it will have in total
-        // 2 * num_leaf_left_subtree - 1 nodes leaf included so the index of the right
-        // child will be the next after this nodes
-        let num_leaf_left_subtree = middle - node_segment.start + 1;
-        let left_child_index = father_index + 1;
-        let right_child_index = father_index + 2 * num_leaf_left_subtree;
-        (left_child_index, right_child_index)
-    }
-}
-
 /* ---------  Problem #1: Min and Max  ---------------- */
 pub mod min_max {
-    use crate::range::Range;
-    use crate::range::left_right_child_index;
-    use std::cmp::{max, min};
+    use segment_tree::{SegmentTreeBeats, SegmentTreeError};
     use std::error::Error;
     use std::fmt::Display;
 
-    struct MaxSegmentTree {
-        tree: Vec<u32>,
-        pending_updates: Vec<Option<u32>>,
-        num_leaf: usize,
+    // A wrapper around the segment tree that exposes the problem’s
+    // interface (range min update and range max) while hiding the underlying
+    // segment-tree implementation. Since a[i] = min(a[i], T) is a chmin, a
+    // segment tree beats can maintain range sums and minimums under the same
+    // updates, so they are exposed too
+    pub struct MinMaxArray {
+        st: SegmentTreeBeats,
     }
 
-    impl MaxSegmentTree {
+    impl MinMaxArray {
         pub fn build(a: &[u32]) -> Self {
-            let mut implicit_tree: Vec<u32> = vec![u32::MAX; 2 * a.len() - 1];
-            //create a max segment tree
-            Self::build_rec(a, &mut implicit_tree, Range::new(0, a.len() - 1), 0);
+            let values: Vec<i64> = a.iter().map(|&v| v as i64).collect();
             Self {
-                tree: implicit_tree,
-                //initially there are no pending updates
-                pending_updates: vec![None; 2 * a.len() - 1],
-                num_leaf: a.len(),
+                st: SegmentTreeBeats::build(&values),
             }
         }
 
-        fn build_rec(a: &[u32], tree: &mut Vec<u32>, node_segment: Range, index: usize) {
-            if node_segment.is_single_point() {
-                tree[index] = a[node_segment.start];
-                return;
-            }
-            let middle = node_segment.middle();
-            let left_child_index = index + 1;
-            let right_child_index = index + 2 * (middle - node_segment.start + 1);
-            Self::build_rec(
-                a,
-                tree,
-                Range::new(node_segment.start, middle),
-                left_child_index,
-            );
-            Self::build_rec(
-                a,
-                tree,
-                Range::new(middle + 1, node_segment.end),
-                right_child_index,
-            );
-            // the maximum value of the current segment is the maximum between its two child segments
-            tree[index] = max(tree[left_child_index], tree[right_child_index]);
+        pub fn max(&mut self, i: usize, j: usize) -> u32 {
+            self.try_max(i, j).unwrap_or_else(|e| panic!("{e}"))
         }
 
-        pub fn query(&mut self, i: usize, j: usize) -> u32 {
-            if i > j {
-                return u32::MIN;
-            }
-            self.query_rec(Range::new(i, j), Range::new(0, self.num_leaf - 1), 0)
-        }
-
-        fn query_rec(&mut self, query_range: Range, node_segment: Range, index: usize) -> u32 {
-            // Since we are visiting this node, we must apply any pending lazy updates.
-            // This is essential if the next step is a total-overlap case, because we must
-            // return the correct fully-updated value to the parent. In partial-overlap cases,
-            // applying updates now allows correct propagation to the children during recursion.
-            // It is not required for no-overlap, but since we are already here, applying it is harmless.
-            self.handle_pending_updates(node_segment, index);
-            if query_range.no_overlap(node_segment) {
-                return u32::MIN;
-            }
-            if query_range.total_overlap(node_segment) {
-                return self.tree[index];
-            }
-            // partial overlap
-            let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
-            let middle = node_segment.middle();
-            let left_value = self.query_rec(
-                query_range,
-                Range::new(node_segment.start, middle),
-                left_child_index,
-            );
-            let right_value = self.query_rec(
-                query_range,
-                Range::new(middle + 1, node_segment.end),
-                right_child_index,
-            );
-            max(left_value, right_value)
+        /// Like `max`, but fails if `[i, j]` ends past the last position.
+        pub fn try_max(&mut self, i: usize, j: usize) -> Result<u32, SegmentTreeError> {
+            // an update can only lower the values, so they always fit in a u32
+            Ok(u32::try_from(self.st.try_max(i, j)?).unwrap_or(u32::MIN))
         }
 
-        fn handle_pending_updates(&mut self, node_segment: Range, index: usize) {
-            if let Some(pending_update) = self.pending_updates[index] {
-                self.update_node_and_propagate(node_segment, index, pending_update);
-                self.pending_updates[index] = None;
-            }
+        pub fn min(&mut self, i: usize, j: usize) -> u32 {
+            u32::try_from(self.st.min(i, j)).unwrap_or(u32::MAX)
         }
 
-        fn update_node_and_propagate(&mut self, node_segment: Range, index: usize, to_update: u32) {
-            self.tree[index] = min(self.tree[index], to_update);
-            //check if the node is not a leaf in the segment tree and propagate lazy updates
-            // to its children since the update for them is not needed now
-            if !node_segment.is_single_point() {
-                let (left_child_index, right_child_index) =
-                    left_right_child_index(node_segment, index);
-                self.pending_updates[left_child_index] =
-                    Self::merge_min(self.pending_updates[left_child_index], to_update);
-                self.pending_updates[right_child_index] =
-                    Self::merge_min(self.pending_updates[right_child_index], to_update);
-            }
+        pub fn sum(&mut self, i: usize, j: usize) -> u64 {
+            self.st.sum(i, j) as u64
         }
 
-        fn merge_min(a: Option<u32>, val: u32) -> Option<u32> {
-            Some(a.unwrap_or(val).min(val))
+        pub fn update(&mut self, i: usize, j: usize, t: u32) {
+            self.st.chmin(i, j, t as i64);
         }
 
-        fn range_update(&mut self, i: usize, j: usize, val: u32) {
-            self.range_update_rec(Range::new(i, j), Range::new(0, self.num_leaf - 1), val, 0);
+        /// Like `update`, but fails without touching the array if `[i, j]` ends past the
+        /// last position.
+        pub fn try_update(&mut self, i: usize, j: usize, t: u32) -> Result<(), SegmentTreeError> {
+            self.st.try_chmin(i, j, t as i64)
         }
 
-        fn range_update_rec(
-            &mut self,
-            query_range: Range,
-            node_segment: Range,
-            val: u32,
-            nav_index: usize,
-        ) {
-            self.handle_pending_updates(node_segment, nav_index);
-            if node_segment.no_overlap(query_range) {
-                return;
-            }
-            if query_range.total_overlap(node_segment) {
-                // total overlap: node_segment is contained in query so we need to instantly apply
-                // the update to have the correct value since it will be used by the partial overlapped
-                // father and lazy propagate it to its children since the update is not needed for them now
-                self.update_node_and_propagate(node_segment, nav_index, val);
-                return;
-            }
-            // partial overlap: range partially contained in node_segment
-            // so we need to navigate left and right children
-            let (left_child_index, right_child_index) =
-                left_right_child_index(node_segment, nav_index);
-            let middle = node_segment.middle();
-            self.range_update_rec(
-                query_range,
-                Range::new(node_segment.start, middle),
-                val,
-                left_child_index,
-            );
-            self.range_update_rec(
-                query_range,
-                Range::new(middle + 1, node_segment.end),
-                val,
-                right_child_index,
-            );
-            // the value of the children has already been updated, so we can use them
-            self.tree[nav_index] = max(self.tree[left_child_index], self.tree[right_child_index]);
+        /// Returns the current value of `a[i]`.
+        pub fn leaf(&mut self, i: usize) -> u32 {
+            self.st.leaf(i) as u32
         }
-    }
-
-    // A wrapper around the segment tree that exposes the problem’s
-    // interface (range min update and range max) while hiding the underlying
-    // segment-tree implementation
-    pub struct MinMaxArray {
-        st: MaxSegmentTree,
-    }
 
-    impl MinMaxArray {
-        pub fn build(a: &[u32]) -> Self {
-            Self {
-                st: MaxSegmentTree::build(a),
-            }
+        /// Iterates the current values of the array, pushing all the pending updates in O(n).
+        pub fn leaves(&mut self) -> impl Iterator<Item = u32> + '_ {
+            // values only decrease from the initial u32 ones, so they always fit in a u32
+            self.st.leaves().map(|v| v as u32)
         }
 
-        pub fn max(&mut self, i: usize, j: usize) -> u32 {
-            self.st.query(i, j)
+        pub fn to_vec(&mut self) -> Vec<u32> {
+            self.leaves().collect()
         }
+    }
 
-        pub fn update(&mut self, i: usize, j: usize, t: u32) {
-            self.st.range_update(i, j, t);
-        }
+    // the positions of the input start from 1
+    fn zero_based(position: usize) -> Result<usize, Box<dyn Error>> {
+        Ok(position.checked_sub(1).ok_or("positions start from 1")?)
     }
 
     pub fn solve(input: &str) -> Result<String, Box<dyn Error>> {
@@ -258,12 +92,12 @@ pub mod min_max {
                 let l: usize = iter.next().ok_or("missing l")?.parse()?;
                 let r: usize = iter.next().ok_or("missing r")?.parse()?;
                 let t: u32 = iter.next().ok_or("missing val")?.parse()?;
-                min_max_arr.update(l - 1, r - 1, t);
+                min_max_arr.try_update(zero_based(l)?, zero_based(r)?, t)?;
             } else if query_type == 1 {
                 // Max(i, j)
                 let l: usize = iter.next().ok_or("missing l")?.parse()?;
                 let r: usize = iter.next().ok_or("missing r")?.parse()?;
-                let ans = min_max_arr.max(l - 1, r - 1);
+                let ans = min_max_arr.try_max(zero_based(l)?, zero_based(r)?)?;
                 output.push_str(&format!("{}\n", ans));
             } else {
                 output.push_str("unknown query type\n");
@@ -275,154 +109,175 @@ pub mod min_max {
 
     #[cfg(test)]
     mod segment_tree_tests {
-        use crate::min_max::MaxSegmentTree;
+        use crate::min_max::MinMaxArray;
+        use segment_tree::SegmentTreeError;
 
         #[test]
         fn test_small_array_build() {
-            let mut tree = MaxSegmentTree::build(&[7, 10, 2, 21]);
+            let mut tree = MinMaxArray::build(&[7, 10, 2, 21]);
 
-            assert_eq!(21, tree.query(0, 3));
-            assert_eq!(10, tree.query(0, 1));
-            assert_eq!(21, tree.query(2, 3));
-            assert_eq!(7, tree.query(0, 0));
-            assert_eq!(10, tree.query(1, 1));
-            assert_eq!(2, tree.query(2, 2));
-            assert_eq!(21, tree.query(3, 3));
+            assert_eq!(21, tree.max(0, 3));
+            assert_eq!(10, tree.max(0, 1));
+            assert_eq!(21, tree.max(2, 3));
+            assert_eq!(7, tree.max(0, 0));
+            assert_eq!(10, tree.max(1, 1));
+            assert_eq!(2, tree.max(2, 2));
+            assert_eq!(21, tree.max(3, 3));
         }
 
         #[test]
         fn test_medium_array_build() {
-            let mut tree = MaxSegmentTree::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+            let mut tree = MinMaxArray::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
 
             // Leaf
-            assert_eq!(8, tree.query(0, 0));
-            assert_eq!(2, tree.query(1, 1));
-            assert_eq!(6, tree.query(2, 2));
-            assert_eq!(21, tree.query(3, 3));
-            assert_eq!(18, tree.query(4, 4));
-            assert_eq!(15, tree.query(5, 5));
-            assert_eq!(2, tree.query(6, 6));
-            assert_eq!(31, tree.query(7, 7));
-            assert_eq!(4, tree.query(8, 8));
-            assert_eq!(16, tree.query(9, 9));
-
-            assert_eq!(8, tree.query(0, 1));
-            assert_eq!(6, tree.query(1, 2));
-            assert_eq!(21, tree.query(3, 4));
-            assert_eq!(15, tree.query(5, 6));
-            assert_eq!(31, tree.query(6, 7));
-            assert_eq!(16, tree.query(8, 9));
-
-            assert_eq!(8, tree.query(0, 2));
-            assert_eq!(21, tree.query(0, 3));
-            assert_eq!(21, tree.query(0, 4));
-            assert_eq!(21, tree.query(3, 4));
-            assert_eq!(31, tree.query(5, 7));
-            assert_eq!(16, tree.query(8, 9));
-            assert_eq!(31, tree.query(5, 9));
-
-            assert_eq!(31, tree.query(0, 9));
-            assert_eq!(31, tree.query(2, 9));
-            assert_eq!(31, tree.query(4, 9));
-            assert_eq!(31, tree.query(7, 9));
+            assert_eq!(8, tree.max(0, 0));
+            assert_eq!(2, tree.max(1, 1));
+            assert_eq!(6, tree.max(2, 2));
+            assert_eq!(21, tree.max(3, 3));
+            assert_eq!(18, tree.max(4, 4));
+            assert_eq!(15, tree.max(5, 5));
+            assert_eq!(2, tree.max(6, 6));
+            assert_eq!(31, tree.max(7, 7));
+            assert_eq!(4, tree.max(8, 8));
+            assert_eq!(16, tree.max(9, 9));
+
+            assert_eq!(8, tree.max(0, 1));
+            assert_eq!(6, tree.max(1, 2));
+            assert_eq!(21, tree.max(3, 4));
+            assert_eq!(15, tree.max(5, 6));
+            assert_eq!(31, tree.max(6, 7));
+            assert_eq!(16, tree.max(8, 9));
+
+            assert_eq!(8, tree.max(0, 2));
+            assert_eq!(21, tree.max(0, 3));
+            assert_eq!(21, tree.max(0, 4));
+            assert_eq!(21, tree.max(3, 4));
+            assert_eq!(31, tree.max(5, 7));
+            assert_eq!(16, tree.max(8, 9));
+            assert_eq!(31, tree.max(5, 9));
+
+            assert_eq!(31, tree.max(0, 9));
+            assert_eq!(31, tree.max(2, 9));
+            assert_eq!(31, tree.max(4, 9));
+            assert_eq!(31, tree.max(7, 9));
         }
 
         #[test]
-        fn hands_on_example() {
-            let mut tree = MaxSegmentTree::build(&[5, 1, 4, 3, 2]);
+        fn test_range_update_right_child_root() {
+            let mut tree = MinMaxArray::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
 
-            tree.range_update(0, 1, 2);
-            assert_eq!(4, tree.query(1, 3));
-            assert_eq!(2, tree.query(0, 1));
+            tree.update(5, 9, 2);
+            assert_eq!(21, tree.max(0, 9));
         }
 
         #[test]
-        fn test_range_update_right_child_root() {
-            let mut tree = MaxSegmentTree::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+        fn test_range_update_leaf() {
+            let mut tree = MinMaxArray::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
 
-            tree.range_update(5, 9, 2);
-            assert_eq!(21, tree.query(0, 9));
+            tree.update(7, 7, 2);
+            assert_eq!(21, tree.max(0, 9));
         }
 
         #[test]
-        fn test_range_update_leaf() {
-            let mut tree = MaxSegmentTree::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+        fn test_range_update_propagation_until_leaf() {
+            let mut tree = MinMaxArray::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
 
-            tree.range_update(7, 7, 2);
-            assert_eq!(21, tree.query(0, 9));
+            tree.update(4, 9, 2);
+            assert_eq!(2, tree.max(5, 7));
+            assert_eq!(2, tree.max(5, 6));
+            assert_eq!(2, tree.max(5, 5));
+            assert_eq!(2, tree.max(6, 6));
         }
 
         #[test]
-        fn test_range_update_propagation_until_leaf() {
-            let mut tree = MaxSegmentTree::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
-
-            tree.range_update(4, 9, 2);
-            assert_eq!(2, tree.query(5, 7));
-            assert_eq!(2, tree.query(5, 6));
-            assert_eq!(2, tree.query(5, 5));
-            assert_eq!(2, tree.query(6, 6));
-        }
-
-        #[test]
-        fn test_range_update_internal_propagation_until_leaf() {
-            let mut tree = MaxSegmentTree::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
-
-            tree.range_update(4, 9, 2);
-            //[5,9] updated
-            assert_eq!(2, tree.tree[10]);
-            assert_eq!(None, tree.pending_updates[10]);
-            //[5, 7] still not updated but lazy update recorded
-            assert_eq!(31, tree.tree[11]);
-            assert_eq!(2, tree.pending_updates[11].unwrap());
-            //[8,9] still not updated but lazy update recorded
-            assert_eq!(16, tree.tree[16]);
-            assert_eq!(2, tree.pending_updates[16].unwrap());
-
-            assert_eq!(2, tree.query(5, 7));
-            //[5,7] updated
-            assert_eq!(2, tree.tree[11]);
-            assert_eq!(None, tree.pending_updates[11]);
-            //[5, 6] still not updated but lazy update recorded
-            assert_eq!(15, tree.tree[12]);
-            assert_eq!(2, tree.pending_updates[12].unwrap());
-            //[7, 7] still not updated but lazy update recorded
-            assert_eq!(31, tree.tree[15]);
-            assert_eq!(2, tree.pending_updates[15].unwrap());
-
-            assert_eq!(2, tree.query(5, 6));
-            //[5,6] updated
-            assert_eq!(2, tree.tree[12]);
-            assert_eq!(None, tree.pending_updates[12]);
-            //[6, 6] still not updated but lazy update recorded
-            assert_eq!(15, tree.tree[13]);
-            assert_eq!(2, tree.pending_updates[13].unwrap());
-            //[7, 7] still not updated but lazy update recorded
-            assert_eq!(2, tree.tree[14]);
-            assert_eq!(2, tree.pending_updates[14].unwrap());
-
-            assert_eq!(2, tree.query(8, 9));
-            //[8, 9] updated
-            assert_eq!(2, tree.tree[16]);
-            assert_eq!(None, tree.pending_updates[16]);
-            //[8, 8] still not updated but lazy update recorded
-            assert_eq!(4, tree.tree[17]);
-            assert_eq!(2, tree.pending_updates[17].unwrap());
-            //[9, 9] still not updated but lazy update recorded
-            assert_eq!(16, tree.tree[18]);
-            assert_eq!(2, tree.pending_updates[18].unwrap());
-
-            assert_eq!(2, tree.query(8, 8));
-
-            assert_eq!(2, tree.query(9, 9));
+        fn test_out_of_bounds_operations() {
+            let mut tree = MinMaxArray::build(&[5, 1, 4, 3, 2]);
+
+            assert_eq!(
+                Err(SegmentTreeError::RangeOutOfBounds {
+                    start: 3,
+                    end: 5,
+                    len: 5
+                }),
+                tree.try_update(3, 5, 0)
+            );
+            assert!(tree.try_max(0, 5).is_err());
+            assert_eq!(Ok(5), tree.try_max(0, 4));
+        }
+
+        #[test]
+        fn test_empty_tree() {
+            let mut tree = MinMaxArray::build(&[]);
+
+            assert_eq!(0, tree.max(1, 0));
+            assert!(tree.try_max(0, 0).is_err());
         }
 
         #[test]
         fn test_range_update_directly_leaf_of_lazy_update() {
-            let mut tree = MaxSegmentTree::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+            let mut tree = MinMaxArray::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+
+            tree.update(4, 9, 2);
+            assert_eq!(2, tree.max(6, 6));
+            assert_eq!(2, tree.max(5, 5));
+        }
+    }
+
+    #[cfg(test)]
+    mod manual_tests {
+        use crate::min_max::{MinMaxArray, solve};
+
+        #[test]
+        fn hands_on_example() {
+            let mut min_max_arr = MinMaxArray::build(&[5, 1, 4, 3, 2]);
+
+            min_max_arr.update(0, 1, 2);
+            assert_eq!(4, min_max_arr.max(1, 3));
+            assert_eq!(2, min_max_arr.max(0, 1));
+        }
+
+        #[test]
+        fn sum_and_min_after_updates() {
+            let mut min_max_arr = MinMaxArray::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+
+            min_max_arr.update(4, 9, 10);
+            //[8, 2, 6, 21, 10, 10, 2, 10, 4, 10]
+            assert_eq!(83, min_max_arr.sum(0, 9));
+            assert_eq!(41, min_max_arr.sum(3, 5));
+            assert_eq!(2, min_max_arr.min(4, 9));
+            min_max_arr.update(0, 9, 3);
+            //[3, 2, 3, 3, 3, 3, 2, 3, 3, 3]
+            assert_eq!(28, min_max_arr.sum(0, 9));
+            assert_eq!(2, min_max_arr.min(0, 1));
+            assert_eq!(3, min_max_arr.min(2, 5));
+            assert_eq!(3, min_max_arr.max(0, 9));
+        }
+
+        #[test]
+        fn snapshot_after_updates() {
+            let mut min_max_arr = MinMaxArray::build(&[8, 2, 6, 21, 18, 15, 2, 31, 4, 16]);
+
+            min_max_arr.update(4, 9, 10);
+            min_max_arr.update(0, 3, 7);
+            assert_eq!(vec![7, 2, 6, 7, 10, 10, 2, 10, 4, 10], min_max_arr.to_vec());
+            assert_eq!(6, min_max_arr.leaf(2));
+            assert_eq!(4, min_max_arr.leaves().filter(|&v| v == 10).count());
+        }
 
-            tree.range_update(4, 9, 2);
-            assert_eq!(2, tree.query(6, 6));
-            assert_eq!(2, tree.query(5, 5));
+        #[test]
+        fn out_of_bounds_operations() {
+            let mut min_max_arr = MinMaxArray::build(&[5, 1, 4]);
+
+            assert!(min_max_arr.try_update(1, 3, 0).is_err());
+            assert!(min_max_arr.try_max(0, 3).is_err());
+            assert_eq!(Ok(5), min_max_arr.try_max(0, 2));
+        }
+
+        #[test]
+        fn solve_rejects_invalid_positions() {
+            assert!(solve("3 1\n5 1 4\n1 1 4\n").is_err());
+            assert!(solve("3 1\n5 1 4\n0 0 2 1\n").is_err());
+            assert_eq!("5\n", solve("3 1\n5 1 4\n1 1 3\n").unwrap());
         }
     }
 
@@ -487,117 +342,15 @@ pub mod min_max {
 
 /* ---------  Problem #2: Is There  ---------------- */
 pub mod is_there {
-    use crate::range::Range;
-    use crate::range::left_right_child_index;
-    use std::collections::HashSet;
+    use segment_tree::{SegmentTreeError, WaveletMatrix};
     use std::error::Error;
     use std::fmt::Display;
 
-    struct HashSetSegmentTree {
-        tree: Vec<Option<HashSet<u32>>>,
-        num_leaf: usize,
-    }
-
-    impl HashSetSegmentTree {
-        pub fn build(a: &[u32]) -> Self {
-            // initialize all with None
-            let mut implicit_tree = vec![None; 2 * a.len() - 1];
-            Self::build_rec(a, &mut implicit_tree, Range::new(0, a.len() - 1), 0);
-            Self {
-                tree: implicit_tree,
-                num_leaf: a.len(),
-            }
-        }
-
-        // since we insert each elem of 'a' in one hashset at each level the cost of construction
-        // is n*log(n) in this case instead of n
-        fn build_rec(
-            a: &[u32],
-            tree: &mut Vec<Option<HashSet<u32>>>,
-            node_segment: Range,
-            index: usize,
-        ) {
-            if node_segment.is_single_point() {
-                let mut hs = HashSet::with_capacity(1);
-                hs.insert(a[node_segment.start]);
-                tree[index] = Some(hs);
-                return;
-            }
-            let middle = node_segment.middle();
-            let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
-            Self::build_rec(
-                a,
-                tree,
-                Range::new(node_segment.start, middle),
-                left_child_index,
-            );
-            Self::build_rec(
-                a,
-                tree,
-                Range::new(middle + 1, node_segment.end),
-                right_child_index,
-            );
-            if let (Some(left), Some(right)) = (&tree[left_child_index], &tree[right_child_index]) {
-                let max_capacity = left.len().max(right.len());
-                let mut new_set = HashSet::with_capacity(max_capacity);
-                // we insert each element in all node until the root and the number of nodes until the root are
-                // log(n) so log(n) insertion. If we consider a cost of O(1) on average for each insertion the overall cost
-                // is log(n) on average for each element -> O(n*log(n)) on average for all the element
-                new_set.extend(left.iter());
-                new_set.extend(right.iter());
-                tree[index] = Some(new_set);
-            }
-        }
-
-        pub fn is_value_present(&self, i: usize, j: usize, k: u32) -> bool {
-            if i > j {
-                return false;
-            }
-            self.value_present_rec(Range::new(i, j), Range::new(0, self.num_leaf - 1), 0, k)
-        }
-
-        fn value_present_rec(
-            &self,
-            query_range: Range,
-            node_segment: Range,
-            index: usize,
-            to_search: u32,
-        ) -> bool {
-            if query_range.no_overlap(node_segment) {
-                return false;
-            }
-            if query_range.total_overlap(node_segment) {
-                // we can check if the element is present within the subset with a cost
-                // of O(1) on average
-                return self.tree[index].as_ref().unwrap().contains(&to_search);
-            }
-            // partial overlap: the answer is present in a descendant of the current node
-            // so we have to perform a recursive descent into the children
-            let (left_child_index, right_child_index) = left_right_child_index(node_segment, index);
-            let middle = node_segment.middle();
-            // check if the value is present in left subtree
-            let left_value = self.value_present_rec(
-                query_range,
-                Range::new(node_segment.start, middle),
-                left_child_index,
-                to_search,
-            );
-            // check if the value is present in right subtree
-            let right_value = self.value_present_rec(
-                query_range,
-                Range::new(middle + 1, node_segment.end),
-                right_child_index,
-                to_search,
-            );
-            left_value || right_value
-        }
-    }
-
     // A wrapper around the segment tree that exposes the problem’s
     // interface (is there query) while hiding the underlying
     // segment-tree implementation
     pub struct SegmentSet {
-        st: HashSetSegmentTree,
+        st: WaveletMatrix,
     }
 
     impl SegmentSet {
@@ -605,16 +358,16 @@ pub mod is_there {
             let diff = Self::create_diff(segments);
             // in segment_coverage[i] is present the number of segment that cover position 'i' and is the
             // prefix sum array of 'diff'
-            let segment_coverage: Vec<u32> = diff
+            let segment_coverage: Vec<i64> = diff
                 .iter()
                 .scan(0, |acc, &v| {
-                    *acc += v;
-                    Some(*acc as u32)
+                    *acc += v as i64;
+                    Some(*acc)
                 })
                 .collect();
-            // create a HashSetSegmentTree over this segment in order to respond
-            // in time O(log(n)) to successive query
-            let st = HashSetSegmentTree::build(segment_coverage.as_slice());
+            // a wavelet matrix over the coverage answers each successive query in
+            // O(log(n)) using n*log(n) bits, instead of a hash set of values per node
+            let st = WaveletMatrix::build(segment_coverage.as_slice());
 
             Self { st }
         }
@@ -638,7 +391,17 @@ pub mod is_there {
         }
 
         pub fn is_there(&self, i: usize, j: usize, k: u32) -> bool {
-            self.st.is_value_present(i, j, k)
+            self.st.position(i, j, k as i64).is_some()
+        }
+
+        /// Like `is_there`, but fails if `[i, j]` ends past the last position.
+        pub fn try_is_there(&self, i: usize, j: usize, k: u32) -> Result<bool, SegmentTreeError> {
+            Ok(self.st.try_position(i, j, k as i64)?.is_some())
+        }
+
+        /// Returns how many positions in `[i, j]` are covered by exactly `k` segments.
+        pub fn count_exactly(&self, i: usize, j: usize, k: u32) -> usize {
+            self.st.count(i, j, k as i64)
         }
     }
 
@@ -662,7 +425,7 @@ pub mod is_there {
             let i: usize = iter.next().ok_or("missing l")?.parse()?;
             let j: usize = iter.next().ok_or("missing r")?.parse()?;
             let k: u32 = iter.next().ok_or("missing val")?.parse()?;
-            let ans: u8 = st.is_there(i, j, k) as u8;
+            let ans: u8 = st.try_is_there(i, j, k)? as u8;
             output.push_str(&format!("{}\n", ans));
         }
 
@@ -682,6 +445,25 @@ pub mod is_there {
             assert!(!segment_set.is_there(1, 3, 1));
             assert!(segment_set.is_there(1, 4, 1));
         }
+
+        #[test]
+        fn out_of_bounds_query_test() {
+            let segment_set = SegmentSet::build(&[(0, 2), (1, 2), (2, 2)]);
+
+            assert_eq!(Ok(true), segment_set.try_is_there(0, 2, 3));
+            assert!(segment_set.try_is_there(1, 3, 3).is_err());
+        }
+
+        #[test]
+        fn count_exactly_test() {
+            // coverage: [2, 4, 3, 2, 1]
+            let segment_set = SegmentSet::build(&[(0, 4), (1, 3), (1, 2), (1, 1), (0, 0)]);
+
+            assert_eq!(2, segment_set.count_exactly(0, 4, 2));
+            assert_eq!(1, segment_set.count_exactly(1, 3, 2));
+            assert_eq!(0, segment_set.count_exactly(0, 4, 0));
+            assert_eq!(1, segment_set.count_exactly(4, 4, 1));
+        }
     }
 
     #[cfg(test)]
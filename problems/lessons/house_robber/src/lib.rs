@@ -32,6 +32,7 @@ impl Solution {
         }
 
         maximum_path_beneficial_from_to(&dag, 0, num_nodes - 1)
+            .unwrap()
             .unwrap()
             .1 as i32
     }
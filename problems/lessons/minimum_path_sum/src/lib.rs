@@ -55,6 +55,7 @@ impl Solution {
 
         dag.add_edge(0, 1, grid[0][0] as u32);
         minimum_paths_cost_from_to(&dag, 0, num_nodes - 1)
+            .unwrap()
             .unwrap()
             .1 as i32
     }
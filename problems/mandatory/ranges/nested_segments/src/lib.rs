@@ -1,6 +1,8 @@
+use fenwick_tree::FenwickTree;
 use segment_tree::IntegerSegmentTree;
+use std::cmp::Reverse;
 use std::collections::HashMap;
-struct Solution;
+pub struct Solution;
 
 impl Solution {
 
@@ -16,7 +18,7 @@ impl Solution {
             segment_tree.add(s, 1);
         }
         // start from "last segment", ie the one that ends last
-        remapped.sort_by(|a, b| b.1.cmp(&a.1));
+        remapped.sort_by_key(|&(_, e, _)| Reverse(e));
 
         for &(s, e, original_index) in &remapped {
             // this counts all segments starting between s+1 and e-1, so the segment starting within the
@@ -30,6 +32,26 @@ impl Solution {
         res
     }
 
+    // Same sweep as nested_segment, but the starting points are counted in a Fenwick tree:
+    // only point updates and range sums are needed, so the n + 1 cells of a Binary Indexed
+    // Tree are enough instead of the 2n - 1 nodes and pending updates of a segment tree
+    pub fn nested_segment_fenwick(segments: &[(i32, i32)]) -> Vec<i32> {
+        let mut res: Vec<i32> = vec![0; segments.len()];
+        let (mut remapped, n_extremes) = Self::remap_segments(segments);
+        let mut fenwick_tree: FenwickTree<i32> = FenwickTree::build_empty(n_extremes);
+        for &(s, _, _) in &remapped {
+            fenwick_tree.add(s, 1);
+        }
+        remapped.sort_by_key(|&(_, e, _)| Reverse(e));
+
+        for &(s, e, original_index) in &remapped {
+            res[original_index] = fenwick_tree.sum(s + 1, e - 1);
+            fenwick_tree.add(s, -1);
+        }
+
+        res
+    }
+
     fn remap_segments(segments: &[(i32, i32)]) -> (Vec<(usize, usize, usize)>, usize) {
         let mut extreme: Vec<i32> = segments.iter().flat_map(|&(s, e)| [s, e]).collect();
         extreme.sort_unstable();
@@ -56,6 +78,7 @@ mod tests {
         let segments = vec![(1, 8), (2, 3), (4, 7), (5, 6)];
         let ans = Solution::nested_segment(&segments);
         assert_eq!(ans, vec![3, 0, 1, 0]);
+        assert_eq!(Solution::nested_segment_fenwick(&segments), ans);
     }
 
     #[test]
@@ -63,6 +86,7 @@ mod tests {
         let segments = vec![(3, 4), (1, 5), (2, 6)];
         let ans = Solution::nested_segment(&segments);
         assert_eq!(ans, vec![0, 1, 1]);
+        assert_eq!(Solution::nested_segment_fenwick(&segments), ans);
     }
 
     #[test]
@@ -70,6 +94,7 @@ mod tests {
         let segments = vec![(1, 2), (3, 4), (5, 6), (7, 8)];
         let ans = Solution::nested_segment(&segments);
         assert_eq!(ans, vec![0, 0, 0, 0]);
+        assert_eq!(Solution::nested_segment_fenwick(&segments), ans);
     }
 
     #[test]
@@ -77,6 +102,7 @@ mod tests {
         let segments = vec![(1, 10), (2, 9), (3, 8), (4, 7), (5, 6)];
         let ans = Solution::nested_segment(&segments);
         assert_eq!(ans, vec![4, 3, 2, 1, 0]);
+        assert_eq!(Solution::nested_segment_fenwick(&segments), ans);
     }
 
     #[test]
@@ -84,6 +110,7 @@ mod tests {
         let segments = vec![(-10, 10), (-5, 0), (-4, -1), (-3, -2), (5, 9)];
         let ans = Solution::nested_segment(&segments);
         assert_eq!(ans, vec![4, 2, 1, 0, 0]);
+        assert_eq!(Solution::nested_segment_fenwick(&segments), ans);
     }
 
     #[test]
@@ -91,5 +118,6 @@ mod tests {
         let segments = vec![(42, 100)];
         let ans = Solution::nested_segment(&segments);
         assert_eq!(ans, vec![0]);
+        assert_eq!(Solution::nested_segment_fenwick(&segments), ans);
     }
 }
@@ -0,0 +1,77 @@
+use mo_algorithm::{MoState, QueryOrder, answer_queries};
+pub struct Solution;
+
+// The power of the window: sum over the values s of cnt[s]^2 * s, where cnt[s] is the number
+// of occurrences of s in the window
+struct Power<'a> {
+    a: &'a [usize],
+    count: Vec<u64>,
+    power: u64,
+}
+
+impl MoState for Power<'_> {
+    type Answer = u64;
+
+    fn add(&mut self, idx: usize) {
+        let s = self.a[idx];
+        // (cnt + 1)^2 * s - cnt^2 * s = (2 * cnt + 1) * s
+        self.power += (2 * self.count[s] + 1) * s as u64;
+        self.count[s] += 1;
+    }
+
+    fn remove(&mut self, idx: usize) {
+        let s = self.a[idx];
+        // cnt^2 * s - (cnt - 1)^2 * s = (2 * cnt - 1) * s
+        self.power -= (2 * self.count[s] - 1) * s as u64;
+        self.count[s] -= 1;
+    }
+
+    fn answer(&self) -> u64 {
+        self.power
+    }
+}
+
+impl Solution {
+    // queries are 1-based and inclusive, as in the statement
+    pub fn powerful_array(a: &[usize], queries: &[(usize, usize)]) -> Vec<u64> {
+        // the power of a window can not be merged from the power of two halves, so segment trees
+        // do not help: Mo's algorithm answers offline moving a window one element at a time
+        let max_value = a.iter().copied().max().unwrap_or(0);
+        let mut state = Power {
+            a,
+            count: vec![0; max_value + 1],
+            power: 0,
+        };
+        let queries: Vec<(usize, usize)> = queries.iter().map(|&(l, r)| (l - 1, r - 1)).collect();
+        answer_queries(&mut state, &queries, QueryOrder::Hilbert)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_1_from_statement() {
+        let ans = Solution::powerful_array(&[1, 2, 1], &[(1, 2), (1, 3)]);
+        assert_eq!(ans, vec![3, 6]);
+    }
+
+    #[test]
+    fn sample_2_from_statement() {
+        let ans = Solution::powerful_array(&[1, 1, 2, 2, 1, 3, 1, 1], &[(2, 7), (1, 6), (2, 7)]);
+        assert_eq!(ans, vec![20, 20, 20]);
+    }
+
+    #[test]
+    fn single_element() {
+        let ans = Solution::powerful_array(&[1_000_000], &[(1, 1)]);
+        assert_eq!(ans, vec![1_000_000]);
+    }
+
+    #[test]
+    fn all_equal() {
+        let ans = Solution::powerful_array(&[5; 10], &[(1, 10), (3, 4)]);
+        assert_eq!(ans, vec![500, 20]);
+    }
+}